@@ -42,7 +42,14 @@ export const ChatMutators = {
       title,
       time,
       msgs,
-    }: { new_id: string; title: string; time: string; msgs: Message[] }
+    }: {
+      new_id: string;
+      source_id?: string;
+      until_id?: string;
+      title: string;
+      time: string;
+      msgs: Message[];
+    }
   ) => {
     const new_chat = {
       id: new_id,
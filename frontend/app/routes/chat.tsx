@@ -147,6 +147,8 @@ export default function Page({ params }: Route.ComponentProps) {
 
       rep.mutate.forkChat({
         new_id,
+        source_id: chat.id,
+        until_id: break_id,
         title: chat.title ?? "Forked chat",
         time: new Date().toISOString(),
         msgs: new_msgs,
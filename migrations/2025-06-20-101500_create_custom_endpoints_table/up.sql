@@ -0,0 +1,11 @@
+CREATE TABLE custom_endpoints (
+  id           BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+  user_id      VARCHAR(255) NOT NULL,
+  name         VARCHAR(50)  NOT NULL,
+  base_url     VARCHAR(1024) NOT NULL,
+  version      INT NOT NULL DEFAULT 1,
+  created_at   TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+  updated_at   TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3) ON UPDATE CURRENT_TIMESTAMP(3),
+
+  UNIQUE KEY uniq_user_name (user_id, name)
+);
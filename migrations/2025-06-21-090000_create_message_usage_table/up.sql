@@ -0,0 +1,15 @@
+CREATE TABLE message_usage (
+  id               BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+  user_id          VARCHAR(255) NOT NULL,
+  chat_id          VARCHAR(255) NOT NULL,
+  message_id       VARCHAR(255) NULL,
+  kind             VARCHAR(32)  NOT NULL,
+  provider         VARCHAR(64)  NOT NULL,
+  model            VARCHAR(255) NOT NULL,
+  input_tokens     INT UNSIGNED NOT NULL DEFAULT 0,
+  output_tokens    INT UNSIGNED NOT NULL DEFAULT 0,
+  reasoning_tokens INT UNSIGNED NOT NULL DEFAULT 0,
+  created_at       TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+
+  INDEX idx_message_usage_user_created (user_id, created_at)
+);
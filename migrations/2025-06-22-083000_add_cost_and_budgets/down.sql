@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS user_budgets;
+
+ALTER TABLE message_usage DROP COLUMN cost_micros;
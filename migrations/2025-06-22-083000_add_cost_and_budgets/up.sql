@@ -0,0 +1,10 @@
+ALTER TABLE message_usage
+  ADD COLUMN cost_micros BIGINT UNSIGNED NULL AFTER reasoning_tokens;
+
+CREATE TABLE user_budgets (
+  user_id              VARCHAR(255) NOT NULL PRIMARY KEY,
+  monthly_limit_micros BIGINT UNSIGNED NOT NULL,
+  version              INT NOT NULL DEFAULT 1,
+  created_at           TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+  updated_at           TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3) ON UPDATE CURRENT_TIMESTAMP(3)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE chats DROP COLUMN system_prompt;
+
+DROP TABLE IF EXISTS system_prompts;
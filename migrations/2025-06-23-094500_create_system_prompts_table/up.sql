@@ -0,0 +1,12 @@
+CREATE TABLE system_prompts (
+  id VARCHAR(255) PRIMARY KEY,
+  user_id VARCHAR(255) NOT NULL,
+  body TEXT NOT NULL,
+  version INT NOT NULL DEFAULT 1,
+  created_at TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+  updated_at TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3)
+    ON UPDATE CURRENT_TIMESTAMP(3),
+  UNIQUE INDEX idx_system_prompts_user_id (user_id)
+);
+
+ALTER TABLE chats ADD COLUMN system_prompt TEXT NULL;
@@ -0,0 +1,3 @@
+ALTER TABLE shared_messages
+  DROP INDEX idx_shared_messages_source_message_id,
+  DROP COLUMN source_message_id;
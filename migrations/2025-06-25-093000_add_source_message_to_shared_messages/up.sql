@@ -0,0 +1,3 @@
+ALTER TABLE shared_messages
+  ADD COLUMN source_message_id VARCHAR(255) NULL AFTER shared_chat_id,
+  ADD INDEX idx_shared_messages_source_message_id (source_message_id);
@@ -0,0 +1 @@
+ALTER TABLE messages ADD COLUMN parts TEXT NULL AFTER reasoning;
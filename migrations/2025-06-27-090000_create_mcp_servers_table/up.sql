@@ -0,0 +1,15 @@
+CREATE TABLE mcp_servers (
+  id            BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+  user_id       VARCHAR(255) NOT NULL,
+  name          VARCHAR(50)  NOT NULL,
+  transport     VARCHAR(16)  NOT NULL,
+  url           VARCHAR(1024) NULL,
+  command       VARCHAR(255) NULL,
+  args          TEXT NULL,
+  enabled_tools TEXT NULL,
+  version       INT NOT NULL DEFAULT 1,
+  created_at    TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+  updated_at    TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3) ON UPDATE CURRENT_TIMESTAMP(3),
+
+  UNIQUE KEY uniq_user_name (user_id, name)
+);
@@ -0,0 +1,5 @@
+ALTER TABLE shared_messages
+  DROP COLUMN citations;
+
+ALTER TABLE messages
+  DROP COLUMN citations;
@@ -0,0 +1,5 @@
+ALTER TABLE messages
+  ADD COLUMN citations TEXT NULL AFTER parts;
+
+ALTER TABLE shared_messages
+  ADD COLUMN citations TEXT NULL AFTER reasoning;
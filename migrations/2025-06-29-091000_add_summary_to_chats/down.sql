@@ -0,0 +1,3 @@
+ALTER TABLE chats
+  DROP COLUMN summary_message_id,
+  DROP COLUMN summary;
@@ -0,0 +1,3 @@
+ALTER TABLE chats
+  ADD COLUMN summary TEXT NULL,
+  ADD COLUMN summary_message_id VARCHAR(255) NULL;
@@ -0,0 +1,6 @@
+ALTER TABLE chats
+  DROP COLUMN top_p,
+  DROP COLUMN temperature,
+  DROP COLUMN reasoning,
+  DROP COLUMN model,
+  DROP COLUMN provider;
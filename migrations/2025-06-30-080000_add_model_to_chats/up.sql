@@ -0,0 +1,6 @@
+ALTER TABLE chats
+  ADD COLUMN provider VARCHAR(255) NULL,
+  ADD COLUMN model VARCHAR(255) NULL,
+  ADD COLUMN reasoning VARCHAR(255) NULL,
+  ADD COLUMN temperature FLOAT NULL,
+  ADD COLUMN top_p FLOAT NULL;
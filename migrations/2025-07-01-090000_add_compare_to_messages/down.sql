@@ -0,0 +1,5 @@
+ALTER TABLE messages
+  DROP INDEX idx_msgs_compare_group,
+  DROP COLUMN selected,
+  DROP COLUMN compare_group,
+  DROP COLUMN model;
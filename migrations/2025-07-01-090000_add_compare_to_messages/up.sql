@@ -0,0 +1,5 @@
+ALTER TABLE messages
+  ADD COLUMN model VARCHAR(255) NULL AFTER citations,
+  ADD COLUMN compare_group VARCHAR(255) NULL AFTER model,
+  ADD COLUMN selected BOOLEAN NOT NULL DEFAULT TRUE AFTER compare_group,
+  ADD INDEX idx_msgs_compare_group (compare_group);
@@ -0,0 +1,3 @@
+ALTER TABLE messages
+  DROP INDEX idx_msgs_parent,
+  DROP COLUMN parent_id;
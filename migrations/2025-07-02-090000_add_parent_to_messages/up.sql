@@ -0,0 +1,15 @@
+ALTER TABLE messages
+  ADD COLUMN parent_id VARCHAR(255) NULL AFTER chat_id,
+  ADD INDEX idx_msgs_parent (parent_id);
+
+-- Existing chats become a single branch, each message answering the one before it. Replies of a
+-- compare that weren't picked hang off the message they answer.
+UPDATE messages m
+JOIN (
+  SELECT id, LAG(id) OVER (PARTITION BY chat_id ORDER BY created_at, id) AS prev_id
+  FROM messages
+  WHERE selected = TRUE
+) p ON p.id = m.id
+SET m.parent_id = p.prev_id;
+
+UPDATE messages SET parent_id = compare_group WHERE compare_group IS NOT NULL;
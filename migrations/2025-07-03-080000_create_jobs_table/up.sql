@@ -0,0 +1,19 @@
+CREATE TABLE jobs (
+  id            VARCHAR(255) PRIMARY KEY,
+  kind          VARCHAR(32)  NOT NULL,
+  user_id       VARCHAR(255) NOT NULL,
+  chat_id       VARCHAR(255) NOT NULL,
+  payload       LONGTEXT     NOT NULL,
+  status        VARCHAR(16)  NOT NULL DEFAULT 'pending',
+  attempts      INT NOT NULL DEFAULT 0,
+  max_attempts  INT NOT NULL,
+  run_at        TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+  locked_by     VARCHAR(255) NULL,
+  locked_until  TIMESTAMP(3) NULL,
+  last_error    TEXT NULL,
+  created_at    TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+  updated_at    TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3) ON UPDATE CURRENT_TIMESTAMP(3),
+
+  INDEX idx_jobs_status_run_at (status, run_at),
+  INDEX idx_jobs_chat (chat_id)
+);
@@ -1,9 +1,6 @@
-use std::sync::Arc;
-
 use anyhow::{Context, Result};
-use futures_util::StreamExt;
 use reqwest::{
-    Client, RequestBuilder,
+    RequestBuilder,
     header::{HeaderMap, HeaderValue},
 };
 use reqwest_eventsource::{Event, EventSource};
@@ -15,13 +12,33 @@ use uuid::Uuid;
 
 use super::{model::AnthropicModel, request::AnthropicRequest};
 use crate::{
-    ai::handler::{StreamResult, create_title_prompt, done, send_error, send_text_delta},
-    models::message::Message,
-    services::sse_manager::SseManager,
+    ai::{
+        handler::{
+            Completion, StreamResult, StreamStep, cancel_stream, done, next_event, send_error,
+            send_reasoning_delta, send_text_delta,
+        },
+        provider::StreamRequest,
+        tools::ToolCall,
+        usage::TokenUsage,
+    },
+    configuration::ProviderSettings,
 };
 
-const BASE: &str = "https://api.anthropic.com/v1/messages";
-const VERSION: &str = "2023-06-01";
+const MESSAGES_PATH: &str = "messages";
+const MODELS_PATH: &str = "models?limit=1000";
+
+fn headers(settings: &ProviderSettings, api_key: &SecretString) -> Result<HeaderMap> {
+    let version = settings
+        .api_version
+        .as_deref()
+        .context("providers.anthropic.api_version is not configured")?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert("x-api-key", HeaderValue::from_str(api_key.expose_secret())?);
+    headers.insert("anthropic-version", HeaderValue::from_str(version)?);
+
+    Ok(headers)
+}
 
 #[derive(Debug, serde::Deserialize)]
 pub struct Response {
@@ -37,24 +54,52 @@ pub struct Response {
     pub usage: Usage,
 }
 
-pub async fn generate_title(
+#[derive(Debug, Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelList {
+    data: Vec<ModelEntry>,
+}
+
+pub async fn list_models(
+    settings: &ProviderSettings,
     api_key: &SecretString,
-    first_body: &str,
-    model: AnthropicModel,
-) -> Result<String> {
-    let model = model.to_string();
-    let prompt = create_title_prompt(first_body);
-    let req = AnthropicRequest::prompt(&model, &prompt);
+) -> Result<Vec<String>> {
+    let list: ModelList = settings
+        .client()?
+        .get(settings.endpoint(MODELS_PATH))
+        .headers(headers(settings, api_key)?)
+        .timeout(settings.request_timeout())
+        .send()
+        .await
+        .context("Anthropic model list request failed")?
+        .error_for_status()
+        .context("Anthropic model list HTTP error")?
+        .json()
+        .await
+        .context("Anthropic model list JSON decode failed")?;
 
-    let mut headers = HeaderMap::new();
+    Ok(list.data.into_iter().map(|m| m.id).collect())
+}
 
-    headers.insert("x-api-key", HeaderValue::from_str(api_key.expose_secret())?);
-    headers.insert("anthropic-version", HeaderValue::from_static(VERSION));
+pub async fn complete(
+    settings: &ProviderSettings,
+    api_key: &SecretString,
+    prompt: &str,
+    max_tokens: u32,
+    model: AnthropicModel,
+) -> Result<Completion> {
+    let model = model.to_string();
+    let req = AnthropicRequest::prompt(&model, prompt, max_tokens);
 
-    let client = Client::new();
-    let resp: Response = client
-        .post(BASE)
-        .headers(headers)
+    let resp: Response = settings
+        .client()?
+        .post(settings.endpoint(MESSAGES_PATH))
+        .headers(headers(settings, api_key)?)
+        .timeout(settings.request_timeout())
         .json(&req)
         .send()
         .await?
@@ -68,12 +113,30 @@ pub async fn generate_title(
         .find(|b| b.kind == "text")
         .context("no text block in claude response")?;
 
-    Ok(text_block.text.trim().to_owned())
+    Ok(Completion {
+        text: text_block.text.trim().to_owned(),
+        model,
+        usage: Some(resp.usage.into()),
+    })
 }
 
 #[derive(Debug, Deserialize)]
-pub struct TextDelta {
-    pub text: String,
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BlockDelta {
+    TextDelta {
+        text: String,
+    },
+    ThinkingDelta {
+        thinking: String,
+    },
+    SignatureDelta {
+        signature: String,
+    },
+    InputJsonDelta {
+        partial_json: String,
+    },
+    #[serde(other)]
+    Other,
 }
 
 #[derive(Debug, Deserialize)]
@@ -82,17 +145,42 @@ pub struct Usage {
     pub input_tokens: Option<u32>,
 }
 
+// Thinking tokens are billed as output and not reported separately.
+impl From<Usage> for TokenUsage {
+    fn from(value: Usage) -> Self {
+        Self {
+            input_tokens: value.input_tokens.unwrap_or_default(),
+            output_tokens: value.output_tokens.unwrap_or_default(),
+            reasoning_tokens: 0,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct MessageHeader {
     pub id: String,
     pub role: String,
+    pub usage: Option<Usage>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ContentBlock {
     #[serde(rename = "type")]
     pub kind: String,
+    // thinking blocks carry `thinking` instead
+    #[serde(default)]
     pub text: String,
+    // set on tool_use blocks
+    pub id: Option<String>,
+    pub name: Option<String>,
+}
+
+// A tool_use block whose input is still streaming in.
+struct PendingCall {
+    index: usize,
+    id: String,
+    name: String,
+    input: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -108,7 +196,7 @@ pub enum StreamEvent {
     Ping,
     ContentBlockDelta {
         index: usize,
-        delta: TextDelta,
+        delta: BlockDelta,
     },
     ContentBlockStop {
         index: usize,
@@ -121,28 +209,63 @@ pub enum StreamEvent {
 }
 
 pub async fn stream(
-    api_key: SecretString,
-    sse: Arc<SseManager>,
-    user_id: String,
-    chat_id: String,
-    model: AnthropicModel,
-    history: Vec<Message>,
+    settings: &ProviderSettings,
+    req: &StreamRequest,
 ) -> Result<Option<StreamResult>> {
-    let model = model.to_string();
-    let req_body = AnthropicRequest::chat(&model, &history, true);
+    let StreamRequest {
+        api_key,
+        sse,
+        user_id,
+        chat_id,
+        model,
+        effort,
+        sampling,
+        capabilities,
+        system_prompt,
+        history,
+        images,
+        tools,
+        turns,
+        cancel,
+        ..
+    } = req;
 
-    let mut headers = HeaderMap::new();
-    headers.insert("x-api-key", HeaderValue::from_str(api_key.expose_secret())?);
-    headers.insert("anthropic-version", HeaderValue::from_static(VERSION));
+    let req_body = AnthropicRequest::chat(
+        model,
+        history,
+        images,
+        system_prompt,
+        true,
+        effort.clone(),
+        capabilities.max_output_tokens,
+    )
+    .with_sampling(*sampling)
+    .with_tools(tools, turns);
 
-    let http_req: RequestBuilder = Client::new().post(BASE).headers(headers).json(&req_body);
+    let http_req: RequestBuilder = settings
+        .client()?
+        .post(settings.endpoint(MESSAGES_PATH))
+        .headers(headers(settings, api_key)?)
+        .json(&req_body);
 
     let mut es = EventSource::new(http_req).context("Anthropic SSE connect")?;
 
     let msg_id = Uuid::new_v4().to_string();
     let mut full_text = String::new();
+    let mut full_reasoning = String::new();
+    let mut signature = String::new();
+    let mut pending: Vec<PendingCall> = Vec::new();
+    let mut usage = TokenUsage::default();
+
+    loop {
+        let ev = match next_event(&mut es, cancel).await {
+            StreamStep::Event(ev) => ev,
+            StreamStep::Finished => break,
+            StreamStep::Cancelled => {
+                return Ok(cancel_stream(sse, user_id, chat_id, full_text, full_reasoning).await);
+            }
+        };
 
-    while let Some(ev) = es.next().await {
         match ev {
             Ok(Event::Open) => info!("Anthropic SSE opened"),
             Ok(Event::Message(msg)) => {
@@ -155,29 +278,75 @@ pub async fn stream(
 
                 let evt: StreamEvent = serde_json::from_str(&msg.data)?;
                 match evt {
-                    StreamEvent::ContentBlockDelta { delta, .. } => {
-                        if !delta.text.is_empty() {
-                            send_text_delta(&sse, &user_id, &chat_id, &delta.text).await;
-                            full_text.push_str(&delta.text);
+                    StreamEvent::ContentBlockStart {
+                        index,
+                        content_block,
+                    } if content_block.kind == "tool_use" => {
+                        pending.push(PendingCall {
+                            index,
+                            id: content_block.id.unwrap_or_default(),
+                            name: content_block.name.unwrap_or_default(),
+                            input: String::new(),
+                        });
+                    }
+                    StreamEvent::ContentBlockDelta { index, delta } => match delta {
+                        BlockDelta::TextDelta { text } if !text.is_empty() => {
+                            send_text_delta(sse, user_id, chat_id, &text).await;
+                            full_text.push_str(&text);
+                        }
+                        BlockDelta::ThinkingDelta { thinking } if !thinking.is_empty() => {
+                            send_reasoning_delta(sse, user_id, chat_id, &thinking).await;
+                            full_reasoning.push_str(&thinking);
+                        }
+                        BlockDelta::SignatureDelta { signature: s } => signature.push_str(&s),
+                        BlockDelta::InputJsonDelta { partial_json } => {
+                            if let Some(call) = pending.iter_mut().find(|c| c.index == index) {
+                                call.input.push_str(&partial_json);
+                            }
+                        }
+                        _ => {}
+                    },
+                    StreamEvent::MessageStart { message } => {
+                        if let Some(u) = message.usage {
+                            usage.input_tokens = u.input_tokens.unwrap_or_default();
                         }
                     }
+                    // output_tokens is cumulative on message_delta
+                    StreamEvent::MessageDelta { usage: Some(u), .. } => {
+                        usage.output_tokens = u.output_tokens.unwrap_or(usage.output_tokens);
+                    }
                     StreamEvent::MessageStop => break,
                     StreamEvent::Ping => {}
                     _ => {}
                 }
             }
             Err(e) => {
-                send_error(&sse, &user_id, &chat_id, &e.to_string()).await;
-                return Ok(None);
+                send_error(sse, user_id, chat_id, &e.to_string()).await;
+                return Err(e.into());
             }
         }
     }
 
-    done(&sse, &user_id, &chat_id, &msg_id).await;
+    let tool_calls: Vec<ToolCall> = pending
+        .into_iter()
+        .map(|c| ToolCall {
+            arguments: ToolCall::parse_arguments(&c.input),
+            id: c.id,
+            name: c.name,
+            signature: None,
+        })
+        .collect();
+
+    if tool_calls.is_empty() {
+        done(sse, user_id, chat_id, &msg_id).await;
+    }
 
     Ok(Some(StreamResult {
         msg_id,
         content: full_text,
-        reasoning: None,
+        reasoning: (!full_reasoning.is_empty()).then_some(full_reasoning),
+        reasoning_signature: (!signature.is_empty()).then_some(signature),
+        tool_calls,
+        usage: Some(usage),
     }))
 }
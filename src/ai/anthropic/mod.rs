@@ -1,3 +1,4 @@
 pub mod handler;
 pub mod model;
+pub mod provider;
 pub mod request;
@@ -1,7 +1,14 @@
 use serde::{Deserialize, Serialize};
-use strum::{Display, EnumString};
+use strum::{Display, EnumString, VariantNames};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, Display, Serialize, Deserialize)]
+use crate::ai::{
+    pricing::ModelPrice,
+    registry::{Capabilities, TEXT_IMAGE_FILE},
+};
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, EnumString, Display, VariantNames, Serialize, Deserialize,
+)]
 #[strum(ascii_case_insensitive)]
 pub enum AnthropicModel {
     #[serde(rename = "claude-3-5-haiku-latest")]
@@ -16,3 +23,23 @@ pub enum AnthropicModel {
     #[strum(serialize = "claude-opus-4-20250514")]
     Opus4,
 }
+
+impl AnthropicModel {
+    pub fn capabilities(self) -> Capabilities {
+        match self {
+            Self::Haiku35 => Capabilities::new(200_000, TEXT_IMAGE_FILE, ModelPrice::new(0.8, 4.0))
+                .with_max_output(8_192)
+                .with_tools(),
+            Self::Sonnet4 => {
+                Capabilities::new(200_000, TEXT_IMAGE_FILE, ModelPrice::new(3.0, 15.0))
+                    .with_max_output(64_000)
+                    .with_reasoning()
+                    .with_tools()
+            }
+            Self::Opus4 => Capabilities::new(200_000, TEXT_IMAGE_FILE, ModelPrice::new(15.0, 75.0))
+                .with_max_output(32_000)
+                .with_reasoning()
+                .with_tools(),
+        }
+    }
+}
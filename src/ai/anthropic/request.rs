@@ -1,10 +1,71 @@
-use crate::models::message::Message;
+use crate::{
+    ai::{
+        attachment::ImageMap,
+        provider::{AiProvider, Sampling},
+        reasoning::EffortLevel,
+        tools::{ToolDefinition, ToolTurn},
+    },
+    models::message::Message,
+};
 use serde::Serialize;
+use serde_json::Value;
+
+// Used when the registry doesn't know the model's output limit.
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+// Room kept for the answer when the thinking budget would take up the whole output.
+const MIN_ANSWER_TOKENS: u32 = 1024;
 
 #[derive(Debug, Serialize)]
 pub struct AnthropicMessage<'a> {
     pub role: &'a str,
-    pub content: &'a str,
+    pub content: MessageContent<'a>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum MessageContent<'a> {
+    Text(&'a str),
+    Blocks(Vec<ContentPart<'a>>),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart<'a> {
+    Text {
+        text: &'a str,
+    },
+    Image {
+        source: ImageSource<'a>,
+    },
+    Thinking {
+        thinking: &'a str,
+        signature: &'a str,
+    },
+    ToolUse {
+        id: &'a str,
+        name: &'a str,
+        input: &'a Value,
+    },
+    ToolResult {
+        tool_use_id: &'a str,
+        content: &'a str,
+        is_error: bool,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnthropicTool<'a> {
+    pub name: &'a str,
+    pub description: &'a str,
+    pub input_schema: &'a Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImageSource<'a> {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub media_type: &'a str,
+    pub data: &'a str,
 }
 
 #[derive(Debug, Serialize)]
@@ -16,34 +77,150 @@ pub struct AnthropicRequest<'a> {
     pub system: Option<&'a str>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<Thinking>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<AnthropicTool<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Thinking {
+    Enabled { budget_tokens: u32 },
 }
 
 impl<'a> AnthropicRequest<'a> {
-    pub fn chat(model: &'a str, history: &'a [Message], stream: bool) -> Self {
+    pub fn chat(
+        model: &'a str,
+        history: &'a [Message],
+        images: &'a ImageMap,
+        system: &'a str,
+        stream: bool,
+        effort: Option<EffortLevel>,
+        max_output_tokens: Option<u32>,
+    ) -> Self {
+        // the thinking budget counts towards max_tokens
+        let max_tokens = max_output_tokens.unwrap_or(DEFAULT_MAX_TOKENS);
+        let budget_tokens = effort.map(|e| {
+            e.thinking_budget()
+                .min(max_tokens.saturating_sub(MIN_ANSWER_TOKENS))
+        });
+
         Self {
             model,
-            max_tokens: 1024,
-            system: None,
+            max_tokens,
+            system: Some(system),
             stream: Some(stream),
+            thinking: budget_tokens.map(|budget_tokens| Thinking::Enabled { budget_tokens }),
+            temperature: None,
+            top_p: None,
+            tools: Vec::new(),
             messages: history
                 .iter()
                 .map(|m| AnthropicMessage {
                     role: &m.role,
-                    content: &m.body,
+                    content: match images.get(&m.id) {
+                        Some(images) => MessageContent::Blocks(
+                            images
+                                .iter()
+                                .map(|i| ContentPart::Image {
+                                    source: ImageSource {
+                                        kind: "base64",
+                                        media_type: &i.media_type,
+                                        data: &i.data,
+                                    },
+                                })
+                                .chain(std::iter::once(ContentPart::Text { text: &m.body }))
+                                .collect(),
+                        ),
+                        None => MessageContent::Text(&m.body),
+                    },
                 })
                 .collect(),
         }
     }
 
-    pub fn prompt(model: &'a str, text: &'a str) -> Self {
+    // Extended thinking only runs at the default temperature, and newer models refuse temperature
+    // and top_p together, so top_p is only sent on its own. A chat's temperature can be set for
+    // another provider's range, so it is capped to Anthropic's.
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        if self.thinking.is_none() {
+            self.temperature = sampling
+                .temperature
+                .map(|t| t.min(AiProvider::Anthropic.max_temperature()));
+            self.top_p = sampling.top_p.filter(|_| sampling.temperature.is_none());
+        }
+        self
+    }
+
+    // Each round is the assistant's tool_use blocks followed by a user message with the results.
+    pub fn with_tools(mut self, tools: &'a [ToolDefinition], turns: &'a [ToolTurn]) -> Self {
+        self.tools = tools
+            .iter()
+            .map(|t| AnthropicTool {
+                name: &t.name,
+                description: &t.description,
+                input_schema: &t.parameters,
+            })
+            .collect();
+
+        for turn in turns {
+            let thinking = turn
+                .reasoning
+                .as_deref()
+                .zip(turn.reasoning_signature.as_deref())
+                .map(|(thinking, signature)| ContentPart::Thinking {
+                    thinking,
+                    signature,
+                });
+            let text = (!turn.text.is_empty()).then(|| ContentPart::Text { text: &turn.text });
+            let calls = turn.calls.iter().map(|c| ContentPart::ToolUse {
+                id: &c.id,
+                name: &c.name,
+                input: &c.arguments,
+            });
+
+            self.messages.push(AnthropicMessage {
+                role: "assistant",
+                content: MessageContent::Blocks(
+                    thinking.into_iter().chain(text).chain(calls).collect(),
+                ),
+            });
+            self.messages.push(AnthropicMessage {
+                role: "user",
+                content: MessageContent::Blocks(
+                    turn.outputs
+                        .iter()
+                        .map(|o| ContentPart::ToolResult {
+                            tool_use_id: &o.call_id,
+                            content: &o.content,
+                            is_error: o.is_error,
+                        })
+                        .collect(),
+                ),
+            });
+        }
+
+        self
+    }
+
+    pub fn prompt(model: &'a str, text: &'a str, max_tokens: u32) -> Self {
         Self {
             model,
-            max_tokens: 32,
+            max_tokens,
             system: None,
             stream: None,
+            thinking: None,
+            temperature: None,
+            top_p: None,
+            tools: Vec::new(),
             messages: vec![AnthropicMessage {
                 role: "user",
-                content: text,
+                content: MessageContent::Text(text),
             }],
         }
     }
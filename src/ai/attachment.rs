@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use base64::{Engine, engine::general_purpose};
+use thiserror::Error;
+
+use crate::{
+    app::AppState,
+    models::{attachment::Attachment, message::Message},
+};
+
+use super::registry::Capabilities;
+
+// Documents may fill at most half of the context window, the rest is left for the conversation
+// and the answer.
+const DOCUMENT_CONTEXT_SHARE: u32 = 2;
+// Used when the registry doesn't know the model's context window.
+const DEFAULT_DOCUMENT_TOKENS: u32 = 8_000;
+// Rough average for English text and code, good enough for a limit.
+const CHARS_PER_TOKEN: usize = 4;
+
+#[derive(Debug, Error)]
+pub enum AttachmentError {
+    #[error(
+        "Attached documents are too large for {model}: about {tokens} tokens, the limit is {limit}."
+    )]
+    DocumentsTooLarge {
+        model: String,
+        tokens: u32,
+        limit: u32,
+    },
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+pub struct ImageInput {
+    pub media_type: String,
+    // base64 encoded
+    pub data: String,
+}
+
+impl ImageInput {
+    pub fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.media_type, self.data)
+    }
+}
+
+// Images of the conversation, keyed by the id of the message they were sent with.
+pub type ImageMap = HashMap<String, Vec<ImageInput>>;
+
+pub async fn load_images(state: &AppState, attachments: &[Attachment]) -> Result<ImageMap> {
+    let service = &state.service_container.attachment_service;
+    let mut images = ImageMap::new();
+
+    for attachment in attachments.iter().filter(|a| a.is_image()) {
+        let Some(message_id) = &attachment.message_id else {
+            continue;
+        };
+
+        let data = service.read_blob(attachment).await?;
+        images
+            .entry(message_id.clone())
+            .or_default()
+            .push(ImageInput {
+                media_type: attachment.content_type.clone(),
+                data: general_purpose::STANDARD.encode(data),
+            });
+    }
+
+    Ok(images)
+}
+
+// Appends the text extracted from each document to the body of the message it was sent with.
+pub async fn inline_documents(
+    state: &AppState,
+    mut messages: Vec<Message>,
+    attachments: &[Attachment],
+    capabilities: &Capabilities,
+    model: &str,
+) -> Result<Vec<Message>, AttachmentError> {
+    let service = &state.service_container.attachment_service;
+    let limit = capabilities
+        .context_window
+        .map(|w| w / DOCUMENT_CONTEXT_SHARE)
+        .unwrap_or(DEFAULT_DOCUMENT_TOKENS);
+    let mut tokens = 0;
+
+    for attachment in attachments.iter().filter(|a| a.is_document()) {
+        let Some(message) = messages
+            .iter_mut()
+            .find(|m| attachment.message_id.as_deref() == Some(m.id.as_str()))
+        else {
+            continue;
+        };
+
+        let text = service.read_text(attachment).await?;
+        tokens += (text.chars().count() / CHARS_PER_TOKEN) as u32;
+        if tokens > limit {
+            return Err(AttachmentError::DocumentsTooLarge {
+                model: model.to_owned(),
+                tokens,
+                limit,
+            });
+        }
+
+        message.body.push_str(&format!(
+            "\n\n<document name=\"{}\">\n{}\n</document>",
+            attachment.filename, text
+        ));
+    }
+
+    Ok(messages)
+}
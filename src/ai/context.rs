@@ -0,0 +1,164 @@
+use crate::{
+    ai::{
+        attachment::ImageMap, provider::StreamRequest, registry::Capabilities,
+        tools::ToolDefinition, usage::TokenUsage,
+    },
+    models::message::Message,
+};
+
+// A rough estimate, but the same for every provider and close enough for English text and code.
+const CHARS_PER_TOKEN: usize = 4;
+// Role markers and separators the providers wrap each message in.
+const MESSAGE_OVERHEAD: u32 = 4;
+const IMAGE_TOKENS: u32 = 1_000;
+// Left for the reply when the registry doesn't know the model's output limit.
+const DEFAULT_OUTPUT_RESERVE: u32 = 4_096;
+// The estimate can be off in either direction, so a tenth of the window is kept free.
+const SAFETY_DIVISOR: u32 = 10;
+// Dropped messages are cut to this before summarizing so one huge paste can't crowd out the rest.
+const MAX_SUMMARY_MESSAGE_CHARS: usize = 4_000;
+const MAX_SUMMARY_INPUT_CHARS: usize = 200_000;
+
+pub const SUMMARY_MAX_TOKENS: u32 = 1_024;
+
+pub fn estimate_tokens(text: &str) -> u32 {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN) as u32
+}
+
+pub fn message_tokens(message: &Message, images: &ImageMap) -> u32 {
+    let text = estimate_tokens(&message.body)
+        + message.reasoning.as_deref().map_or(0, estimate_tokens)
+        + message.parts.as_deref().map_or(0, estimate_tokens);
+    let images = images.get(&message.id).map_or(0, Vec::len) as u32 * IMAGE_TOKENS;
+
+    text + images + MESSAGE_OVERHEAD
+}
+
+// Tokens left for the history once everything else sent with it is accounted for. None when the
+// model's context window is unknown, the history is then sent as is.
+pub fn history_budget(
+    capabilities: &Capabilities,
+    system_prompt: &str,
+    tools: &[ToolDefinition],
+) -> Option<u32> {
+    let window = capabilities.context_window?;
+    let output = capabilities
+        .max_output_tokens
+        .unwrap_or(DEFAULT_OUTPUT_RESERVE)
+        .min(window / 2);
+    let tools = tool_tokens(tools);
+
+    Some(
+        window
+            .saturating_sub(window / SAFETY_DIVISOR)
+            .saturating_sub(output)
+            .saturating_sub(estimate_tokens(system_prompt))
+            .saturating_sub(tools)
+            .saturating_sub(SUMMARY_MAX_TOKENS),
+    )
+}
+
+// For a request the provider didn't report usage for, a cancelled one among them: all of the
+// input, and whatever came back before it stopped.
+pub fn estimate_usage(req: &StreamRequest, content: &str, reasoning: Option<&str>) -> TokenUsage {
+    let history: u32 = req
+        .history
+        .iter()
+        .map(|m| message_tokens(m, &req.images))
+        .sum();
+    let turns: u32 = req
+        .turns
+        .iter()
+        .map(|t| {
+            estimate_tokens(&t.text)
+                + t.reasoning.as_deref().map_or(0, estimate_tokens)
+                + t.calls
+                    .iter()
+                    .map(|c| estimate_tokens(&c.arguments.to_string()))
+                    .sum::<u32>()
+                + t.outputs
+                    .iter()
+                    .map(|o| estimate_tokens(&o.content))
+                    .sum::<u32>()
+        })
+        .sum();
+    let reasoning = reasoning.map_or(0, estimate_tokens);
+
+    TokenUsage {
+        input_tokens: estimate_tokens(&req.system_prompt)
+            + tool_tokens(&req.tools)
+            + history
+            + turns,
+        output_tokens: estimate_tokens(content) + reasoning,
+        reasoning_tokens: reasoning,
+    }
+}
+
+fn tool_tokens(tools: &[ToolDefinition]) -> u32 {
+    tools
+        .iter()
+        .map(|t| {
+            estimate_tokens(&t.name)
+                + estimate_tokens(&t.description)
+                + estimate_tokens(&t.parameters.to_string())
+        })
+        .sum()
+}
+
+// Splits the history into the newest messages that fit the budget and the older ones that don't,
+// both oldest first. The last message is always kept, and the kept history starts with a user
+// message since not every provider accepts one that opens with the assistant.
+pub fn split_history(
+    mut messages: Vec<Message>,
+    budget: u32,
+    images: &ImageMap,
+) -> (Vec<Message>, Vec<Message>) {
+    let mut used = 0;
+    let mut start = messages.len();
+    while start > 0 {
+        let tokens = message_tokens(&messages[start - 1], images);
+        if start < messages.len() && used + tokens > budget {
+            break;
+        }
+        used += tokens;
+        start -= 1;
+    }
+
+    while start + 1 < messages.len() && messages[start].role != "user" {
+        start += 1;
+    }
+
+    let kept = messages.split_off(start);
+    (kept, messages)
+}
+
+pub fn create_summary_prompt(previous: Option<&str>, messages: &[Message]) -> String {
+    // Newest first so that when the input is cut, it is the oldest turns that go.
+    let mut total = 0;
+    let mut turns = Vec::new();
+    for message in messages.iter().rev() {
+        let body: String = message
+            .body
+            .chars()
+            .take(MAX_SUMMARY_MESSAGE_CHARS)
+            .collect();
+        total += body.len();
+        if total > MAX_SUMMARY_INPUT_CHARS {
+            break;
+        }
+        turns.push(format!("{}: {}", message.role, body));
+    }
+    turns.reverse();
+
+    let previous = match previous {
+        Some(summary) => format!("Summary of the conversation so far:\n{summary}\n\n"),
+        None => String::new(),
+    };
+
+    format!(
+        "Write a concise summary of the following conversation between a user and an assistant. \
+        Keep the facts, decisions, names, code identifiers and open questions needed to continue \
+        it, and leave out pleasantries. Respond with the summary only.\n\n{previous}Conversation:\n{}",
+        turns.join("\n\n")
+    )
+}
@@ -0,0 +1,224 @@
+use anyhow::{Context, Result};
+use reqwest::RequestBuilder;
+use reqwest_eventsource::{Event, EventSource};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use tracing::info;
+use uuid::Uuid;
+
+use super::request::ChatCompletionRequest;
+use crate::{
+    ai::{
+        handler::{
+            Completion, StreamResult, StreamStep, cancel_stream, done, next_event, send_error,
+            send_text_delta,
+        },
+        provider::StreamRequest,
+        usage::{CompletionUsage, TokenUsage},
+    },
+    configuration::ProviderSettings,
+};
+
+const COMPLETIONS_PATH: &str = "chat/completions";
+const MODELS_PATH: &str = "models";
+
+#[derive(Debug, Deserialize)]
+pub struct Choice {
+    pub message: AssistantMessage,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssistantMessage {
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompletionResponse {
+    pub choices: Vec<Choice>,
+    pub usage: Option<CompletionUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModelEntry {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModelList {
+    pub data: Vec<ModelEntry>,
+}
+
+// Local servers are often run without auth, so the key is only sent when one was configured.
+fn authorize(req: RequestBuilder, api_key: &SecretString) -> RequestBuilder {
+    match api_key.expose_secret() {
+        "" => req,
+        key => req.bearer_auth(key),
+    }
+}
+
+pub async fn list_models(
+    settings: &ProviderSettings,
+    api_key: &SecretString,
+) -> Result<Vec<String>> {
+    let req = settings
+        .client()?
+        .get(settings.endpoint(MODELS_PATH))
+        .timeout(settings.request_timeout());
+
+    let list: ModelList = authorize(req, api_key)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(list.data.into_iter().map(|m| m.id).collect())
+}
+
+pub async fn complete(
+    settings: &ProviderSettings,
+    api_key: &SecretString,
+    prompt: &str,
+    max_tokens: u32,
+    model: &str,
+) -> Result<Completion> {
+    let req = ChatCompletionRequest::prompt(model, prompt, max_tokens);
+
+    let http_req = settings
+        .client()?
+        .post(settings.endpoint(COMPLETIONS_PATH))
+        .timeout(settings.request_timeout())
+        .json(&req);
+
+    let resp: CompletionResponse = authorize(http_req, api_key)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let text = resp
+        .choices
+        .first()
+        .context("no choices in completion response")?
+        .message
+        .content
+        .trim()
+        .to_owned();
+
+    Ok(Completion {
+        text,
+        model: model.to_owned(),
+        usage: resp.usage.map(TokenUsage::from),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Delta {
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChunkChoice {
+    pub delta: Delta,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamChunk {
+    #[serde(default)]
+    pub choices: Vec<ChunkChoice>,
+    pub usage: Option<CompletionUsage>,
+}
+
+pub async fn stream(
+    settings: &ProviderSettings,
+    req: &StreamRequest,
+) -> Result<Option<StreamResult>> {
+    let StreamRequest {
+        api_key,
+        sse,
+        user_id,
+        chat_id,
+        model,
+        sampling,
+        system_prompt,
+        history,
+        cancel,
+        ..
+    } = req;
+
+    let req_body =
+        ChatCompletionRequest::chat(model, history, system_prompt, true).with_sampling(*sampling);
+
+    let http_req = settings
+        .client()?
+        .post(settings.endpoint(COMPLETIONS_PATH))
+        .json(&req_body);
+
+    let mut es =
+        EventSource::new(authorize(http_req, api_key)).context("Custom endpoint SSE connect")?;
+
+    let msg_id = Uuid::new_v4().to_string();
+    let mut full_text = String::new();
+    let mut usage: Option<TokenUsage> = None;
+    let mut finished = false;
+
+    loop {
+        let ev = match next_event(&mut es, cancel).await {
+            StreamStep::Event(ev) => ev,
+            StreamStep::Finished => break,
+            StreamStep::Cancelled => {
+                return Ok(cancel_stream(sse, user_id, chat_id, full_text, String::new()).await);
+            }
+        };
+
+        match ev {
+            Ok(Event::Open) => info!("Custom endpoint SSE opened"),
+            Ok(Event::Message(msg)) => {
+                let data = msg.data.trim();
+                if data == "[DONE]" {
+                    break;
+                }
+                if data.is_empty() {
+                    continue;
+                }
+
+                let chunk: StreamChunk = serde_json::from_str(data)?;
+                if let Some(u) = chunk.usage {
+                    usage = Some(u.into());
+                }
+
+                if let Some(choice) = chunk.choices.first() {
+                    if let Some(content) = &choice.delta.content {
+                        send_text_delta(sse, user_id, chat_id, content).await;
+                        full_text.push_str(content);
+                    }
+
+                    // usage arrives in a separate chunk after the finish reason, so keep
+                    // reading until [DONE]
+                    if matches!(&choice.finish_reason, Some(r) if r == "stop") {
+                        finished = true;
+                    }
+                }
+            }
+            // The server may close the stream without [DONE] once the reply is complete.
+            Err(reqwest_eventsource::Error::StreamEnded) if finished => break,
+            Err(e) => {
+                send_error(sse, user_id, chat_id, &e.to_string()).await;
+                return Err(e.into());
+            }
+        }
+    }
+
+    done(sse, user_id, chat_id, &msg_id).await;
+
+    Ok(Some(StreamResult {
+        msg_id,
+        content: full_text,
+        reasoning: None,
+        reasoning_signature: None,
+        tool_calls: Vec::new(),
+        usage,
+    }))
+}
@@ -0,0 +1,60 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use secrecy::SecretString;
+
+use crate::{
+    ai::{
+        handler::{Completion, StreamResult},
+        provider::{ChatProvider, StreamRequest},
+    },
+    configuration::ProviderSettings,
+};
+
+use super::handler;
+
+// An OpenAI compatible chat completions server registered by the user. Models are whatever the
+// server exposes, there is no known list to fall back on.
+pub struct CustomProvider {
+    settings: ProviderSettings,
+    title_model: String,
+}
+
+impl CustomProvider {
+    pub fn new(settings: ProviderSettings, title_model: String) -> Self {
+        Self {
+            settings,
+            title_model,
+        }
+    }
+}
+
+#[async_trait]
+impl ChatProvider for CustomProvider {
+    fn models(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    async fn list_models(&self, api_key: &SecretString) -> Result<Vec<String>> {
+        handler::list_models(&self.settings, api_key).await
+    }
+
+    async fn stream(&self, req: &StreamRequest) -> Result<Option<StreamResult>> {
+        handler::stream(&self.settings, req).await
+    }
+
+    async fn complete(
+        &self,
+        api_key: &SecretString,
+        prompt: &str,
+        max_tokens: u32,
+    ) -> Result<Completion> {
+        handler::complete(
+            &self.settings,
+            api_key,
+            prompt,
+            max_tokens,
+            &self.title_model,
+        )
+        .await
+    }
+}
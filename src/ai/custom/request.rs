@@ -0,0 +1,76 @@
+use crate::{ai::provider::Sampling, models::message::Message};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct ChatMessage<'a> {
+    pub role: &'a str,
+    pub content: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChatCompletionRequest<'a> {
+    pub model: &'a str,
+    pub messages: Vec<ChatMessage<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StreamOptions {
+    pub include_usage: bool,
+}
+
+impl<'a> ChatCompletionRequest<'a> {
+    pub fn chat(model: &'a str, history: &'a [Message], system: &'a str, stream: bool) -> Self {
+        let system = ChatMessage {
+            role: "system",
+            content: system,
+        };
+
+        Self {
+            model,
+            messages: std::iter::once(system)
+                .chain(history.iter().map(|m| ChatMessage {
+                    role: &m.role,
+                    content: &m.body,
+                }))
+                .collect(),
+            stream: Some(stream),
+            max_tokens: None,
+            temperature: None,
+            top_p: None,
+            stream_options: stream.then_some(StreamOptions {
+                include_usage: true,
+            }),
+        }
+    }
+
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.temperature = sampling.temperature;
+        self.top_p = sampling.top_p;
+        self
+    }
+
+    pub fn prompt(model: &'a str, text: &'a str, max_tokens: u32) -> Self {
+        Self {
+            model,
+            messages: vec![ChatMessage {
+                role: "user",
+                content: text,
+            }],
+            stream: None,
+            max_tokens: Some(max_tokens),
+            temperature: None,
+            top_p: None,
+            stream_options: None,
+        }
+    }
+}
@@ -1,41 +1,64 @@
 use anyhow::{Context, Result, anyhow};
-use futures_util::StreamExt;
-use reqwest::{Client, RequestBuilder};
+use reqwest::RequestBuilder;
 use reqwest_eventsource::{Event, EventSource};
 use secrecy::{ExposeSecret, SecretString};
 use serde::Deserialize;
-use std::sync::Arc;
+use serde_json::Value;
 use tracing::{info, warn};
 use uuid::Uuid;
 
 use crate::{
     ai::{
         gemini::{model::GeminiModel, request::*},
-        handler::{StreamResult, create_title_prompt, done, send_error, send_text_delta},
+        handler::{
+            Completion, StreamResult, StreamStep, cancel_stream, done, next_event, send_error,
+            send_reasoning_delta, send_text_delta,
+        },
+        provider::StreamRequest,
+        tools::ToolCall,
+        usage::TokenUsage,
     },
-    models::message::Message,
-    services::sse_manager::SseManager,
+    configuration::ProviderSettings,
 };
 
-const GOOGLE_SSE_BASE: &str = "https://generativelanguage.googleapis.com/v1beta/models";
-
 pub async fn stream(
-    api_key: SecretString,
-    sse: Arc<SseManager>,
-    user_id: String,
-    chat_id: String,
-    model: GeminiModel,
-    messages: Vec<Message>,
+    settings: &ProviderSettings,
+    req: &StreamRequest,
 ) -> Result<Option<StreamResult>> {
-    let req_body = GeminiRequest::chat(&messages);
-    let url = format!(
-        "{}/{model}:streamGenerateContent?alt=sse&key={}",
-        GOOGLE_SSE_BASE,
+    let StreamRequest {
+        api_key,
+        sse,
+        user_id,
+        chat_id,
+        model,
+        effort,
+        sampling,
+        capabilities,
+        system_prompt,
+        history: messages,
+        images,
+        tools,
+        turns,
+        cancel,
+        ..
+    } = req;
+
+    let req_body = GeminiRequest::chat(
+        messages,
+        images,
+        system_prompt,
+        capabilities,
+        effort.clone(),
+    )
+    .with_sampling(*sampling)
+    .with_tools(tools, turns);
+    let url = settings.endpoint(&format!(
+        "models/{model}:streamGenerateContent?alt=sse&key={}",
         api_key.expose_secret(),
-        model = model.to_string()
-    );
+    ));
 
-    let http_req: RequestBuilder = Client::new()
+    let http_req: RequestBuilder = settings
+        .client()?
         .post(&url)
         .header("Content-Type", "application/json")
         .json(&req_body);
@@ -44,8 +67,19 @@ pub async fn stream(
 
     let msg_id = Uuid::new_v4().to_string();
     let mut full_text = String::new();
+    let mut full_reasoning = String::new();
+    let mut tool_calls: Vec<ToolCall> = Vec::new();
+    let mut usage: Option<TokenUsage> = None;
+
+    loop {
+        let ev = match next_event(&mut es, cancel).await {
+            StreamStep::Event(ev) => ev,
+            StreamStep::Finished => break,
+            StreamStep::Cancelled => {
+                return Ok(cancel_stream(sse, user_id, chat_id, full_text, full_reasoning).await);
+            }
+        };
 
-    while let Some(ev) = es.next().await {
         match ev {
             Ok(Event::Open) => info!("Gemini SSE opened"),
             Ok(Event::Message(msg)) => {
@@ -56,7 +90,7 @@ pub async fn stream(
                     continue;
                 }
 
-                let payload: GeminiSsePayload = match serde_json::from_str(&msg.data) {
+                let mut payload: GeminiSsePayload = match serde_json::from_str(&msg.data) {
                     Ok(p) => p,
                     Err(e) => {
                         warn!(error = %e, raw = msg.data, "Gemini JSON parse error");
@@ -64,17 +98,27 @@ pub async fn stream(
                     }
                 };
 
-                let (delta, stop, fail) = parse_payload(payload);
+                // every chunk carries the running totals, the last one wins
+                if let Some(metadata) = payload.usage_metadata.take() {
+                    usage = Some(metadata.into());
+                }
+
+                let (delta, thoughts, stop, fail) = parse_payload(payload, &mut tool_calls);
 
                 if let Some(reason) = fail {
-                    send_error(&sse, &user_id, &chat_id, &reason).await;
+                    send_error(sse, user_id, chat_id, &reason).await;
                     es.close();
                     return Err(anyhow!("Gemini stopped: {reason}"));
                 }
 
+                if !thoughts.is_empty() {
+                    full_reasoning.push_str(&thoughts);
+                    send_reasoning_delta(sse, user_id, chat_id, &thoughts).await;
+                }
+
                 if !delta.is_empty() {
                     full_text.push_str(&delta);
-                    send_text_delta(&sse, &user_id, &chat_id, &delta).await;
+                    send_text_delta(sse, user_id, chat_id, &delta).await;
                 }
 
                 if stop {
@@ -82,56 +126,117 @@ pub async fn stream(
                 }
             }
             Err(e) => {
-                send_error(&sse, &user_id, &chat_id, &e.to_string()).await;
+                send_error(sse, user_id, chat_id, &e.to_string()).await;
                 es.close();
                 return Err(anyhow!(e));
             }
         }
     }
 
-    if full_text.is_empty() {
+    if full_text.is_empty() && tool_calls.is_empty() {
         warn!("Gemini stream ended with no text");
         return Ok(None);
     }
 
-    done(&sse, &user_id, &chat_id, &msg_id).await;
+    if tool_calls.is_empty() {
+        done(sse, user_id, chat_id, &msg_id).await;
+    }
 
     Ok(Some(StreamResult {
         msg_id,
         content: full_text,
-        reasoning: None,
+        reasoning: (!full_reasoning.is_empty()).then_some(full_reasoning),
+        reasoning_signature: None,
+        tool_calls,
+        usage,
     }))
 }
 
-pub async fn generate_title(
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModelEntry {
+    name: String,
+    #[serde(default)]
+    supported_generation_methods: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelList {
+    #[serde(default)]
+    models: Vec<ModelEntry>,
+}
+
+pub async fn list_models(
+    settings: &ProviderSettings,
     api_key: &SecretString,
-    first_user_message: &str,
+) -> Result<Vec<String>> {
+    let url = settings.endpoint(&format!(
+        "models?pageSize=1000&key={}",
+        api_key.expose_secret()
+    ));
+
+    let list: ModelList = settings
+        .client()?
+        .get(&url)
+        .timeout(settings.request_timeout())
+        .send()
+        .await
+        .context("Google model list request failed")?
+        .error_for_status()
+        .context("Google model list HTTP error")?
+        .json()
+        .await
+        .context("Google model list JSON decode failed")?;
+
+    // Names come back as "models/<id>"; embedding models can't generate content.
+    Ok(list
+        .models
+        .into_iter()
+        .filter(|m| {
+            m.supported_generation_methods
+                .iter()
+                .any(|g| g == "generateContent")
+        })
+        .map(|m| {
+            m.name
+                .strip_prefix("models/")
+                .map(str::to_owned)
+                .unwrap_or(m.name)
+        })
+        .collect())
+}
+
+pub async fn complete(
+    settings: &ProviderSettings,
+    api_key: &SecretString,
+    prompt: &str,
+    max_tokens: u32,
     model: GeminiModel,
-) -> Result<String> {
-    let title_prompt = create_title_prompt(first_user_message);
-    let req_body = GeminiRequest::prompt(&title_prompt);
+) -> Result<Completion> {
+    let req_body = GeminiRequest::prompt(prompt).with_max_output(max_tokens);
 
-    let url = format!(
-        "{}/{model}:generateContent?key={}",
-        GOOGLE_SSE_BASE,
+    let url = settings.endpoint(&format!(
+        "models/{model}:generateContent?key={}",
         api_key.expose_secret(),
-        model = model.to_string()
-    );
+    ));
 
-    let payload: GeminiSsePayload = Client::new()
+    let mut payload: GeminiSsePayload = settings
+        .client()?
         .post(&url)
         .header("Content-Type", "application/json")
+        .timeout(settings.request_timeout())
         .json(&req_body)
         .send()
         .await
-        .context("Google title request failed")?
+        .context("Google completion request failed")?
         .error_for_status()
-        .context("Google title request HTTP error")?
+        .context("Google completion request HTTP error")?
         .json()
         .await
-        .context("Google title JSON decode failed")?;
+        .context("Google completion JSON decode failed")?;
 
-    let title = payload
+    let usage = payload.usage_metadata.take().map(TokenUsage::from);
+    let text = payload
         .candidates
         .into_iter()
         .flat_map(|c| c.content)
@@ -141,10 +246,14 @@ pub async fn generate_title(
         .trim()
         .to_owned();
 
-    if title.is_empty() {
-        Err(anyhow!("Google API returned empty title"))
+    if text.is_empty() {
+        Err(anyhow!("Google API returned an empty completion"))
     } else {
-        Ok(title)
+        Ok(Completion {
+            text,
+            model: model.to_string(),
+            usage,
+        })
     }
 }
 
@@ -152,6 +261,29 @@ pub async fn generate_title(
 struct GeminiSsePayload {
     #[serde(default)]
     candidates: Vec<Candidate>,
+    #[serde(rename = "usageMetadata")]
+    usage_metadata: Option<UsageMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageMetadata {
+    #[serde(default)]
+    prompt_token_count: u32,
+    #[serde(default)]
+    candidates_token_count: u32,
+    #[serde(default)]
+    thoughts_token_count: u32,
+}
+
+impl From<UsageMetadata> for TokenUsage {
+    fn from(value: UsageMetadata) -> Self {
+        Self {
+            input_tokens: value.prompt_token_count,
+            output_tokens: value.candidates_token_count + value.thoughts_token_count,
+            reasoning_tokens: value.thoughts_token_count,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -168,20 +300,52 @@ struct Content {
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct Part {
     text: Option<String>,
+    // set on thought summaries when includeThoughts is requested
+    #[serde(default)]
+    thought: bool,
+    function_call: Option<FunctionCallPart>,
+    thought_signature: Option<String>,
 }
 
-fn parse_payload(payload: GeminiSsePayload) -> (String, bool, Option<String>) {
+// Calls arrive whole, never split across chunks.
+#[derive(Debug, Deserialize)]
+struct FunctionCallPart {
+    id: Option<String>,
+    name: String,
+    #[serde(default)]
+    args: Value,
+}
+
+fn parse_payload(
+    payload: GeminiSsePayload,
+    calls: &mut Vec<ToolCall>,
+) -> (String, String, bool, Option<String>) {
     let mut delta = String::new();
+    let mut thoughts = String::new();
     let mut stop = false;
     let mut fail: Option<String> = None;
 
     for cand in payload.candidates {
         if let Some(content) = cand.content {
             for part in content.parts {
-                if let Some(t) = part.text {
-                    delta.push_str(&t);
+                if let Some(call) = part.function_call {
+                    calls.push(ToolCall {
+                        id: call.id.unwrap_or_else(|| Uuid::new_v4().to_string()),
+                        name: call.name,
+                        arguments: match call.args {
+                            Value::Null => Value::Object(Default::default()),
+                            args => args,
+                        },
+                        signature: part.thought_signature.clone(),
+                    });
+                }
+                match part.text {
+                    Some(t) if part.thought => thoughts.push_str(&t),
+                    Some(t) => delta.push_str(&t),
+                    None => {}
                 }
             }
         }
@@ -191,5 +355,5 @@ fn parse_payload(payload: GeminiSsePayload) -> (String, bool, Option<String>) {
             None => {}
         }
     }
-    (delta, stop, fail)
+    (delta, thoughts, stop, fail)
 }
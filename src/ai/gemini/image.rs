@@ -0,0 +1,114 @@
+use anyhow::{Context, Result, anyhow};
+use base64::{Engine, engine::general_purpose};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+
+use crate::{
+    ai::{
+        gemini::{handler::UsageMetadata, request::GeminiRequest},
+        image::GeneratedImage,
+        pricing::ModelPrice,
+        usage::TokenUsage,
+    },
+    configuration::ProviderSettings,
+};
+
+const IMAGE_MODEL: &str = "gemini-2.0-flash-preview-image-generation";
+// an image is billed as about 1300 output tokens
+const IMAGE_PRICE: ModelPrice = ModelPrice::new(0.10, 30.0);
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+    usage_metadata: Option<UsageMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Candidate {
+    content: Option<Content>,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Content {
+    #[serde(default)]
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Part {
+    text: Option<String>,
+    inline_data: Option<InlineData>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InlineData {
+    mime_type: String,
+    data: String,
+}
+
+pub async fn generate(
+    settings: &ProviderSettings,
+    api_key: &SecretString,
+    prompt: &str,
+) -> Result<GeneratedImage> {
+    let req_body = GeminiRequest::image(prompt);
+    let url = settings.endpoint(&format!(
+        "models/{IMAGE_MODEL}:generateContent?key={}",
+        api_key.expose_secret(),
+    ));
+
+    let res: ImageResponse = settings
+        .client()?
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .json(&req_body)
+        .send()
+        .await
+        .context("Google image request failed")?
+        .error_for_status()
+        .context("Google image request HTTP error")?
+        .json()
+        .await
+        .context("Google image JSON decode failed")?;
+
+    let usage = res.usage_metadata.map(TokenUsage::from);
+    let mut caption = String::new();
+    let mut image: Option<InlineData> = None;
+
+    for cand in res.candidates {
+        if let Some(reason) = cand.finish_reason.filter(|r| r != "STOP") {
+            return Err(anyhow!("Google stopped: {reason}"));
+        }
+        for part in cand.content.into_iter().flat_map(|c| c.parts) {
+            if let Some(text) = part.text {
+                caption.push_str(&text);
+            }
+            if image.is_none() {
+                image = part.inline_data;
+            }
+        }
+    }
+
+    // The model answers in text only when it declines to draw the prompt.
+    let image = image.ok_or_else(|| match caption.trim() {
+        "" => anyhow!("Google returned no image"),
+        text => anyhow!("Google returned no image: {text}"),
+    })?;
+
+    Ok(GeneratedImage {
+        media_type: image.mime_type,
+        data: general_purpose::STANDARD
+            .decode(image.data)
+            .context("Google image is not valid base64")?,
+        caption: Some(caption.trim().to_owned()).filter(|c| !c.is_empty()),
+        model: IMAGE_MODEL.to_owned(),
+        usage,
+        price: Some(IMAGE_PRICE),
+    })
+}
@@ -1,7 +1,14 @@
 use serde::{Deserialize, Serialize};
-use strum::{Display, EnumString};
+use strum::{Display, EnumString, VariantNames};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, Display, Serialize, Deserialize)]
+use crate::ai::{
+    pricing::ModelPrice,
+    registry::{ALL_MODALITIES, Capabilities},
+};
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, EnumString, Display, VariantNames, Serialize, Deserialize,
+)]
 #[strum(ascii_case_insensitive)]
 pub enum GeminiModel {
     #[serde(rename = "gemini-2.5-pro")]
@@ -16,3 +23,28 @@ pub enum GeminiModel {
     #[strum(serialize = "gemini-2.0-flash")]
     Flash20,
 }
+
+impl GeminiModel {
+    pub fn capabilities(self) -> Capabilities {
+        match self {
+            // prompts up to 200k tokens
+            Self::Pro25 => {
+                Capabilities::new(1_048_576, ALL_MODALITIES, ModelPrice::new(1.25, 10.0))
+                    .with_max_output(65_536)
+                    .with_reasoning()
+                    .with_tools()
+            }
+            Self::Flash25 => {
+                Capabilities::new(1_048_576, ALL_MODALITIES, ModelPrice::new(0.3, 2.5))
+                    .with_max_output(65_536)
+                    .with_reasoning()
+                    .with_tools()
+            }
+            Self::Flash20 => {
+                Capabilities::new(1_048_576, ALL_MODALITIES, ModelPrice::new(0.1, 0.4))
+                    .with_max_output(8_192)
+                    .with_tools()
+            }
+        }
+    }
+}
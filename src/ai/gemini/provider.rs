@@ -0,0 +1,55 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use secrecy::SecretString;
+use strum::VariantNames;
+
+use crate::{
+    ai::{
+        handler::{Completion, StreamResult},
+        provider::{ChatProvider, StreamRequest},
+    },
+    configuration::ProviderSettings,
+};
+
+use super::{handler, model::GeminiModel};
+
+pub struct GeminiProvider {
+    settings: ProviderSettings,
+}
+
+impl GeminiProvider {
+    pub fn new(settings: ProviderSettings) -> Self {
+        Self { settings }
+    }
+}
+
+#[async_trait]
+impl ChatProvider for GeminiProvider {
+    fn models(&self) -> &'static [&'static str] {
+        GeminiModel::VARIANTS
+    }
+
+    async fn list_models(&self, api_key: &SecretString) -> Result<Vec<String>> {
+        handler::list_models(&self.settings, api_key).await
+    }
+
+    async fn stream(&self, req: &StreamRequest) -> Result<Option<StreamResult>> {
+        handler::stream(&self.settings, req).await
+    }
+
+    async fn complete(
+        &self,
+        api_key: &SecretString,
+        prompt: &str,
+        max_tokens: u32,
+    ) -> Result<Completion> {
+        handler::complete(
+            &self.settings,
+            api_key,
+            prompt,
+            max_tokens,
+            GeminiModel::Flash20,
+        )
+        .await
+    }
+}
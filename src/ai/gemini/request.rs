@@ -1,8 +1,63 @@
 use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::ai::{
+    attachment::ImageMap,
+    provider::Sampling,
+    reasoning::EffortLevel,
+    registry::Capabilities,
+    tools::{ToolDefinition, ToolTurn},
+};
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct GeminiRequest<'a> {
     pub contents: Vec<GeminiMessage<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<GeminiMessage<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation_config: Option<GenerationConfig>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<GeminiTool<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiTool<'a> {
+    pub function_declarations: Vec<FunctionDeclaration<'a>>,
+}
+
+// parametersJsonSchema takes full JSON schema, `parameters` only an OpenAPI subset of it.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionDeclaration<'a> {
+    pub name: &'a str,
+    pub description: &'a str,
+    pub parameters_json_schema: &'a Value,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking_config: Option<ThinkingConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_modalities: Option<&'static [&'static str]>,
+}
+
+// Without a budget the model decides how long to think.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThinkingConfig {
+    pub include_thoughts: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking_budget: Option<u32>,
 }
 
 #[derive(Debug, Serialize)]
@@ -13,12 +68,54 @@ pub struct GeminiMessage<'a> {
 }
 
 #[derive(Debug, Serialize)]
-pub struct GeminiPart<'a> {
-    pub text: &'a str,
+#[serde(untagged)]
+pub enum GeminiPart<'a> {
+    Text {
+        text: &'a str,
+    },
+    #[serde(rename_all = "camelCase")]
+    InlineData {
+        inline_data: InlineData<'a>,
+    },
+    #[serde(rename_all = "camelCase")]
+    FunctionCall {
+        function_call: FunctionCall<'a>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        thought_signature: Option<&'a str>,
+    },
+    #[serde(rename_all = "camelCase")]
+    FunctionResponse {
+        function_response: FunctionResponse<'a>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct FunctionCall<'a> {
+    pub name: &'a str,
+    pub args: &'a Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FunctionResponse<'a> {
+    pub name: &'a str,
+    pub response: Value,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineData<'a> {
+    pub mime_type: &'a str,
+    pub data: &'a str,
 }
 
 impl<'a> GeminiRequest<'a> {
-    pub fn chat(history: &'a [crate::models::message::Message]) -> Self {
+    pub fn chat(
+        history: &'a [crate::models::message::Message],
+        images: &'a ImageMap,
+        system: &'a str,
+        capabilities: &Capabilities,
+        effort: Option<EffortLevel>,
+    ) -> Self {
         let contents = history
             .iter()
             .map(|m| GeminiMessage {
@@ -26,19 +123,133 @@ impl<'a> GeminiRequest<'a> {
                     "assistant" => "model",
                     _ => "user",
                 }),
-                parts: vec![GeminiPart { text: &m.body }],
+                parts: std::iter::once(GeminiPart::Text { text: &m.body })
+                    .chain(images.get(&m.id).into_iter().flatten().map(|i| {
+                        GeminiPart::InlineData {
+                            inline_data: InlineData {
+                                mime_type: &i.media_type,
+                                data: &i.data,
+                            },
+                        }
+                    }))
+                    .collect(),
             })
             .collect();
 
-        Self { contents }
+        let generation_config = GenerationConfig {
+            max_output_tokens: capabilities.max_output_tokens,
+            temperature: None,
+            top_p: None,
+            thinking_config: capabilities.reasoning.then(|| ThinkingConfig {
+                include_thoughts: true,
+                thinking_budget: effort.map(|e| e.thinking_budget()),
+            }),
+            response_modalities: None,
+        };
+
+        Self {
+            contents,
+            system_instruction: Some(GeminiMessage {
+                role: None,
+                parts: vec![GeminiPart::Text { text: system }],
+            }),
+            generation_config: Some(generation_config),
+            tools: Vec::new(),
+        }
+    }
+
+    // Each round is the model's calls followed by a user turn with the responses.
+    pub fn with_tools(mut self, tools: &'a [ToolDefinition], turns: &'a [ToolTurn]) -> Self {
+        if !tools.is_empty() {
+            self.tools = vec![GeminiTool {
+                function_declarations: tools
+                    .iter()
+                    .map(|t| FunctionDeclaration {
+                        name: &t.name,
+                        description: &t.description,
+                        parameters_json_schema: &t.parameters,
+                    })
+                    .collect(),
+            }];
+        }
+
+        for turn in turns {
+            let text = (!turn.text.is_empty()).then(|| GeminiPart::Text { text: &turn.text });
+            let calls = turn.calls.iter().map(|c| GeminiPart::FunctionCall {
+                function_call: FunctionCall {
+                    name: &c.name,
+                    args: &c.arguments,
+                },
+                thought_signature: c.signature.as_deref(),
+            });
+            self.contents.push(GeminiMessage {
+                role: Some("model"),
+                parts: text.into_iter().chain(calls).collect(),
+            });
+
+            self.contents.push(GeminiMessage {
+                role: Some("user"),
+                parts: turn
+                    .outputs
+                    .iter()
+                    .map(|o| GeminiPart::FunctionResponse {
+                        function_response: FunctionResponse {
+                            name: &o.name,
+                            response: match o.is_error {
+                                true => json!({ "error": o.content }),
+                                false => json!({ "output": o.content }),
+                            },
+                        },
+                    })
+                    .collect(),
+            });
+        }
+
+        self
     }
 
     pub fn prompt(text: &'a str) -> Self {
         Self {
             contents: vec![GeminiMessage {
                 role: Some("user"),
-                parts: vec![GeminiPart { text }],
+                parts: vec![GeminiPart::Text { text }],
             }],
+            system_instruction: None,
+            generation_config: None,
+            tools: Vec::new(),
+        }
+    }
+
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        if let Some(config) = self.generation_config.as_mut() {
+            config.temperature = sampling.temperature;
+            config.top_p = sampling.top_p;
+        }
+        self
+    }
+
+    pub fn with_max_output(mut self, max_output_tokens: u32) -> Self {
+        self.generation_config = Some(GenerationConfig {
+            max_output_tokens: Some(max_output_tokens),
+            temperature: None,
+            top_p: None,
+            thinking_config: None,
+            response_modalities: None,
+        });
+        self
+    }
+
+    // Image output models refuse requests that don't also allow text.
+    pub fn image(text: &'a str) -> Self {
+        Self {
+            generation_config: Some(GenerationConfig {
+                max_output_tokens: None,
+                temperature: None,
+                top_p: None,
+                thinking_config: None,
+                response_modalities: Some(&["TEXT", "IMAGE"]),
+            }),
+            ..Self::prompt(text)
         }
     }
 }
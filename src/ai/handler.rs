@@ -1,25 +1,118 @@
-use anyhow::Result;
-use serde_json::json;
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+use diesel::{Connection, MysqlConnection};
+use futures_util::{StreamExt, future::join_all};
+use reqwest_eventsource::{Event, EventSource};
+use serde_json::{Value, json};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
 
 use crate::{
-    ai::provider::{AiProvider, ProviderError, pick_provider},
+    ai::{
+        attachment::{AttachmentError, ImageMap, inline_documents, load_images},
+        context::{self, SUMMARY_MAX_TOKENS},
+        image::pick_image_provider,
+        mcp,
+        provider::{
+            ChatProvider, ProviderError, ProviderSetup, StreamRequest, pick_compare_provider,
+            pick_provider,
+        },
+        registry::{self, Capabilities, Modality},
+        tools::{ToolCall, ToolContext, ToolDefinition, ToolOutput, ToolRegistry, ToolTurn},
+        usage::TokenUsage,
+    },
     app::AppState,
-    jobs::Job,
-    models::message::Message,
+    jobs::{Job, is_transient},
+    models::{
+        attachment::{self, Attachment},
+        message::{CompareModel, Message, MessageMode, ReplyTo},
+        message_usage::{self, UsageKind},
+    },
     services::sse_manager::{EventType, SseManager, SseMessage},
 };
 
-use super::{
-    anthropic::{self, model::AnthropicModel},
-    gemini::{self, model::GeminiModel},
-    openai::{self, model::OpenAiModel},
-    openrouter::{self, model::OpenRouterModel},
-};
-
 pub struct StreamResult {
     pub msg_id: String,
     pub content: String,
     pub reasoning: Option<String>,
+    pub reasoning_signature: Option<String>,
+    // When set the model is waiting for the results instead of having answered.
+    pub tool_calls: Vec<ToolCall>,
+    pub usage: Option<TokenUsage>,
+}
+
+// One run of a generation job. A retry streams the reply again from the start under a new id, so
+// clients can drop whatever the failed attempt sent.
+#[derive(Debug, Clone)]
+pub struct Attempt {
+    pub id: String,
+    pub retry: bool,
+}
+
+// Where the events of one reply go. Each event carries the attempt it belongs to, and since the
+// replies of a compare stream into the same chat at once, the model writing it for those.
+#[derive(Debug, Clone)]
+pub struct StreamSink {
+    manager: Arc<SseManager>,
+    attempt_id: String,
+    model: Option<String>,
+}
+
+impl StreamSink {
+    pub fn new(manager: Arc<SseManager>, attempt: &Attempt) -> Self {
+        Self {
+            manager,
+            attempt_id: attempt.id.clone(),
+            model: None,
+        }
+    }
+
+    pub fn tagged(manager: Arc<SseManager>, attempt: &Attempt, model: String) -> Self {
+        Self {
+            manager,
+            attempt_id: attempt.id.clone(),
+            model: Some(model),
+        }
+    }
+
+    pub async fn send_to_user(&self, user: &str, mut message: SseMessage) {
+        if let Some(Value::Object(data)) = message.data.as_mut() {
+            data.insert(
+                "attempt_id".to_owned(),
+                Value::String(self.attempt_id.clone()),
+            );
+            if let Some(model) = &self.model {
+                data.insert("model".to_owned(), Value::String(model.clone()));
+            }
+        }
+        self.manager.send_to_user(user, message).await;
+    }
+}
+
+// Answer to a one-off prompt, e.g. a title or a summary.
+pub struct Completion {
+    pub text: String,
+    pub model: String,
+    pub usage: Option<TokenUsage>,
+}
+
+// Formatting rules the frontend relies on. A user's own prompt is appended to these rather than
+// replacing them.
+const BASE_INSTRUCTIONS: &str = "All code that you generate MUST be generated so that it is correctly rendered inside of a <code> block. Keep decoration in text to a minimum, just respond with clear information, in markdown format. RemarkGFM is used to help parse your output.";
+
+// Rounds of tool calls allowed for one reply, a model stuck calling tools is cut off here.
+const MAX_TOOL_TURNS: usize = 8;
+const TOOL_LIMIT_REACHED: &str = "Error: tool call limit reached. Answer with what you have found so far, without calling tools.";
+const PARAGRAPH: &str = "\n\n";
+const TITLE_MAX_TOKENS: u32 = 32;
+const MAX_COMPARE_MODELS: usize = 4;
+
+pub fn build_system_prompt(custom: Option<&str>) -> String {
+    match custom.map(str::trim).filter(|c| !c.is_empty()) {
+        Some(custom) => format!("{BASE_INSTRUCTIONS}\n\n{custom}"),
+        None => BASE_INSTRUCTIONS.to_owned(),
+    }
 }
 
 pub fn create_title_prompt(msg: &str) -> String {
@@ -29,70 +122,118 @@ pub fn create_title_prompt(msg: &str) -> String {
     )
 }
 
+// Queues the answer to a new user message.
 pub fn enqueue_ai_jobs(
     state: &AppState,
-    user_id: String,
-    chat_id: String,
-    new_msg_body: String,
-    messages: Vec<Message>,
+    conn: &mut MysqlConnection,
+    msg: &Message,
+    mode: MessageMode,
+    compare: Vec<CompareModel>,
 ) -> Result<()> {
-    if messages.len() == 1 {
-        state.job_tx.send(Job::GenerateTitle {
-            chat_id: chat_id.clone(),
-            user_id: user_id.clone(),
-            first_body: new_msg_body,
-        })?;
+    if compare.len() > MAX_COMPARE_MODELS {
+        bail!("At most {MAX_COMPARE_MODELS} models can be compared");
     }
 
-    state.job_tx.send(Job::GenerateResponse {
-        chat_id,
-        user_id,
-        messages: messages,
-    })?;
+    let queue = &state.service_container.job_queue_service;
+    if msg.parent_id.is_none() {
+        let job = Job::Title {
+            chat_id: msg.chat_id.clone(),
+            user_id: msg.user_id.clone(),
+            first_body: msg.body.clone(),
+        };
+        queue.enqueue(conn, &job)?;
+    }
+
+    let job = match mode {
+        MessageMode::Chat => Job::Response {
+            chat_id: msg.chat_id.clone(),
+            user_id: msg.user_id.clone(),
+            message_id: msg.id.clone(),
+            compare,
+        },
+        MessageMode::Image => Job::Image {
+            chat_id: msg.chat_id.clone(),
+            user_id: msg.user_id.clone(),
+            message_id: msg.id.clone(),
+            prompt: msg.body.clone(),
+        },
+    };
+    queue.enqueue(conn, &job)?;
 
     Ok(())
 }
 
+// Another answer to `parent`, saved next to the ones it already has.
+pub fn enqueue_regenerate(
+    state: &AppState,
+    conn: &mut MysqlConnection,
+    parent: &Message,
+) -> Result<()> {
+    let job = Job::Response {
+        chat_id: parent.chat_id.clone(),
+        user_id: parent.user_id.clone(),
+        message_id: parent.id.clone(),
+        compare: Vec::new(),
+    };
+    state
+        .service_container
+        .job_queue_service
+        .enqueue(conn, &job)
+}
+
 pub async fn generate_title(
     state: &AppState,
     chat_id: String,
     user_id: String,
     first_body: String,
 ) -> Result<()> {
-    let (provider, api_key) = {
+    let setup = {
         let mut conn = state.db_pool.get()?;
-        let setup = pick_provider(state, &mut conn, &user_id)?;
-
-        (setup.provider, setup.api_key)
-    };
-
-    let new_title = match provider {
-        AiProvider::OpenAi => {
-            openai::handler::generate_title(&api_key, &first_body, OpenAiModel::Gpt41Nano).await?
-        }
-        AiProvider::Google => {
-            gemini::handler::generate_title(&api_key, &first_body, GeminiModel::Flash20).await?
-        }
-        AiProvider::Anthropic => {
-            anthropic::handler::generate_title(&api_key, &first_body, AnthropicModel::Haiku35)
-                .await?
-        }
-        AiProvider::OpenRouter => {
-            openrouter::handler::generate_title(
-                &api_key,
-                &first_body,
-                OpenRouterModel::GeminiFlash25,
-            )
-            .await?
+        match pick_provider(state, &mut conn, &user_id, &chat_id) {
+            Ok(s) => s,
+            // the response job reports this to the user, retrying won't help
+            Err(e @ ProviderError::BudgetExhausted { .. }) => {
+                tracing::info!(%chat_id, "Skipping title generation: {e}");
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
         }
     };
 
+    let provider = setup.resolve(&state.config.providers)?;
+    let result = provider
+        .complete(
+            &setup.api_key,
+            &create_title_prompt(&first_body),
+            TITLE_MAX_TOKENS,
+        )
+        .await?;
+
     {
         let mut conn = state.db_pool.get()?;
-        state
-            .service_container
-            .chat_service
-            .update_title(&mut conn, &chat_id, &new_title, &user_id)?;
+        state.service_container.chat_service.update_title(
+            &mut conn,
+            &chat_id,
+            &result.text,
+            &user_id,
+        )?;
+
+        if let Some(usage) = result.usage {
+            record_usage(
+                state,
+                &mut conn,
+                &user_id,
+                message_usage::CreateArgs {
+                    chat_id: chat_id.clone(),
+                    message_id: None,
+                    kind: UsageKind::Title,
+                    provider: setup.provider.to_string(),
+                    price: registry::lookup(&setup.provider, &result.model).price,
+                    model: result.model.clone(),
+                    usage,
+                },
+            );
+        }
     }
 
     state.sse_manager.replicache_poke(&user_id).await;
@@ -104,17 +245,69 @@ pub async fn generate_response(
     state: &AppState,
     chat_id: String,
     user_id: String,
-    messages: Vec<Message>,
+    message_id: String,
+    compare: Vec<CompareModel>,
+    attempt: &Attempt,
 ) -> Result<()> {
+    if attempt.retry {
+        state
+            .sse_manager
+            .reset_chat(&user_id, &chat_id, &attempt.id)
+            .await;
+    }
+
+    let (messages, attachments) = {
+        let mut conn = state.db_pool.get()?;
+        let messages = state.service_container.message_service.thread(
+            &mut conn,
+            &chat_id,
+            &message_id,
+            &user_id,
+        )?;
+        let message_ids: Vec<&str> = messages.iter().map(|m| m.id.as_str()).collect();
+        let attachments = state
+            .service_container
+            .attachment_service
+            .list_for_messages(&mut conn, &message_ids)?;
+        (messages, attachments)
+    };
+    if messages.last().is_none_or(|m| m.id != message_id) {
+        return nothing_to_answer(state, &user_id, &chat_id, &message_id).await;
+    }
+
+    if !compare.is_empty() {
+        return generate_compare(
+            state,
+            chat_id,
+            user_id,
+            messages,
+            attachments,
+            compare,
+            attempt,
+        )
+        .await;
+    }
+
+    let sse = StreamSink::new(state.sse_manager.clone(), attempt);
+    let reply_to = Some(ReplyTo {
+        parent_id: message_id,
+        compare_model: None,
+    });
     let setup = {
         let mut conn = state.db_pool.get()?;
-        match pick_provider(state, &mut conn, &user_id) {
+        match pick_provider(state, &mut conn, &user_id, &chat_id) {
             Ok(s) => s,
             Err(ProviderError::MissingApiKey(p)) => {
                 state
                     .service_container
                     .message_service
-                    .save_assistant_error(&mut conn, &chat_id, p.clone(), &user_id)?;
+                    .save_assistant_error(
+                        &mut conn,
+                        &chat_id,
+                        &format!("Missing API key for {p}"),
+                        reply_to,
+                        &user_id,
+                    )?;
 
                 state
                     .sse_manager
@@ -129,10 +322,146 @@ pub async fn generate_response(
                 state.sse_manager.replicache_poke(&user_id).await;
                 return Ok(());
             }
+            Err(e @ ProviderError::BudgetExhausted { .. }) => {
+                return report_failure(state, &sse, &chat_id, &user_id, &e.to_string(), reply_to)
+                    .await;
+            }
             Err(e) => return Err(e.into()),
         }
     };
 
+    let generation = state.generation_registry.start(&chat_id, &user_id);
+    let reply = Reply {
+        setup,
+        sse,
+        reply_to,
+    };
+    generate_reply(
+        state,
+        &chat_id,
+        &user_id,
+        messages,
+        &attachments,
+        reply,
+        generation.token(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+// Sends the prompt to every model at once. Their replies are saved as siblings, and once all are
+// in, the first of them in the order the models were given is picked to continue the conversation
+// unless the user already picked one.
+async fn generate_compare(
+    state: &AppState,
+    chat_id: String,
+    user_id: String,
+    messages: Vec<Message>,
+    attachments: Vec<Attachment>,
+    compare: Vec<CompareModel>,
+    attempt: &Attempt,
+) -> Result<()> {
+    let group = messages
+        .last()
+        .map(|m| m.id.clone())
+        .context("No message to compare replies to")?;
+
+    tracing::info!(%chat_id, models = compare.len(), "Starting compare");
+
+    // One generation for all of them, cancelling the chat stops every reply.
+    let generation = state.generation_registry.start(&chat_id, &user_id);
+    let cancel = generation.token();
+
+    let replies = join_all(compare.iter().map(|choice| {
+        let reply_to = ReplyTo {
+            parent_id: group.clone(),
+            compare_model: Some(choice.tag()),
+        };
+        let sse = StreamSink::tagged(state.sse_manager.clone(), attempt, choice.tag());
+        let (chat_id, user_id) = (&chat_id, &user_id);
+        let (messages, attachments, cancel) = (messages.clone(), &attachments, cancel.clone());
+
+        async move {
+            let result = async {
+                let setup = {
+                    let mut conn = state.db_pool.get()?;
+                    pick_compare_provider(state, &mut conn, user_id, chat_id, choice)?
+                };
+                let reply = Reply {
+                    setup,
+                    sse: sse.clone(),
+                    reply_to: Some(reply_to.clone()),
+                };
+                generate_reply(state, chat_id, user_id, messages, attachments, reply, cancel).await
+            }
+            .await;
+
+            // One model failing leaves the others' replies standing.
+            match result {
+                Ok(message) => message,
+                Err(e) => {
+                    tracing::warn!(%chat_id, model = choice.tag(), error = ?e, "Compare reply failed");
+                    let reason = e.to_string();
+                    if let Err(e) =
+                        report_failure(state, &sse, chat_id, user_id, &reason, Some(reply_to)).await
+                    {
+                        tracing::error!(error = ?e, "Failed to save compare error");
+                    }
+                    None
+                }
+            }
+        }
+    }))
+    .await;
+
+    drop(generation);
+
+    // The replies are saved, failing from here on would only have them generated again.
+    if let Some(first) = replies.into_iter().flatten().next() {
+        let selected = state
+            .db_pool
+            .get()
+            .map_err(anyhow::Error::from)
+            .and_then(|mut conn| {
+                state
+                    .service_container
+                    .message_service
+                    .select_default(&mut conn, &first.id, &user_id)
+            });
+        if let Err(e) = selected {
+            tracing::error!(%chat_id, error = ?e, "Failed to pick a compare reply");
+        }
+    }
+    state.sse_manager.replicache_poke(&user_id).await;
+
+    Ok(())
+}
+
+// The model a reply is generated with and where its events go.
+struct Reply {
+    setup: ProviderSetup,
+    sse: StreamSink,
+    // The message replied to, with the model for the replies of a compare.
+    reply_to: Option<ReplyTo>,
+}
+
+// Streams one reply and saves it. None when there was nothing to save, or the failure was saved
+// in its place.
+async fn generate_reply(
+    state: &AppState,
+    chat_id: &str,
+    user_id: &str,
+    messages: Vec<Message>,
+    attachments: &[Attachment],
+    reply: Reply,
+    cancel: CancellationToken,
+) -> Result<Option<Message>> {
+    let Reply {
+        setup,
+        sse,
+        reply_to,
+    } = reply;
     let provider_string = setup.provider.to_string();
 
     tracing::info!(
@@ -141,68 +470,613 @@ pub async fn generate_response(
         "Starting stream"
     );
 
-    // If there is an error here, we should handle it rather than relying on the stream to save the
-    // errors
-    let stream_res = match setup.provider {
-        AiProvider::OpenAi => {
-            openai::handler::stream(
-                setup.api_key,
-                state.sse_manager.clone(),
-                user_id.clone(),
-                chat_id.clone(),
-                setup.model.parse()?,
-                setup.effort,
-                messages.clone(),
-            )
-            .await?
-        }
-        AiProvider::Google => {
-            gemini::handler::stream(
-                setup.api_key,
-                state.sse_manager.clone(),
-                user_id.clone(),
-                chat_id.clone(),
-                setup.model.parse()?,
-                messages.clone(),
-            )
-            .await?
-        }
-        AiProvider::Anthropic => {
-            anthropic::handler::stream(
-                setup.api_key,
-                state.sse_manager.clone(),
-                user_id.clone(),
-                chat_id.clone(),
-                setup.model.parse()?,
-                messages.clone(),
-            )
-            .await?
-        }
-        AiProvider::OpenRouter => {
-            openrouter::handler::stream(
-                setup.api_key,
-                state.sse_manager.clone(),
-                user_id.clone(),
-                chat_id.clone(),
-                setup.model.parse()?,
-                messages.clone(),
-            )
-            .await?
-        }
-    };
-
-    if let Some(stream_res) = stream_res {
+    let provider = setup.resolve(&state.config.providers)?;
+    let capabilities = registry::lookup(&setup.provider, &setup.model);
+
+    let effort = setup.effort.clone().filter(|_| capabilities.reasoning);
+
+    let messages =
+        match inline_documents(state, messages, attachments, &capabilities, &setup.model).await {
+            Ok(messages) => messages,
+            Err(e @ AttachmentError::DocumentsTooLarge { .. }) => {
+                report_failure(state, &sse, chat_id, user_id, &e.to_string(), reply_to).await?;
+                return Ok(None);
+            }
+            Err(AttachmentError::Other(e)) => return Err(e),
+        };
+
+    // Images are left out for models that can't see them, the text of the message still goes.
+    let images = if capabilities.modalities.contains(&Modality::Image) {
+        load_images(state, attachments).await?
+    } else {
+        ImageMap::new()
+    };
+
+    let mut system_prompt = {
+        let mut conn = state.db_pool.get()?;
+        resolve_system_prompt(state, &mut conn, chat_id, user_id)?
+    };
+
+    let tools = if capabilities.tools {
+        let mut tools = ToolRegistry::builtin(state.service_container.search_backend.clone());
+        for tool in mcp::load_tools(state, user_id).await? {
+            tools.register(tool);
+        }
+        tools
+    } else {
+        ToolRegistry::default()
+    };
+    let definitions = tools.definitions();
+
+    let (messages, summary) = fit_context(
+        state,
+        provider.as_ref(),
+        &setup,
+        &capabilities,
+        chat_id,
+        user_id,
+        messages,
+        &images,
+        &system_prompt,
+        &definitions,
+    )
+    .await?;
+    // Goes in the system prompt rather than as a message so the history still alternates roles.
+    if let Some(summary) = summary {
+        system_prompt.push_str(&format!(
+            "{PARAGRAPH}Summary of the earlier conversation:\n{summary}"
+        ));
+    }
+
+    let mut req = StreamRequest {
+        api_key: setup.api_key,
+        sse,
+        user_id: user_id.to_owned(),
+        chat_id: chat_id.to_owned(),
+        model: setup.model.clone(),
+        effort,
+        sampling: setup.sampling,
+        capabilities,
+        system_prompt,
+        history: messages,
+        images,
+        tools: definitions,
+        turns: Vec::new(),
+        cancel,
+    };
+
+    // Usage is recorded whether or not there is a reply to save. A cancelled stream, and the tool
+    // rounds before one that failed, were billed all the same.
+    let mut usage = None;
+    let result = match run_tools(state, provider.as_ref(), &tools, &mut req, &mut usage).await {
+        Ok(Some(stream_res)) => {
+            let parts = req.turns.iter().flat_map(ToolTurn::parts).collect();
+            let citations = ToolTurn::citations(&req.turns);
+            state
+                .db_pool
+                .get()
+                .map_err(anyhow::Error::from)
+                .and_then(|mut conn| {
+                    state
+                        .service_container
+                        .message_service
+                        .save_assistant_reply(
+                            &mut conn, chat_id, stream_res, parts, citations, reply_to, user_id,
+                        )
+                })
+                .map(Some)
+        }
+        result => result.map(|_| None),
+    };
+
+    if let Some(usage) = usage {
+        match state.db_pool.get() {
+            Ok(mut conn) => record_usage(
+                state,
+                &mut conn,
+                user_id,
+                message_usage::CreateArgs {
+                    chat_id: chat_id.to_owned(),
+                    message_id: result
+                        .as_ref()
+                        .ok()
+                        .and_then(|m| m.as_ref())
+                        .map(|m| m.id.clone()),
+                    kind: UsageKind::Reply,
+                    provider: provider_string,
+                    price: capabilities.price,
+                    model: setup.model,
+                    usage,
+                },
+            ),
+            Err(e) => tracing::error!(error = ?e, "Failed to record token usage"),
+        }
+    }
+
+    result
+}
+
+pub async fn generate_image(
+    state: &AppState,
+    chat_id: String,
+    user_id: String,
+    message_id: String,
+    prompt: String,
+    attempt: &Attempt,
+) -> Result<()> {
+    if attempt.retry {
+        state
+            .sse_manager
+            .reset_chat(&user_id, &chat_id, &attempt.id)
+            .await;
+    }
+
+    let asked = {
         let mut conn = state.db_pool.get()?;
         state
             .service_container
             .message_service
-            .save_assistant_reply(&mut conn, &chat_id, stream_res, &user_id)?;
+            .exists(&mut conn, &message_id)?
+    };
+    if !asked {
+        return nothing_to_answer(state, &user_id, &chat_id, &message_id).await;
     }
 
+    let sse = StreamSink::new(state.sse_manager.clone(), attempt);
+    let reply_to = ReplyTo {
+        parent_id: message_id,
+        compare_model: None,
+    };
+    let setup = {
+        let mut conn = state.db_pool.get()?;
+        match pick_image_provider(state, &mut conn, &user_id) {
+            Ok(s) => s,
+            Err(e @ (ProviderError::BudgetExhausted { .. } | ProviderError::NoImageProvider)) => {
+                let reason = e.to_string();
+                return report_failure(state, &sse, &chat_id, &user_id, &reason, Some(reply_to))
+                    .await;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    };
+
+    tracing::info!(provider = %setup.provider, "Starting image generation");
+
+    let generation = state.generation_registry.start(&chat_id, &user_id);
+    let cancel = generation.token();
+    send_progress(&sse, &user_id, &chat_id, "generating").await;
+
+    let image = tokio::select! {
+        biased;
+        _ = cancel.cancelled() => {
+            let msg_id = Uuid::new_v4().to_string();
+            cancelled(&sse, &user_id, &chat_id, &msg_id).await;
+            return Ok(());
+        }
+        res = setup.generate(&state.config.providers, &prompt) => match res {
+            Ok(image) => image,
+            Err(e) if is_transient(&e) => {
+                send_error(&sse, &user_id, &chat_id, &e.to_string()).await;
+                return Err(e);
+            }
+            // Trying again won't help, so the prompt is answered with the error.
+            Err(e) => {
+                let reason = e.to_string();
+                return report_failure(state, &sse, &chat_id, &user_id, &reason, Some(reply_to))
+                    .await;
+            }
+        },
+    };
+
+    drop(generation);
+
+    send_progress(&sse, &user_id, &chat_id, "saving").await;
+
+    let attachment_service = &state.service_container.attachment_service;
+    let attachment_id = Uuid::new_v4().to_string();
+    let storage_key = Attachment::storage_key(&user_id, &attachment_id);
+    let mut conn = state.db_pool.get()?;
+    attachment_service
+        .store_blob(&storage_key, &image.data)
+        .await?;
+
+    // The reply and its image appear together or not at all.
+    let saved = conn.transaction(|conn| {
+        let message = state
+            .service_container
+            .message_service
+            .save_assistant_reply(
+                conn,
+                &chat_id,
+                StreamResult {
+                    msg_id: Uuid::new_v4().to_string(),
+                    content: image.caption.clone().unwrap_or_default(),
+                    reasoning: None,
+                    reasoning_signature: None,
+                    tool_calls: Vec::new(),
+                    usage: None,
+                },
+                Vec::new(),
+                Vec::new(),
+                Some(reply_to),
+                &user_id,
+            )?;
+
+        let args = attachment::CreateArgs {
+            id: attachment_id.clone(),
+            filename: image.filename(),
+            content_type: image.media_type.clone(),
+            size_bytes: image.data.len() as u64,
+            storage_key: storage_key.clone(),
+        };
+        attachment_service.create(conn, args, &user_id)?;
+        attachment_service.attach(conn, &[attachment_id], &message.id, &user_id)?;
+
+        anyhow::Ok(message)
+    });
+    let message = match saved {
+        Ok(message) => message,
+        Err(e) => {
+            // Nothing refers to the blob without the rows.
+            if let Err(e) = attachment_service.delete_stored(&storage_key).await {
+                tracing::warn!(storage_key, error = ?e, "Failed to delete an unsaved image");
+            }
+            return Err(e);
+        }
+    };
+
+    if let Some(usage) = image.usage {
+        record_usage(
+            state,
+            &mut conn,
+            &user_id,
+            message_usage::CreateArgs {
+                chat_id: chat_id.clone(),
+                message_id: Some(message.id.clone()),
+                kind: UsageKind::Image,
+                provider: setup.provider.to_string(),
+                price: image.price,
+                model: image.model,
+                usage,
+            },
+        );
+    }
+
+    done(&sse, &user_id, &chat_id, &message.id).await;
+    state.sse_manager.replicache_poke(&user_id).await;
+
+    Ok(())
+}
+
+// Streams until the model answers instead of calling tools. Each round's calls are run and sent
+// back with the next request, the rounds are kept on the request so they can be saved with the
+// reply.
+async fn run_tools(
+    state: &AppState,
+    provider: &dyn ChatProvider,
+    tools: &ToolRegistry,
+    req: &mut StreamRequest,
+    usage: &mut Option<TokenUsage>,
+) -> Result<Option<StreamResult>> {
+    let ctx = ToolContext {
+        state: state.clone(),
+        user_id: req.user_id.clone(),
+        chat_id: req.chat_id.clone(),
+    };
+    let sse = req.sse.clone();
+
+    let last = loop {
+        let res = provider.stream(req).await?;
+        // Usage only comes at the end of a stream, one that was cut short is estimated.
+        let spent = match &res {
+            Some(r) => r.usage.unwrap_or_else(|| {
+                context::estimate_usage(req, &r.content, r.reasoning.as_deref())
+            }),
+            None => context::estimate_usage(req, "", None),
+        };
+        *usage.get_or_insert_default() += spent;
+
+        let res = match res {
+            Some(res) if !res.tool_calls.is_empty() && !req.cancel.is_cancelled() => res,
+            res => break res,
+        };
+
+        // At the limit the calls are answered with an error instead of being run, which leaves the
+        // model one last round to reply with what it has. Calls it makes after that are dropped.
+        let at_limit = req.turns.len() >= MAX_TOOL_TURNS;
+        if req.turns.len() > MAX_TOOL_TURNS {
+            tracing::warn!(
+                chat_id = req.chat_id,
+                "Tool calls made past the round limit"
+            );
+            break Some(res);
+        }
+
+        let mut outputs = Vec::with_capacity(res.tool_calls.len());
+        for call in &res.tool_calls {
+            if at_limit {
+                outputs.push(ToolOutput {
+                    call_id: call.id.clone(),
+                    name: call.name.clone(),
+                    content: TOOL_LIMIT_REACHED.to_owned(),
+                    is_error: true,
+                    citations: Vec::new(),
+                });
+                continue;
+            }
+            send_tool_call(&sse, &req.user_id, &req.chat_id, call).await;
+            let output = tools.execute(&ctx, call).await;
+            send_tool_result(&sse, &req.user_id, &req.chat_id, &output).await;
+            outputs.push(output);
+        }
+
+        // keeps the text of the next round from running into this one's
+        if !res.content.is_empty() {
+            send_text_delta(&sse, &req.user_id, &req.chat_id, PARAGRAPH).await;
+        }
+
+        req.turns.push(ToolTurn {
+            text: res.content,
+            reasoning: res.reasoning,
+            reasoning_signature: res.reasoning_signature,
+            calls: res.tool_calls,
+            outputs,
+        });
+    };
+
+    if req.turns.is_empty() {
+        return Ok(last);
+    }
+
+    // A round that ended without text still produced a reply worth keeping.
+    let last = match last {
+        Some(last) => last,
+        None if req.cancel.is_cancelled() => StreamResult {
+            msg_id: Uuid::new_v4().to_string(),
+            content: String::new(),
+            reasoning: None,
+            reasoning_signature: None,
+            tool_calls: Vec::new(),
+            usage: None,
+        },
+        None => {
+            let msg_id = Uuid::new_v4().to_string();
+            done(&sse, &req.user_id, &req.chat_id, &msg_id).await;
+            StreamResult {
+                msg_id,
+                content: String::new(),
+                reasoning: None,
+                reasoning_signature: None,
+                tool_calls: Vec::new(),
+                usage: None,
+            }
+        }
+    };
+
+    let texts = req.turns.iter().map(|t| t.text.as_str());
+    let content = join_paragraphs(texts.chain(std::iter::once(last.content.as_str())));
+    let reasonings = req
+        .turns
+        .iter()
+        .map(|t| t.reasoning.as_deref().unwrap_or(""));
+    let reasoning = join_paragraphs(reasonings.chain(last.reasoning.as_deref()));
+
+    Ok(Some(StreamResult {
+        msg_id: last.msg_id,
+        content,
+        reasoning: (!reasoning.is_empty()).then_some(reasoning),
+        reasoning_signature: None,
+        tool_calls: Vec::new(),
+        usage: *usage,
+    }))
+}
+
+fn join_paragraphs<'a>(texts: impl Iterator<Item = &'a str>) -> String {
+    texts
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join(PARAGRAPH)
+}
+
+// The message was deleted while its job waited in the queue, so the job finishes without a reply.
+async fn nothing_to_answer(
+    state: &AppState,
+    user_id: &str,
+    chat_id: &str,
+    message_id: &str,
+) -> Result<()> {
+    tracing::info!(chat_id, message_id, "Message to answer is gone, skipping");
+    state
+        .sse_manager
+        .send_to_user(
+            user_id,
+            SseMessage {
+                event_type: EventType::Exit,
+                data: Some(json!({ "chat_id": chat_id })),
+            },
+        )
+        .await;
+    Ok(())
+}
+
+// Failures the user has to fix themselves are saved as the reply instead of being retried.
+async fn report_failure(
+    state: &AppState,
+    sse: &StreamSink,
+    chat_id: &str,
+    user_id: &str,
+    reason: &str,
+    reply_to: Option<ReplyTo>,
+) -> Result<()> {
+    let mut conn = state.db_pool.get()?;
+    state
+        .service_container
+        .message_service
+        .save_assistant_error(&mut conn, chat_id, reason, reply_to, user_id)?;
+
+    send_error(sse, user_id, chat_id, reason).await;
+    state.sse_manager.replicache_poke(user_id).await;
+
     Ok(())
 }
 
-pub async fn send_text_delta(sse: &SseManager, user: &str, chat: &str, delta: &str) {
+// Drops the oldest messages that don't fit the model's context window and stands a summary of them
+// in for them. The summary is kept on the chat and only the turns dropped since are folded in, so
+// it isn't recomputed from scratch on every reply.
+#[allow(clippy::too_many_arguments)]
+async fn fit_context(
+    state: &AppState,
+    provider: &dyn ChatProvider,
+    setup: &ProviderSetup,
+    capabilities: &Capabilities,
+    chat_id: &str,
+    user_id: &str,
+    messages: Vec<Message>,
+    images: &ImageMap,
+    system_prompt: &str,
+    tools: &[ToolDefinition],
+) -> Result<(Vec<Message>, Option<String>)> {
+    let Some(budget) = context::history_budget(capabilities, system_prompt, tools) else {
+        return Ok((messages, None));
+    };
+
+    let (kept, dropped) = context::split_history(messages, budget, images);
+    let Some(last_dropped) = dropped.last() else {
+        return Ok((kept, None));
+    };
+
+    let chat = {
+        let mut conn = state.db_pool.get()?;
+        state
+            .service_container
+            .chat_service
+            .get(&mut conn, chat_id, user_id)?
+    };
+
+    let covered = chat
+        .summary_message_id
+        .as_deref()
+        .and_then(|id| dropped.iter().position(|m| m.id == id));
+    let (previous, pending) = match (covered, chat.summary.as_deref()) {
+        (Some(i), Some(summary)) => (Some(summary), &dropped[i + 1..]),
+        _ => (None, &dropped[..]),
+    };
+    if pending.is_empty() {
+        return Ok((kept, previous.map(str::to_owned)));
+    }
+
+    tracing::info!(
+        %chat_id,
+        dropped = dropped.len(),
+        pending = pending.len(),
+        "Summarizing history that no longer fits the context window"
+    );
+
+    let prompt = context::create_summary_prompt(previous, pending);
+    let result = match provider
+        .complete(&setup.api_key, &prompt, SUMMARY_MAX_TOKENS)
+        .await
+    {
+        Ok(result) => result,
+        // Plain truncation still gets the reply out, the summary is retried on the next one.
+        Err(e) => {
+            tracing::warn!(%chat_id, error = ?e, "Failed to summarize history");
+            return Ok((kept, previous.map(str::to_owned)));
+        }
+    };
+
+    let mut conn = state.db_pool.get()?;
+    state.service_container.chat_service.save_summary(
+        &mut conn,
+        chat_id,
+        &result.text,
+        &last_dropped.id,
+        user_id,
+    )?;
+
+    if let Some(usage) = result.usage {
+        record_usage(
+            state,
+            &mut conn,
+            user_id,
+            message_usage::CreateArgs {
+                chat_id: chat_id.to_owned(),
+                message_id: None,
+                kind: UsageKind::Summary,
+                provider: setup.provider.to_string(),
+                price: registry::lookup(&setup.provider, &result.model).price,
+                model: result.model,
+                usage,
+            },
+        );
+    }
+
+    Ok((kept, Some(result.text)))
+}
+
+// A prompt set on the chat wins over the user's default one.
+fn resolve_system_prompt(
+    state: &AppState,
+    conn: &mut MysqlConnection,
+    chat_id: &str,
+    user_id: &str,
+) -> Result<String> {
+    let chat = state
+        .service_container
+        .chat_service
+        .get(conn, chat_id, user_id)?;
+
+    let custom = match chat.system_prompt {
+        Some(prompt) => Some(prompt),
+        None => state
+            .service_container
+            .system_prompt_service
+            .get_for_user(conn, user_id)?
+            .map(|p| p.body),
+    };
+
+    Ok(build_system_prompt(custom.as_deref()))
+}
+
+// Usage is bookkeeping, failing to record it must not fail (and retry) the job.
+fn record_usage(
+    state: &AppState,
+    conn: &mut MysqlConnection,
+    user_id: &str,
+    args: message_usage::CreateArgs,
+) {
+    if let Err(e) = state
+        .service_container
+        .usage_service
+        .record(conn, user_id, args)
+    {
+        tracing::error!(error = ?e, "Failed to record token usage");
+    }
+}
+
+pub enum StreamStep {
+    Event(Result<Event, reqwest_eventsource::Error>),
+    Cancelled,
+    Finished,
+}
+
+// Shared by every provider stream loop so that cancellation is observed while waiting on the
+// upstream connection, not only between events.
+pub async fn next_event(es: &mut EventSource, cancel: &CancellationToken) -> StreamStep {
+    tokio::select! {
+        biased;
+        _ = cancel.cancelled() => {
+            es.close();
+            StreamStep::Cancelled
+        }
+        ev = es.next() => match ev {
+            Some(ev) => StreamStep::Event(ev),
+            None => StreamStep::Finished,
+        },
+    }
+}
+
+pub async fn send_text_delta(sse: &StreamSink, user: &str, chat: &str, delta: &str) {
     let payload = json!({ "chat_id": chat, "chunk": delta });
     sse.send_to_user(
         user,
@@ -214,7 +1088,7 @@ pub async fn send_text_delta(sse: &SseManager, user: &str, chat: &str, delta: &s
     .await;
 }
 
-pub async fn send_reasoning_delta(sse: &SseManager, user: &str, chat: &str, delta: &str) {
+pub async fn send_reasoning_delta(sse: &StreamSink, user: &str, chat: &str, delta: &str) {
     let payload = json!({ "chat_id": chat, "reasoning": delta });
     sse.send_to_user(
         user,
@@ -226,7 +1100,7 @@ pub async fn send_reasoning_delta(sse: &SseManager, user: &str, chat: &str, delt
     .await;
 }
 
-pub async fn send_error(sse: &SseManager, user: &str, chat: &str, error: &str) {
+pub async fn send_error(sse: &StreamSink, user: &str, chat: &str, error: &str) {
     let payload = json!({ "chat_id": chat, "error": error });
     sse.send_to_user(
         user,
@@ -238,7 +1112,93 @@ pub async fn send_error(sse: &SseManager, user: &str, chat: &str, error: &str) {
     .await;
 }
 
-pub async fn done(sse: &SseManager, user: &str, chat: &str, id: &str) {
+pub async fn send_progress(sse: &StreamSink, user: &str, chat: &str, status: &str) {
+    let payload = json!({ "chat_id": chat, "status": status });
+    sse.send_to_user(
+        user,
+        SseMessage {
+            event_type: EventType::Progress,
+            data: Some(payload),
+        },
+    )
+    .await;
+}
+
+pub async fn send_tool_call(sse: &StreamSink, user: &str, chat: &str, call: &ToolCall) {
+    let payload = json!({
+        "chat_id": chat,
+        "call_id": call.id,
+        "name": call.name,
+        "arguments": call.arguments,
+    });
+    sse.send_to_user(
+        user,
+        SseMessage {
+            event_type: EventType::ToolCall,
+            data: Some(payload),
+        },
+    )
+    .await;
+}
+
+pub async fn send_tool_result(sse: &StreamSink, user: &str, chat: &str, output: &ToolOutput) {
+    let payload = json!({
+        "chat_id": chat,
+        "call_id": output.call_id,
+        "name": output.name,
+        "content": output.content,
+        "is_error": output.is_error,
+        "citations": output.citations,
+    });
+    sse.send_to_user(
+        user,
+        SseMessage {
+            event_type: EventType::ToolResult,
+            data: Some(payload),
+        },
+    )
+    .await;
+}
+
+pub async fn cancelled(sse: &StreamSink, user: &str, chat: &str, id: &str) {
+    let payload = json!({ "chat_id": chat, "msg_id": id });
+    sse.send_to_user(
+        user,
+        SseMessage {
+            event_type: EventType::Cancelled,
+            data: Some(payload),
+        },
+    )
+    .await;
+}
+
+// Called by a provider stream once it has observed the cancellation token. Whatever was produced
+// so far is kept so that it can be saved as the assistant reply.
+pub async fn cancel_stream(
+    sse: &StreamSink,
+    user: &str,
+    chat: &str,
+    content: String,
+    reasoning: String,
+) -> Option<StreamResult> {
+    let msg_id = Uuid::new_v4().to_string();
+    cancelled(sse, user, chat, &msg_id).await;
+
+    if content.is_empty() && reasoning.is_empty() {
+        return None;
+    }
+
+    Some(StreamResult {
+        msg_id,
+        content,
+        reasoning: (!reasoning.is_empty()).then_some(reasoning),
+        reasoning_signature: None,
+        tool_calls: Vec::new(),
+        usage: None,
+    })
+}
+
+pub async fn done(sse: &StreamSink, user: &str, chat: &str, id: &str) {
     let payload = json!({ "chat_id": chat, "msg_id": id });
     sse.send_to_user(
         user,
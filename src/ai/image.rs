@@ -0,0 +1,91 @@
+use anyhow::{Context, Result, bail};
+use diesel::MysqlConnection;
+use secrecy::SecretString;
+
+use crate::{app::AppState, configuration::ProvidersSettings};
+
+use super::{
+    gemini, openai,
+    pricing::ModelPrice,
+    provider::{AiProvider, ProviderError, ProviderResult, check_budget},
+    usage::TokenUsage,
+};
+
+pub struct GeneratedImage {
+    pub media_type: String,
+    pub data: Vec<u8>,
+    // text some models return alongside the image
+    pub caption: Option<String>,
+    pub model: String,
+    pub usage: Option<TokenUsage>,
+    pub price: Option<ModelPrice>,
+}
+
+impl GeneratedImage {
+    pub fn filename(&self) -> String {
+        let extension = match self.media_type.as_str() {
+            "image/jpeg" => "jpg",
+            "image/webp" => "webp",
+            _ => "png",
+        };
+        format!("generated.{extension}")
+    }
+}
+
+#[derive(Debug)]
+pub struct ImageSetup {
+    pub provider: AiProvider,
+    pub api_key: SecretString,
+}
+
+impl ImageSetup {
+    pub async fn generate(
+        &self,
+        settings: &ProvidersSettings,
+        prompt: &str,
+    ) -> Result<GeneratedImage> {
+        match self.provider {
+            AiProvider::OpenAi => {
+                openai::image::generate(&settings.openai, &self.api_key, prompt).await
+            }
+            AiProvider::Google => {
+                gemini::image::generate(&settings.google, &self.api_key, prompt).await
+            }
+            ref p => bail!("{p} can't generate images"),
+        }
+    }
+}
+
+// Prefers the provider of the active model, otherwise whichever image capable provider the user
+// has a key for.
+pub fn pick_image_provider(
+    state: &AppState,
+    conn: &mut MysqlConnection,
+    user_id: &str,
+) -> ProviderResult<ImageSetup> {
+    check_budget(state, conn, user_id)?;
+
+    let active = state
+        .service_container
+        .active_model_service
+        .get_for_user(conn, user_id)
+        .context("query active_model")?
+        .and_then(|a| a.provider.parse::<AiProvider>().ok());
+
+    let mut candidates = vec![AiProvider::OpenAi, AiProvider::Google];
+    if let Some(pos) = active.and_then(|a| candidates.iter().position(|p| *p == a)) {
+        candidates.swap(0, pos);
+    }
+
+    candidates
+        .into_iter()
+        .find_map(|provider| {
+            state
+                .service_container
+                .api_key_service
+                .get_and_decrypt(conn, user_id, &provider.to_string())
+                .ok()
+                .map(|api_key| ImageSetup { provider, api_key })
+        })
+        .ok_or(ProviderError::NoImageProvider)
+}
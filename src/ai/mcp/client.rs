@@ -0,0 +1,249 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use secrecy::SecretString;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{
+    configuration::McpSettings,
+    models::mcp_server::{McpServer, McpTransport},
+};
+
+use super::transport::{HttpTransport, StdioTransport, Transport};
+
+const PROTOCOL_VERSION: &str = "2025-06-18";
+// Guards against a server that keeps handing out cursors.
+const MAX_TOOL_PAGES: usize = 10;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteTool {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default = "empty_schema")]
+    pub input_schema: Value,
+}
+
+fn empty_schema() -> Value {
+    json!({ "type": "object", "properties": {} })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListToolsResult {
+    tools: Vec<RemoteTool>,
+    next_cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallToolResult {
+    #[serde(default)]
+    content: Vec<Content>,
+    #[serde(default)]
+    pub is_error: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Content {
+    Text {
+        text: String,
+    },
+    Image {
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    Audio {
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    Resource {
+        resource: EmbeddedResource,
+    },
+    ResourceLink {
+        uri: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddedResource {
+    uri: String,
+    text: Option<String>,
+}
+
+impl CallToolResult {
+    // Only text reaches the model, anything else is described by a placeholder.
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .map(|c| match c {
+                Content::Text { text } => text.clone(),
+                Content::Image { mime_type } => format!("[{mime_type} image]"),
+                Content::Audio { mime_type } => format!("[{mime_type} audio]"),
+                Content::Resource { resource } => resource
+                    .text
+                    .clone()
+                    .unwrap_or_else(|| format!("[resource {}]", resource.uri)),
+                Content::ResourceLink { uri } => format!("[resource {uri}]"),
+                Content::Other => "[unsupported content]".to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+pub struct McpClient {
+    transport: Transport,
+    next_id: AtomicU64,
+    request_timeout: Duration,
+}
+
+impl McpClient {
+    pub async fn connect(
+        server: &McpServer,
+        api_key: Option<SecretString>,
+        settings: &McpSettings,
+    ) -> Result<Self> {
+        let transport = match server.transport() {
+            Some(McpTransport::Stdio) => {
+                let command = server.command.as_deref().context("server has no command")?;
+                let args = server.args();
+                // Checked again here in case the operator has since taken the command away.
+                if !settings.allows_stdio(command, &args) {
+                    bail!("'{command}' is not allowed as an MCP server command");
+                }
+                Transport::Stdio(StdioTransport::spawn(&server.name, command, &args)?)
+            }
+            Some(McpTransport::Http) => {
+                let url = server.url.clone().context("server has no url")?;
+                let client = reqwest::Client::builder()
+                    .connect_timeout(settings.connect_timeout())
+                    .build()?;
+                Transport::Http(HttpTransport::new(client, url, api_key))
+            }
+            None => bail!("Unknown transport '{}'", server.transport),
+        };
+
+        let client = Self {
+            transport,
+            next_id: AtomicU64::new(1),
+            request_timeout: settings.request_timeout(),
+        };
+
+        match tokio::time::timeout(settings.connect_timeout(), client.initialize()).await {
+            Ok(Ok(())) => Ok(client),
+            Ok(Err(e)) => {
+                client.close().await;
+                Err(e)
+            }
+            Err(_) => {
+                client.close().await;
+                bail!("MCP server did not initialize in time")
+            }
+        }
+    }
+
+    async fn initialize(&self) -> Result<()> {
+        let result = self
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }),
+            )
+            .await
+            .context("initialize")?;
+
+        let version = result
+            .get("protocolVersion")
+            .and_then(Value::as_str)
+            .unwrap_or(PROTOCOL_VERSION);
+        self.transport.set_protocol_version(version);
+
+        self.transport
+            .notify(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+            .await
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+
+        let response =
+            tokio::time::timeout(self.request_timeout, self.transport.request(id, &message))
+                .await
+                .map_err(|_| {
+                    anyhow!(
+                        "MCP server did not answer within {}s",
+                        self.request_timeout.as_secs()
+                    )
+                })??;
+
+        if let Some(error) = response.get("error") {
+            let message = error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error");
+            bail!("MCP error: {message}");
+        }
+
+        response
+            .get("result")
+            .cloned()
+            .context("MCP response has no result")
+    }
+
+    pub async fn list_tools(&self) -> Result<Vec<RemoteTool>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        for _ in 0..MAX_TOOL_PAGES {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let page: ListToolsResult =
+                serde_json::from_value(self.request("tools/list", params).await?)
+                    .context("tools/list result")?;
+
+            tools.extend(page.tools);
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        Ok(tools)
+    }
+
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<CallToolResult> {
+        let result = self
+            .request(
+                "tools/call",
+                json!({ "name": name, "arguments": arguments }),
+            )
+            .await?;
+        serde_json::from_value(result).context("tools/call result")
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.transport.is_closed()
+    }
+
+    pub async fn close(&self) {
+        self.transport.close().await;
+    }
+}
@@ -0,0 +1,285 @@
+pub mod client;
+pub mod tool;
+pub mod transport;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, bail};
+use futures_util::future::join_all;
+use secrecy::SecretString;
+use tower_sessions_redis_store::fred::prelude::Pool;
+
+use crate::{app::AppState, configuration::McpSettings, infra, models::mcp_server::McpServer};
+
+use self::{
+    client::{McpClient, RemoteTool},
+    tool::McpTool,
+};
+
+use super::tools::Tool;
+
+// A server that failed to connect isn't retried on every message while it is down.
+const RETRY_AFTER: Duration = Duration::from_secs(60);
+const MIN_REAP_INTERVAL: Duration = Duration::from_secs(10);
+// Closes are sent to every process, the workers hold sessions of their own.
+const CLOSE_CHANNEL: &str = "mcp-close";
+
+pub struct McpSession {
+    pub client: McpClient,
+    user_id: String,
+    tools: Mutex<Vec<RemoteTool>>,
+    last_used: Mutex<Instant>,
+}
+
+impl McpSession {
+    pub fn tools(&self) -> Vec<RemoteTool> {
+        self.tools.lock().expect("mcp session poisoned").clone()
+    }
+
+    pub async fn refresh_tools(&self) -> Result<Vec<RemoteTool>> {
+        let tools = self.client.list_tools().await?;
+        *self.tools.lock().expect("mcp session poisoned") = tools.clone();
+        Ok(tools)
+    }
+
+    fn touch(&self) {
+        *self.last_used.lock().expect("mcp session poisoned") = Instant::now();
+    }
+
+    fn idle_for(&self) -> Duration {
+        self.last_used
+            .lock()
+            .expect("mcp session poisoned")
+            .elapsed()
+    }
+}
+
+// Connections to the users' MCP servers, keyed by server id. They are opened on first use, shared
+// by all of the user's chats and closed once idle.
+pub struct McpManager {
+    settings: McpSettings,
+    sessions: tokio::sync::Mutex<HashMap<u64, Arc<McpSession>>>,
+    failures: Mutex<HashMap<u64, Instant>>,
+    cache: Pool,
+}
+
+impl std::fmt::Debug for McpManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("McpManager")
+            .field("settings", &self.settings)
+            .finish_non_exhaustive()
+    }
+}
+
+impl McpManager {
+    pub fn new(settings: McpSettings, cache: Pool) -> Self {
+        Self {
+            settings,
+            sessions: Default::default(),
+            failures: Default::default(),
+            cache,
+        }
+    }
+
+    pub async fn session(
+        &self,
+        server: &McpServer,
+        api_key: Option<SecretString>,
+    ) -> Result<Arc<McpSession>> {
+        if let Some(session) = self.existing(server).await {
+            return Ok(session);
+        }
+
+        if let Some(failed_at) = self
+            .failures
+            .lock()
+            .expect("mcp manager poisoned")
+            .get(&server.id)
+            && failed_at.elapsed() < RETRY_AFTER
+        {
+            bail!("MCP server failed to connect recently");
+        }
+
+        // Connecting happens outside the lock so a slow server doesn't hold up everyone else's.
+        let connected = async {
+            let client = McpClient::connect(server, api_key, &self.settings).await?;
+            let tools = client.list_tools().await?;
+            anyhow::Ok((client, tools))
+        }
+        .await;
+
+        let (client, tools) = match connected {
+            Ok(connected) => connected,
+            Err(e) => {
+                self.failures
+                    .lock()
+                    .expect("mcp manager poisoned")
+                    .insert(server.id, Instant::now());
+                return Err(e);
+            }
+        };
+        self.failures
+            .lock()
+            .expect("mcp manager poisoned")
+            .remove(&server.id);
+
+        tracing::info!(
+            server = server.name,
+            tools = tools.len(),
+            "MCP server connected"
+        );
+
+        let session = Arc::new(McpSession {
+            client,
+            user_id: server.user_id.clone(),
+            tools: Mutex::new(tools),
+            last_used: Mutex::new(Instant::now()),
+        });
+
+        let mut sessions = self.sessions.lock().await;
+        // Another chat connected at the same time, keep theirs.
+        if let Some(existing) = sessions.get(&server.id).filter(|s| !s.client.is_closed()) {
+            let existing = existing.clone();
+            drop(sessions);
+            session.client.close().await;
+            return Ok(existing);
+        }
+        sessions.insert(server.id, session.clone());
+
+        Ok(session)
+    }
+
+    async fn existing(&self, server: &McpServer) -> Option<Arc<McpSession>> {
+        let mut sessions = self.sessions.lock().await;
+        let session = sessions.get(&server.id)?.clone();
+
+        if session.client.is_closed() || session.user_id != server.user_id {
+            sessions.remove(&server.id);
+            drop(sessions);
+            session.client.close().await;
+            return None;
+        }
+
+        session.touch();
+        Some(session)
+    }
+
+    // Lets the next use try the server again straight away, e.g. when the user asks for its tools.
+    pub fn clear_failure(&self, server_id: u64) {
+        self.failures
+            .lock()
+            .expect("mcp manager poisoned")
+            .remove(&server_id);
+    }
+
+    pub async fn close(&self, server_id: u64) {
+        self.clear_failure(server_id);
+        let session = self.sessions.lock().await.remove(&server_id);
+        if let Some(session) = session {
+            session.client.close().await;
+        }
+    }
+
+    // Closes the session here and in every other process, e.g. once the server is deleted.
+    pub async fn close_everywhere(&self, server_id: u64) {
+        self.close(server_id).await;
+        if let Err(e) =
+            infra::redis::publish(&self.cache, CLOSE_CHANNEL, server_id.to_string()).await
+        {
+            tracing::warn!(error = ?e, "Failed to send MCP session close");
+        }
+    }
+
+    pub async fn run_close_listener(self: Arc<Self>) {
+        infra::redis::subscribe(self.cache.clone(), CLOSE_CHANNEL, |payload| {
+            let manager = self.clone();
+            async move {
+                match payload.parse::<u64>() {
+                    Ok(server_id) => manager.close(server_id).await,
+                    Err(e) => tracing::warn!(error = ?e, "Dropping malformed MCP session close"),
+                }
+            }
+        })
+        .await;
+    }
+
+    pub async fn run_reaper(self: Arc<Self>) {
+        let idle_timeout = self.settings.idle_timeout();
+        let mut interval = tokio::time::interval((idle_timeout / 2).max(MIN_REAP_INTERVAL));
+
+        loop {
+            interval.tick().await;
+
+            let expired: Vec<_> = {
+                let mut sessions = self.sessions.lock().await;
+                let ids: Vec<_> = sessions
+                    .iter()
+                    .filter(|(_, s)| s.client.is_closed() || s.idle_for() >= idle_timeout)
+                    .map(|(id, _)| *id)
+                    .collect();
+                ids.iter().filter_map(|id| sessions.remove(id)).collect()
+            };
+
+            for session in expired {
+                session.client.close().await;
+            }
+        }
+    }
+}
+
+// The enabled tools of every MCP server the user has registered. Servers that can't be reached
+// are left out so the reply still goes ahead.
+pub async fn load_tools(state: &AppState, user_id: &str) -> Result<Vec<Arc<dyn Tool>>> {
+    let servers = {
+        let mut conn = state.db_pool.get()?;
+        let service = &state.service_container.mcp_server_service;
+        service
+            .list(&mut conn, user_id)?
+            .into_iter()
+            .map(|server| {
+                let api_key = service.get_api_key(&mut conn, user_id, &server);
+                (server, api_key)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let connected = join_all(servers.into_iter().map(|(server, api_key)| async move {
+        let session = state.mcp_manager.session(&server, api_key).await;
+        (server, session)
+    }))
+    .await;
+
+    let mut tools: Vec<Arc<dyn Tool>> = Vec::new();
+    let mut names = HashSet::new();
+    for (server, session) in connected {
+        let session = match session {
+            Ok(session) => session,
+            Err(e) => {
+                tracing::warn!(server = server.name, error = ?e, "MCP server unavailable");
+                continue;
+            }
+        };
+
+        for remote in session.tools() {
+            if !server.is_enabled(&remote.name) {
+                continue;
+            }
+            // Tools are called by name, a second one with the same name could never be reached.
+            let tool = McpTool::new(&server.name, remote, session.clone());
+            if !names.insert(tool.definition().name) {
+                tracing::warn!(
+                    server = server.name,
+                    "Skipping MCP tool with a duplicate name"
+                );
+                continue;
+            }
+            tools.push(Arc::new(tool));
+        }
+    }
+
+    Ok(tools)
+}
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::ai::tools::{Tool, ToolContext, ToolDefinition, ToolResponse};
+
+use super::{McpSession, client::RemoteTool};
+
+const MAX_NAME_LEN: usize = 64;
+// Hex characters of the hash that keeps truncated names apart.
+const NAME_HASH_LEN: usize = 8;
+// Keeps a server that returns whole documents from filling the context window.
+const MAX_OUTPUT_CHARS: usize = 20_000;
+
+pub struct McpTool {
+    definition: ToolDefinition,
+    remote_name: String,
+    session: Arc<McpSession>,
+}
+
+impl McpTool {
+    pub fn new(server: &str, remote: RemoteTool, session: Arc<McpSession>) -> Self {
+        let description = match &remote.description {
+            Some(description) => format!("{description} (from the {server} MCP server)"),
+            None => format!("{} from the {server} MCP server", remote.name),
+        };
+
+        Self {
+            definition: ToolDefinition {
+                name: tool_name(server, &remote.name),
+                description,
+                parameters: remote.input_schema,
+            },
+            remote_name: remote.name,
+            session,
+        }
+    }
+}
+
+// Prefixed with the server so tools from different servers can't collide, and squeezed into the
+// names the providers accept. Names that are too long end in a hash of the full name instead, so
+// tools sharing a long prefix still get different names.
+pub fn tool_name(server: &str, tool: &str) -> String {
+    let full = format!("{server}__{tool}");
+    let name: String = full
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.len() <= MAX_NAME_LEN {
+        return name;
+    }
+
+    let hash: String = Sha256::digest(full.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .take(NAME_HASH_LEN / 2)
+        .collect();
+    format!("{}_{hash}", &name[..MAX_NAME_LEN - NAME_HASH_LEN - 1])
+}
+
+#[async_trait]
+impl Tool for McpTool {
+    fn definition(&self) -> ToolDefinition {
+        self.definition.clone()
+    }
+
+    async fn call(&self, _ctx: &ToolContext, arguments: Value) -> Result<ToolResponse> {
+        if !arguments.is_object() {
+            bail!("Arguments must be a JSON object");
+        }
+
+        self.session.touch();
+        let result = self
+            .session
+            .client
+            .call_tool(&self.remote_name, arguments)
+            .await?;
+
+        let mut text = result.text();
+        if let Some((end, _)) = text.char_indices().nth(MAX_OUTPUT_CHARS) {
+            text.truncate(end);
+            text.push_str("\n[output truncated]");
+        }
+
+        if result.is_error {
+            bail!(text);
+        }
+        Ok(text.into())
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    collections::HashMap,
+    process::Stdio,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use eventsource_stream::Eventsource;
+use futures_util::StreamExt;
+use reqwest::{StatusCode, header};
+use secrecy::{ExposeSecret, SecretString};
+use serde_json::{Value, json};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::oneshot,
+};
+
+const SESSION_HEADER: &str = "mcp-session-id";
+const PROTOCOL_HEADER: &str = "mcp-protocol-version";
+// Stdio servers get a clean environment so they can't read the app's secrets, only what they
+// need to find and run their binaries.
+const PASSTHROUGH_ENV: [&str; 3] = ["PATH", "HOME", "LANG"];
+
+pub enum Transport {
+    Stdio(StdioTransport),
+    Http(HttpTransport),
+}
+
+impl Transport {
+    // Sends a request and waits for the response carrying the same id.
+    pub async fn request(&self, id: u64, message: &Value) -> Result<Value> {
+        match self {
+            Transport::Stdio(t) => t.request(id, message).await,
+            Transport::Http(t) => t.request(id, message).await,
+        }
+    }
+
+    pub async fn notify(&self, message: &Value) -> Result<()> {
+        match self {
+            Transport::Stdio(t) => t.send(message).await,
+            Transport::Http(t) => t.notify(message).await,
+        }
+    }
+
+    pub fn set_protocol_version(&self, version: &str) {
+        if let Transport::Http(t) = self {
+            *t.protocol_version.lock().expect("mcp transport poisoned") = Some(version.to_owned());
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        match self {
+            Transport::Stdio(t) => t.closed.load(Ordering::Relaxed),
+            Transport::Http(t) => t.closed.load(Ordering::Relaxed),
+        }
+    }
+
+    pub async fn close(&self) {
+        match self {
+            Transport::Stdio(t) => t.close().await,
+            Transport::Http(t) => t.close().await,
+        }
+    }
+}
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>;
+
+pub struct StdioTransport {
+    child: tokio::sync::Mutex<Child>,
+    stdin: Arc<tokio::sync::Mutex<ChildStdin>>,
+    pending: Pending,
+    closed: Arc<AtomicBool>,
+}
+
+// Drops the waiting entry when a request finishes or is abandoned, so a response that arrives
+// after a timeout has nowhere to go.
+struct PendingGuard<'a> {
+    pending: &'a Pending,
+    id: u64,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.pending
+            .lock()
+            .expect("mcp transport poisoned")
+            .remove(&self.id);
+    }
+}
+
+impl StdioTransport {
+    pub fn spawn(name: &str, command: &str, args: &[String]) -> Result<Self> {
+        let mut cmd = Command::new(command);
+        cmd.args(args)
+            .env_clear()
+            .envs(
+                PASSTHROUGH_ENV
+                    .iter()
+                    .filter_map(|key| std::env::var_os(key).map(|value| (key.to_string(), value))),
+            )
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("Could not start '{command}'"))?;
+        let stdin = child.stdin.take().context("stdin not piped")?;
+        let stdout = child.stdout.take().context("stdout not piped")?;
+        let stderr = child.stderr.take().context("stderr not piped")?;
+
+        let transport = Self {
+            child: tokio::sync::Mutex::new(child),
+            stdin: Arc::new(tokio::sync::Mutex::new(stdin)),
+            pending: Pending::default(),
+            closed: Arc::new(AtomicBool::new(false)),
+        };
+
+        tokio::spawn(read_stdout(
+            stdout,
+            transport.stdin.clone(),
+            transport.pending.clone(),
+            transport.closed.clone(),
+        ));
+
+        // Servers log to stderr, it has to be drained or a chatty server blocks on a full pipe.
+        let name = name.to_owned();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                tracing::debug!(server = name, "{line}");
+            }
+        });
+
+        Ok(transport)
+    }
+
+    async fn request(&self, id: u64, message: &Value) -> Result<Value> {
+        let (tx, rx) = oneshot::channel();
+        self.pending
+            .lock()
+            .expect("mcp transport poisoned")
+            .insert(id, tx);
+        let _guard = PendingGuard {
+            pending: &self.pending,
+            id,
+        };
+
+        self.send(message).await?;
+        rx.await.map_err(|_| anyhow!("MCP server exited"))
+    }
+
+    async fn send(&self, message: &Value) -> Result<()> {
+        if self.closed.load(Ordering::Relaxed) {
+            bail!("MCP server exited");
+        }
+        write_line(&self.stdin, message).await
+    }
+
+    async fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        if let Err(e) = self.child.lock().await.kill().await {
+            tracing::debug!(error = ?e, "Could not kill MCP server");
+        }
+    }
+}
+
+async fn write_line(stdin: &tokio::sync::Mutex<ChildStdin>, message: &Value) -> Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(&line).await?;
+    stdin.flush().await?;
+    Ok(())
+}
+
+// Hands responses to whoever is waiting on their id. Requests from the server are answered here,
+// pings are the only ones a client without capabilities has to support.
+async fn read_stdout(
+    stdout: ChildStdout,
+    stdin: Arc<tokio::sync::Mutex<ChildStdin>>,
+    pending: Pending,
+    closed: Arc<AtomicBool>,
+) {
+    let mut lines = BufReader::new(stdout).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            tracing::debug!("Ignoring non JSON line from MCP server");
+            continue;
+        };
+
+        match (message.get("id"), message.get("method")) {
+            (Some(id), Some(method)) => {
+                let reply = if method == "ping" {
+                    json!({ "jsonrpc": "2.0", "id": id, "result": {} })
+                } else {
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": -32601, "message": "Method not found" }
+                    })
+                };
+                if write_line(&stdin, &reply).await.is_err() {
+                    break;
+                }
+            }
+            (Some(id), None) => {
+                let waiting = id
+                    .as_u64()
+                    .and_then(|id| pending.lock().expect("mcp transport poisoned").remove(&id));
+                if let Some(tx) = waiting {
+                    let _ = tx.send(message);
+                }
+            }
+            // notifications
+            _ => {}
+        }
+    }
+
+    closed.store(true, Ordering::Relaxed);
+    // wakes everyone still waiting with an error
+    pending.lock().expect("mcp transport poisoned").clear();
+}
+
+pub struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<SecretString>,
+    session_id: Mutex<Option<String>>,
+    protocol_version: Mutex<Option<String>>,
+    closed: AtomicBool,
+}
+
+impl HttpTransport {
+    pub fn new(client: reqwest::Client, url: String, api_key: Option<SecretString>) -> Self {
+        Self {
+            client,
+            url,
+            api_key,
+            session_id: Mutex::new(None),
+            protocol_version: Mutex::new(None),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    fn session_id(&self) -> Option<String> {
+        self.session_id
+            .lock()
+            .expect("mcp transport poisoned")
+            .clone()
+    }
+
+    fn build(&self, method: reqwest::Method) -> reqwest::RequestBuilder {
+        let mut builder = self
+            .client
+            .request(method, &self.url)
+            .header(header::ACCEPT, "application/json, text/event-stream");
+
+        if let Some(api_key) = &self.api_key {
+            builder = builder.bearer_auth(api_key.expose_secret());
+        }
+        if let Some(session_id) = self.session_id() {
+            builder = builder.header(SESSION_HEADER, session_id);
+        }
+        if let Some(version) = self
+            .protocol_version
+            .lock()
+            .expect("mcp transport poisoned")
+            .clone()
+        {
+            builder = builder.header(PROTOCOL_HEADER, version);
+        }
+        builder
+    }
+
+    async fn post(&self, message: &Value) -> Result<reqwest::Response> {
+        let res = self
+            .build(reqwest::Method::POST)
+            .json(message)
+            .send()
+            .await
+            .context("MCP request failed")?;
+
+        // The server forgot the session, a new connection has to initialize again.
+        if res.status() == StatusCode::NOT_FOUND && self.session_id().is_some() {
+            self.closed.store(true, Ordering::Relaxed);
+            bail!("MCP session expired");
+        }
+
+        let res = res.error_for_status().context("MCP request HTTP error")?;
+        if let Some(session_id) = res
+            .headers()
+            .get(SESSION_HEADER)
+            .and_then(|v| v.to_str().ok())
+        {
+            *self.session_id.lock().expect("mcp transport poisoned") = Some(session_id.to_owned());
+        }
+
+        Ok(res)
+    }
+
+    async fn request(&self, id: u64, message: &Value) -> Result<Value> {
+        let res = self.post(message).await?;
+
+        let is_stream = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+
+        if !is_stream {
+            return res.json().await.context("MCP response JSON decode failed");
+        }
+
+        // The response may be preceded by notifications about the request's progress.
+        let mut events = res.bytes_stream().eventsource();
+        while let Some(event) = events.next().await {
+            let event = event.context("MCP event stream failed")?;
+            let Ok(message) = serde_json::from_str::<Value>(&event.data) else {
+                continue;
+            };
+            if message.get("id").and_then(Value::as_u64) == Some(id)
+                && message.get("method").is_none()
+            {
+                return Ok(message);
+            }
+        }
+
+        bail!("MCP server closed the stream without responding")
+    }
+
+    async fn notify(&self, message: &Value) -> Result<()> {
+        self.post(message).await?;
+        Ok(())
+    }
+
+    // Tells the server it can drop the session, failures don't matter as it expires either way.
+    async fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        if self.session_id().is_none() {
+            return;
+        }
+        if let Err(e) = self.build(reqwest::Method::DELETE).send().await {
+            tracing::debug!(error = ?e, "Could not end MCP session");
+        }
+    }
+}
@@ -1,7 +1,16 @@
 pub mod anthropic;
+pub mod attachment;
+pub mod context;
+pub mod custom;
 pub mod gemini;
 pub mod handler;
+pub mod image;
+pub mod mcp;
 pub mod openai;
 pub mod openrouter;
+pub mod pricing;
 pub mod provider;
 pub mod reasoning;
+pub mod registry;
+pub mod tools;
+pub mod usage;
@@ -1,28 +1,28 @@
 use anyhow::{Context, Result, anyhow};
-use futures_util::StreamExt;
-use reqwest::Client;
 use reqwest_eventsource::{Event, EventSource};
 use secrecy::{ExposeSecret, SecretString};
 use serde::Deserialize;
-use std::sync::Arc;
 use tracing::{info, warn};
 
 use crate::{
     ai::{
+        attachment::ImageMap,
         handler::{
-            StreamResult, create_title_prompt, done, send_error, send_reasoning_delta,
-            send_text_delta,
+            Completion, StreamResult, StreamStep, cancel_stream, done, next_event, send_error,
+            send_reasoning_delta, send_text_delta,
         },
-        openai::request::Turn,
-        reasoning::EffortLevel,
+        openai::request::{InputItem, InputPart, ToolItem, Turn, TurnContent},
+        provider::StreamRequest,
+        tools::{ToolCall, ToolTurn},
+        usage::TokenUsage,
     },
+    configuration::ProviderSettings,
     models::message::Message,
-    services::sse_manager::SseManager,
 };
 
 use super::{
     model::OpenAiModel,
-    request::{OpenAiRequest, RESPONSES_URL},
+    request::{OpenAiRequest, RESPONSES_PATH},
 };
 
 #[derive(Deserialize, Debug)]
@@ -49,6 +49,33 @@ struct ResponseObject {
     id: String,
     output: Option<Vec<MessageOutput>>,
     error: Option<OpenAIError>,
+    usage: Option<ResponseUsage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ResponseUsage {
+    input_tokens: u32,
+    output_tokens: u32,
+    output_tokens_details: Option<OutputTokensDetails>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OutputTokensDetails {
+    #[serde(default)]
+    reasoning_tokens: u32,
+}
+
+impl From<ResponseUsage> for TokenUsage {
+    fn from(value: ResponseUsage) -> Self {
+        Self {
+            input_tokens: value.input_tokens,
+            output_tokens: value.output_tokens,
+            reasoning_tokens: value
+                .output_tokens_details
+                .map(|d| d.reasoning_tokens)
+                .unwrap_or_default(),
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -61,6 +88,11 @@ struct MessageOutput {
 
     #[serde(default)]
     summary: Vec<SummaryPart>,
+
+    // set on function_call outputs
+    call_id: Option<String>,
+    name: Option<String>,
+    arguments: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -82,43 +114,75 @@ struct OpenAIError {
     message: String,
 }
 
-const INSTRUCTIONS: &str = "All code that you generate MUST be generated so that it is correctly rendered inside of a <code> block. Keep decoration in text to a minimum, just respond with clear information, in markdown format. RemarkGFM is used to help parse your output.";
-
 pub async fn stream(
-    api_key: SecretString,
-    sse_manager: Arc<SseManager>,
-    user_id: String,
-    chat_id: String,
-    model: OpenAiModel,
-    reasoning: Option<EffortLevel>,
-    messages: Vec<Message>,
+    settings: &ProviderSettings,
+    req: &StreamRequest,
 ) -> Result<Option<StreamResult>> {
-    let request_body = OpenAiRequest::chat(
+    let StreamRequest {
+        api_key,
+        sse: sse_manager,
+        user_id,
+        chat_id,
         model,
-        build_turns(&messages),
-        reasoning,
-        Some(INSTRUCTIONS.into()),
-    )?;
+        effort: reasoning,
+        sampling,
+        capabilities,
+        system_prompt,
+        history: messages,
+        images,
+        tools,
+        turns,
+        cancel,
+        ..
+    } = req;
 
-    let req = Client::new()
-        .post(RESPONSES_URL)
+    let request_body = OpenAiRequest::chat(
+        model,
+        build_input(messages, images, turns),
+        reasoning.clone(),
+        Some(system_prompt),
+        capabilities.max_output_tokens,
+    )?
+    .with_sampling(*sampling)
+    .with_tools(tools);
+
+    let req = settings
+        .client()?
+        .post(settings.endpoint(RESPONSES_PATH))
         .bearer_auth(api_key.expose_secret())
         .json(&request_body);
 
     let mut es = EventSource::new(req).context("connect sse")?;
+    let mut content_final = String::new();
     let mut reasoning_final = String::new();
 
-    while let Some(ev) = es.next().await {
+    loop {
+        let ev = match next_event(&mut es, cancel).await {
+            StreamStep::Event(ev) => ev,
+            StreamStep::Finished => break,
+            StreamStep::Cancelled => {
+                return Ok(cancel_stream(
+                    sse_manager,
+                    user_id,
+                    chat_id,
+                    content_final,
+                    reasoning_final,
+                )
+                .await);
+            }
+        };
+
         match ev {
             Ok(Event::Open) => info!("OpenAI SSE opened"),
             Ok(Event::Message(msg)) => {
                 let evt: StreamEvent = serde_json::from_str(&msg.data)?;
                 match evt {
                     StreamEvent::ResponseOutputTextDelta { delta } => {
-                        send_text_delta(&sse_manager, &user_id, &chat_id, &delta).await;
+                        send_text_delta(sse_manager, user_id, chat_id, &delta).await;
+                        content_final.push_str(&delta);
                     }
                     StreamEvent::ResponseReasoningSummaryTextDelta { delta } => {
-                        send_reasoning_delta(&sse_manager, &user_id, &chat_id, &delta).await;
+                        send_reasoning_delta(sse_manager, user_id, chat_id, &delta).await;
                         reasoning_final.push_str(&delta);
                     }
                     StreamEvent::ResponseCompleted { response } => {
@@ -133,26 +197,41 @@ pub async fn stream(
                         let reasoning_opt =
                             (!reasoning_final.is_empty()).then(|| reasoning_final.clone());
 
+                        let tool_calls = extract_tool_calls(outputs);
+                        if !tool_calls.is_empty() {
+                            return Ok(Some(StreamResult {
+                                msg_id: response.id,
+                                content: content_final,
+                                reasoning: reasoning_opt,
+                                reasoning_signature: None,
+                                tool_calls,
+                                usage: response.usage.map(TokenUsage::from),
+                            }));
+                        }
+
                         if let Some(final_content) = extract_message_text(outputs) {
-                            done(&sse_manager, &user_id, &chat_id, &response.id).await;
+                            done(sse_manager, user_id, chat_id, &response.id).await;
 
-                            return Ok(Some(super::handler::StreamResult {
+                            return Ok(Some(StreamResult {
                                 msg_id: response.id,
                                 content: final_content,
                                 reasoning: reasoning_opt,
+                                reasoning_signature: None,
+                                tool_calls: Vec::new(),
+                                usage: response.usage.map(TokenUsage::from),
                             }));
                         }
                     }
                     StreamEvent::ResponseFailed { response } => {
                         let e = response.error.map(|e| e.message).unwrap_or_default();
-                        send_error(&sse_manager, &user_id, &chat_id, &e).await;
+                        send_error(sse_manager, user_id, chat_id, &e).await;
                         return Err(anyhow!("OpenAI failed: {e}"));
                     }
                     StreamEvent::Unknown => warn!("unknown event"),
                 }
             }
             Err(e) => {
-                send_error(&sse_manager, &user_id, &chat_id, &e.to_string()).await;
+                send_error(sse_manager, user_id, chat_id, &e.to_string()).await;
                 return Err(e.into());
             }
         }
@@ -161,37 +240,135 @@ pub async fn stream(
     Ok(None)
 }
 
-fn build_turns(history: &[Message]) -> Vec<Turn> {
-    history
+fn build_input<'a>(
+    history: &'a [Message],
+    images: &'a ImageMap,
+    turns: &'a [ToolTurn],
+) -> Vec<InputItem<'a>> {
+    let mut items: Vec<InputItem<'a>> = history
         .iter()
-        .map(|m| Turn {
-            role: match m.role.as_str() {
-                "assistant" => "assistant",
-                _ => "user",
-            }
-            .into(),
-            content: &m.body,
+        .map(|m| {
+            let content = match images.get(&m.id) {
+                Some(images) => TurnContent::Parts(
+                    std::iter::once(InputPart::InputText { text: &m.body })
+                        .chain(images.iter().map(|i| InputPart::InputImage {
+                            image_url: i.data_url(),
+                        }))
+                        .collect(),
+                ),
+                None => TurnContent::Text(&m.body),
+            };
+
+            InputItem::Message(Turn {
+                role: match m.role.as_str() {
+                    "assistant" => "assistant",
+                    _ => "user",
+                },
+                content,
+            })
         })
-        .collect()
+        .collect();
+
+    for turn in turns {
+        if !turn.text.is_empty() {
+            items.push(InputItem::Message(Turn {
+                role: "assistant",
+                content: TurnContent::Text(&turn.text),
+            }));
+        }
+        items.extend(turn.calls.iter().map(|c| {
+            InputItem::Tool(ToolItem::FunctionCall {
+                call_id: &c.id,
+                name: &c.name,
+                arguments: c.arguments.to_string(),
+            })
+        }));
+        items.extend(turn.outputs.iter().map(|o| {
+            InputItem::Tool(ToolItem::FunctionCallOutput {
+                call_id: &o.call_id,
+                output: &o.content,
+            })
+        }));
+    }
+
+    items
+}
+
+const MODELS_PATH: &str = "models";
+
+// Embedding, audio and image models share the listing with the chat ones.
+const NON_CHAT_MARKERS: &[&str] = &[
+    "audio",
+    "embedding",
+    "image",
+    "realtime",
+    "search",
+    "transcribe",
+    "tts",
+];
+
+#[derive(Debug, Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelList {
+    data: Vec<ModelEntry>,
 }
 
-pub async fn generate_title(
+fn is_chat_model(id: &str) -> bool {
+    let family = id.starts_with("gpt-")
+        || (id.starts_with('o') && id[1..].starts_with(|c: char| c.is_ascii_digit()));
+
+    family && !NON_CHAT_MARKERS.iter().any(|m| id.contains(m))
+}
+
+pub async fn list_models(
+    settings: &ProviderSettings,
     api_key: &SecretString,
-    first_message: &str,
-    model: OpenAiModel,
-) -> Result<String> {
-    let title_prompt = create_title_prompt(first_message);
-    let request_body = OpenAiRequest::prompt(model, &title_prompt, false, None, None)?;
+) -> Result<Vec<String>> {
+    let list: ModelList = settings
+        .client()?
+        .get(settings.endpoint(MODELS_PATH))
+        .bearer_auth(api_key.expose_secret())
+        .timeout(settings.request_timeout())
+        .send()
+        .await
+        .context("OpenAI model list request failed")?
+        .error_for_status()
+        .context("OpenAI model list HTTP error")?
+        .json()
+        .await
+        .context("OpenAI model list JSON decode failed")?;
+
+    Ok(list
+        .data
+        .into_iter()
+        .map(|m| m.id)
+        .filter(|id| is_chat_model(id))
+        .collect())
+}
 
-    let client = Client::new();
+pub async fn complete(
+    settings: &ProviderSettings,
+    api_key: &SecretString,
+    prompt: &str,
+    max_tokens: u32,
+    model: OpenAiModel,
+) -> Result<Completion> {
+    let model = model.to_string();
+    let request_body = OpenAiRequest::prompt(&model, prompt, max_tokens)?;
 
-    let response = client
-        .post(RESPONSES_URL)
+    let response = settings
+        .client()?
+        .post(settings.endpoint(RESPONSES_PATH))
         .bearer_auth(api_key.expose_secret())
+        .timeout(settings.request_timeout())
         .json(&request_body)
         .send()
         .await
-        .context("Failed to send request to OpenAI for title generation")?;
+        .context("Failed to send completion request to OpenAI")?;
 
     let response = response
         .error_for_status()
@@ -202,7 +379,7 @@ pub async fn generate_title(
         .await
         .context("Failed to deserialize OpenAI response object")?;
 
-    let title = response_object
+    let text = response_object
         .output
         .as_deref()
         .unwrap_or(&[])
@@ -212,7 +389,11 @@ pub async fn generate_title(
         .map(|content| content.text.clone())
         .context("OpenAI response did not contain valid output text")?;
 
-    Ok(title)
+    Ok(Completion {
+        text,
+        model,
+        usage: response_object.usage.map(TokenUsage::from),
+    })
 }
 
 fn extract_reasoning_summary(outputs: &[MessageOutput]) -> Option<String> {
@@ -230,6 +411,21 @@ fn extract_reasoning_summary(outputs: &[MessageOutput]) -> Option<String> {
         .filter(|s| !s.is_empty())
 }
 
+fn extract_tool_calls(outputs: &[MessageOutput]) -> Vec<ToolCall> {
+    outputs
+        .iter()
+        .filter(|o| o.output_type == "function_call")
+        .filter_map(|o| {
+            Some(ToolCall {
+                id: o.call_id.clone()?,
+                name: o.name.clone()?,
+                arguments: ToolCall::parse_arguments(o.arguments.as_deref().unwrap_or_default()),
+                signature: None,
+            })
+        })
+        .collect()
+}
+
 fn extract_message_text(outputs: &[MessageOutput]) -> Option<String> {
     outputs
         .iter()
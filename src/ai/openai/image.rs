@@ -0,0 +1,99 @@
+use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::{image::GeneratedImage, pricing::ModelPrice, usage::TokenUsage},
+    configuration::ProviderSettings,
+};
+
+const IMAGES_PATH: &str = "images/generations";
+const IMAGE_MODEL: &str = "gpt-image-1";
+// text input and image output tokens
+const IMAGE_PRICE: ModelPrice = ModelPrice::new(5.0, 40.0);
+
+#[derive(Debug, Serialize)]
+struct ImageRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+    n: u8,
+    size: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageResponse {
+    #[serde(default)]
+    data: Vec<ImageData>,
+    usage: Option<ImageUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageData {
+    b64_json: Option<String>,
+    revised_prompt: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageUsage {
+    #[serde(default)]
+    input_tokens: u32,
+    #[serde(default)]
+    output_tokens: u32,
+}
+
+impl From<ImageUsage> for TokenUsage {
+    fn from(value: ImageUsage) -> Self {
+        Self {
+            input_tokens: value.input_tokens,
+            output_tokens: value.output_tokens,
+            reasoning_tokens: 0,
+        }
+    }
+}
+
+pub async fn generate(
+    settings: &ProviderSettings,
+    api_key: &SecretString,
+    prompt: &str,
+) -> Result<GeneratedImage> {
+    let req_body = ImageRequest {
+        model: IMAGE_MODEL,
+        prompt,
+        n: 1,
+        size: "auto",
+    };
+
+    // No request timeout, generating an image routinely takes longer than a title does.
+    let res: ImageResponse = settings
+        .client()?
+        .post(settings.endpoint(IMAGES_PATH))
+        .bearer_auth(api_key.expose_secret())
+        .json(&req_body)
+        .send()
+        .await
+        .context("OpenAI image request failed")?
+        .error_for_status()
+        .context("OpenAI image request HTTP error")?
+        .json()
+        .await
+        .context("OpenAI image JSON decode failed")?;
+
+    let image = res
+        .data
+        .into_iter()
+        .next()
+        .context("OpenAI returned no image")?;
+    let encoded = image.b64_json.context("OpenAI returned no image data")?;
+
+    Ok(GeneratedImage {
+        media_type: "image/png".to_owned(),
+        data: general_purpose::STANDARD
+            .decode(encoded)
+            .context("OpenAI image is not valid base64")?,
+        caption: image.revised_prompt,
+        model: IMAGE_MODEL.to_owned(),
+        usage: res.usage.map(TokenUsage::from),
+        price: Some(IMAGE_PRICE),
+    })
+}
@@ -1,3 +1,5 @@
 pub mod handler;
+pub mod image;
 pub mod model;
+pub mod provider;
 pub mod request;
@@ -1,7 +1,14 @@
 use serde::{Deserialize, Serialize};
-use strum::{Display, EnumString};
+use strum::{Display, EnumString, VariantNames};
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, EnumString, Display, Serialize, Deserialize)]
+use crate::ai::{
+    pricing::ModelPrice,
+    registry::{Capabilities, TEXT, TEXT_IMAGE},
+};
+
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, EnumString, Display, VariantNames, Serialize, Deserialize,
+)]
 #[strum(ascii_case_insensitive)]
 pub enum OpenAiModel {
     #[serde(rename = "gpt-4o")]
@@ -34,6 +41,35 @@ pub enum OpenAiModel {
 }
 
 impl OpenAiModel {
+    pub fn capabilities(self) -> Capabilities {
+        match self {
+            Self::Gpt4o => Capabilities::new(128_000, TEXT_IMAGE, ModelPrice::new(2.5, 10.0))
+                .with_max_output(16_384)
+                .with_tools(),
+            Self::Gpt41 => Capabilities::new(1_047_576, TEXT_IMAGE, ModelPrice::new(2.0, 8.0))
+                .with_max_output(32_768)
+                .with_tools(),
+            Self::Gpt41Mini => Capabilities::new(1_047_576, TEXT_IMAGE, ModelPrice::new(0.4, 1.6))
+                .with_max_output(32_768)
+                .with_tools(),
+            Self::Gpt41Nano => Capabilities::new(1_047_576, TEXT_IMAGE, ModelPrice::new(0.1, 0.4))
+                .with_max_output(32_768)
+                .with_tools(),
+            Self::O3Mini => Capabilities::new(200_000, TEXT, ModelPrice::new(1.1, 4.4))
+                .with_max_output(100_000)
+                .with_reasoning()
+                .with_tools(),
+            Self::O4Mini => Capabilities::new(200_000, TEXT_IMAGE, ModelPrice::new(1.1, 4.4))
+                .with_max_output(100_000)
+                .with_reasoning()
+                .with_tools(),
+            Self::O3 => Capabilities::new(200_000, TEXT_IMAGE, ModelPrice::new(2.0, 8.0))
+                .with_max_output(100_000)
+                .with_reasoning()
+                .with_tools(),
+        }
+    }
+
     #[inline]
     pub fn requires_reasoning(self) -> bool {
         matches!(self, Self::O3 | Self::O3Mini | Self::O4Mini)
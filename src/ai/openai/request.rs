@@ -1,69 +1,138 @@
 use super::model::OpenAiModel;
-use crate::ai::reasoning::{EffortLevel, Reasoning};
-use serde::{Deserialize, Serialize};
+use crate::ai::{
+    provider::Sampling,
+    reasoning::{EffortLevel, Reasoning},
+    tools::ToolDefinition,
+};
+use serde::Serialize;
+use serde_json::Value;
 
-pub const RESPONSES_URL: &str = "https://api.openai.com/v1/responses";
+pub const RESPONSES_PATH: &str = "responses";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Turn<'a> {
     pub role: &'a str,
-    pub content: &'a str,
+    pub content: TurnContent<'a>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum TurnContent<'a> {
+    Text(&'a str),
+    Parts(Vec<InputPart<'a>>),
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputPart<'a> {
+    InputText { text: &'a str },
+    InputImage { image_url: String },
+}
+
+// Tool calls and their results are items of their own next to the messages.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum InputItem<'a> {
+    Message(Turn<'a>),
+    Tool(ToolItem<'a>),
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolItem<'a> {
+    FunctionCall {
+        call_id: &'a str,
+        name: &'a str,
+        arguments: String,
+    },
+    FunctionCallOutput {
+        call_id: &'a str,
+        output: &'a str,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
 #[serde(untagged)]
 pub enum Input<'a> {
     Text(&'a str),
-    Chat(Vec<Turn<'a>>),
+    Chat(Vec<InputItem<'a>>),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionTool<'a> {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub name: &'a str,
+    pub description: &'a str,
+    pub parameters: &'a Value,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OpenAiRequest<'a> {
     #[serde(rename = "model")]
-    model: OpenAiModel,
+    model: &'a str,
 
     input: Input<'a>,
     stream: bool,
 
     instructions: Option<&'a str>,
     reasoning: Option<Reasoning>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<FunctionTool<'a>>,
 }
 
 impl<'a> OpenAiRequest<'a> {
-    pub fn prompt(
-        model: OpenAiModel,
-        text: &'a str,
-        stream: bool,
-        effort: Option<EffortLevel>,
-        instructions: Option<&'a str>,
-    ) -> anyhow::Result<Self> {
-        Self::new(model, Input::Text(text), stream, effort, instructions)
+    pub fn prompt(model: &'a str, text: &'a str, max_output_tokens: u32) -> anyhow::Result<Self> {
+        Self::new(
+            model,
+            Input::Text(text),
+            false,
+            None,
+            None,
+            Some(max_output_tokens),
+        )
     }
 
     pub fn chat(
-        model: OpenAiModel,
-        turns: Vec<Turn<'a>>,
+        model: &'a str,
+        turns: Vec<InputItem<'a>>,
         effort: Option<EffortLevel>,
         instructions: Option<&'a str>,
+        max_output_tokens: Option<u32>,
     ) -> anyhow::Result<Self> {
-        Self::new(model, Input::Chat(turns), true, effort, instructions)
+        Self::new(
+            model,
+            Input::Chat(turns),
+            true,
+            effort,
+            instructions,
+            max_output_tokens,
+        )
     }
 
     fn new(
-        model: OpenAiModel,
+        model: &'a str,
         input: Input<'a>,
         stream: bool,
         effort: Option<EffortLevel>,
         instructions: Option<&'a str>,
+        max_output_tokens: Option<u32>,
     ) -> anyhow::Result<Self> {
-        let reasoning = if model.requires_reasoning() {
-            Some(Reasoning::new(effort.ok_or_else(|| {
-                anyhow::anyhow!("model {model} requires reasoning")
-            })?))
-        } else {
-            None
-        };
+        // Models outside the known list get reasoning only when an effort was picked for them.
+        let requires_reasoning = model
+            .parse::<OpenAiModel>()
+            .is_ok_and(|m| m.requires_reasoning());
+        if requires_reasoning && effort.is_none() {
+            anyhow::bail!("model {model} requires reasoning");
+        }
+        let reasoning = effort.map(Reasoning::new);
 
         Ok(Self {
             model,
@@ -71,6 +140,32 @@ impl<'a> OpenAiRequest<'a> {
             stream,
             instructions,
             reasoning,
+            max_output_tokens,
+            temperature: None,
+            top_p: None,
+            tools: Vec::new(),
         })
     }
+
+    // Reasoning models reject sampling parameters.
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        if self.reasoning.is_none() {
+            self.temperature = sampling.temperature;
+            self.top_p = sampling.top_p;
+        }
+        self
+    }
+
+    pub fn with_tools(mut self, tools: &'a [ToolDefinition]) -> Self {
+        self.tools = tools
+            .iter()
+            .map(|t| FunctionTool {
+                kind: "function",
+                name: &t.name,
+                description: &t.description,
+                parameters: &t.parameters,
+            })
+            .collect();
+        self
+    }
 }
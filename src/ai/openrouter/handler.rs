@@ -1,9 +1,6 @@
-use std::sync::Arc;
-
 use anyhow::{Context, Result};
-use futures_util::StreamExt;
 use reqwest::{
-    Client, RequestBuilder,
+    RequestBuilder,
     header::{HeaderMap, HeaderValue},
 };
 use reqwest_eventsource::{Event, EventSource};
@@ -14,12 +11,20 @@ use uuid::Uuid;
 
 use super::{model::OpenRouterModel, request::OpenRouterRequest};
 use crate::{
-    ai::handler::{StreamResult, create_title_prompt, done, send_error, send_text_delta},
-    models::message::Message,
-    services::sse_manager::SseManager,
+    ai::{
+        handler::{
+            Completion, StreamResult, StreamStep, cancel_stream, done, next_event, send_error,
+            send_reasoning_delta, send_text_delta,
+        },
+        provider::StreamRequest,
+        tools::ToolCall,
+        usage::{CompletionUsage, TokenUsage},
+    },
+    configuration::ProviderSettings,
 };
 
-const BASE: &str = "https://openrouter.ai/api/v1/chat/completions";
+const COMPLETIONS_PATH: &str = "chat/completions";
+const MODELS_PATH: &str = "models";
 
 #[derive(Debug, Deserialize)]
 pub struct Choice {
@@ -36,21 +41,55 @@ pub struct AssistantMessage {
 pub struct CompletionResponse {
     pub id: String,
     pub choices: Vec<Choice>,
+    pub usage: Option<CompletionUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelList {
+    data: Vec<ModelEntry>,
+}
+
+pub async fn list_models(
+    settings: &ProviderSettings,
+    api_key: &SecretString,
+) -> Result<Vec<String>> {
+    let list: ModelList = settings
+        .client()?
+        .get(settings.endpoint(MODELS_PATH))
+        .bearer_auth(api_key.expose_secret())
+        .timeout(settings.request_timeout())
+        .send()
+        .await
+        .context("OpenRouter model list request failed")?
+        .error_for_status()
+        .context("OpenRouter model list HTTP error")?
+        .json()
+        .await
+        .context("OpenRouter model list JSON decode failed")?;
+
+    Ok(list.data.into_iter().map(|m| m.id).collect())
 }
 
-pub async fn generate_title(
+pub async fn complete(
+    settings: &ProviderSettings,
     api_key: &SecretString,
-    first_body: &str,
+    prompt: &str,
+    max_tokens: u32,
     model: OpenRouterModel,
-) -> Result<String> {
-    let prompt = create_title_prompt(first_body);
+) -> Result<Completion> {
     let model = model.to_string();
-    let req = OpenRouterRequest::prompt(&model, &prompt);
+    let req = OpenRouterRequest::prompt(&model, prompt, max_tokens);
 
-    let client = Client::new();
-    let resp: CompletionResponse = client
-        .post(BASE)
+    let resp: CompletionResponse = settings
+        .client()?
+        .post(settings.endpoint(COMPLETIONS_PATH))
         .bearer_auth(api_key.expose_secret())
+        .timeout(settings.request_timeout())
         .json(&req)
         .send()
         .await?
@@ -58,20 +97,50 @@ pub async fn generate_title(
         .json()
         .await?;
 
-    Ok(resp
+    let text = resp
         .choices
         .first()
         .context("no choices in OpenRouter response")?
         .message
         .content
         .trim()
-        .to_owned())
+        .to_owned();
+
+    Ok(Completion {
+        text,
+        model,
+        usage: resp.usage.map(TokenUsage::from),
+    })
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Delta {
     pub content: Option<String>,
+    pub reasoning: Option<String>,
     pub role: Option<String>,
+    pub tool_calls: Option<Vec<ToolCallDelta>>,
+}
+
+// The first delta of a call has its id and name, the arguments follow in pieces.
+#[derive(Debug, Deserialize)]
+pub struct ToolCallDelta {
+    pub index: usize,
+    pub id: Option<String>,
+    pub function: Option<FunctionDelta>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FunctionDelta {
+    pub name: Option<String>,
+    pub arguments: Option<String>,
+}
+
+#[derive(Default)]
+struct PendingCall {
+    index: usize,
+    id: String,
+    name: String,
+    arguments: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -83,19 +152,44 @@ pub struct ChunkChoice {
 #[derive(Debug, Deserialize)]
 pub struct StreamChunk {
     pub id: String,
+    #[serde(default)]
     pub choices: Vec<ChunkChoice>,
+    pub usage: Option<CompletionUsage>,
 }
 
 pub async fn stream(
-    api_key: SecretString,
-    sse: Arc<SseManager>,
-    user_id: String,
-    chat_id: String,
-    model: OpenRouterModel,
-    history: Vec<Message>,
+    settings: &ProviderSettings,
+    req: &StreamRequest,
 ) -> Result<Option<StreamResult>> {
-    let model = model.to_string();
-    let req_body = OpenRouterRequest::chat(&model, &history, true);
+    let StreamRequest {
+        api_key,
+        sse,
+        user_id,
+        chat_id,
+        model,
+        effort,
+        sampling,
+        capabilities,
+        system_prompt,
+        history,
+        images,
+        tools,
+        turns,
+        cancel,
+        ..
+    } = req;
+
+    let req_body = OpenRouterRequest::chat(
+        model,
+        history,
+        images,
+        system_prompt,
+        true,
+        effort.clone(),
+        capabilities.max_output_tokens,
+    )
+    .with_sampling(*sampling)
+    .with_tools(tools, turns);
 
     let mut headers = HeaderMap::new();
     headers.insert(
@@ -103,14 +197,30 @@ pub async fn stream(
         HeaderValue::from_str(&format!("Bearer {}", api_key.expose_secret()))?,
     );
 
-    let http_req: RequestBuilder = Client::new().post(BASE).headers(headers).json(&req_body);
+    let http_req: RequestBuilder = settings
+        .client()?
+        .post(settings.endpoint(COMPLETIONS_PATH))
+        .headers(headers)
+        .json(&req_body);
 
     let mut es = EventSource::new(http_req).context("OpenRouter SSE connect")?;
 
     let msg_id = Uuid::new_v4().to_string();
     let mut full_text = String::new();
+    let mut full_reasoning = String::new();
+    let mut pending: Vec<PendingCall> = Vec::new();
+    let mut usage: Option<TokenUsage> = None;
+    let mut finished = false;
+
+    loop {
+        let ev = match next_event(&mut es, cancel).await {
+            StreamStep::Event(ev) => ev,
+            StreamStep::Finished => break,
+            StreamStep::Cancelled => {
+                return Ok(cancel_stream(sse, user_id, chat_id, full_text, full_reasoning).await);
+            }
+        };
 
-    while let Some(ev) = es.next().await {
         match ev {
             Ok(Event::Open) => info!("OpenRouter SSE opened"),
             Ok(Event::Message(msg)) => {
@@ -123,29 +233,81 @@ pub async fn stream(
                 }
 
                 let chunk: StreamChunk = serde_json::from_str(data)?;
+                if let Some(u) = chunk.usage {
+                    usage = Some(u.into());
+                }
+
                 if let Some(choice) = chunk.choices.first() {
+                    if let Some(reasoning) = &choice.delta.reasoning {
+                        send_reasoning_delta(sse, user_id, chat_id, reasoning).await;
+                        full_reasoning.push_str(reasoning);
+                    }
+
                     if let Some(content) = &choice.delta.content {
-                        send_text_delta(&sse, &user_id, &chat_id, content).await;
+                        send_text_delta(sse, user_id, chat_id, content).await;
                         full_text.push_str(content);
                     }
 
-                    if matches!(&choice.finish_reason, Some(r) if r == "stop") {
-                        break;
+                    for delta in choice.delta.tool_calls.iter().flatten() {
+                        let call = match pending.iter_mut().find(|c| c.index == delta.index) {
+                            Some(call) => call,
+                            None => {
+                                pending.push(PendingCall {
+                                    index: delta.index,
+                                    ..Default::default()
+                                });
+                                pending.last_mut().expect("just pushed")
+                            }
+                        };
+                        if let Some(id) = &delta.id {
+                            call.id.clone_from(id);
+                        }
+                        if let Some(function) = &delta.function {
+                            if let Some(name) = &function.name {
+                                call.name.push_str(name);
+                            }
+                            if let Some(arguments) = &function.arguments {
+                                call.arguments.push_str(arguments);
+                            }
+                        }
+                    }
+
+                    // usage arrives in a separate chunk after the finish reason, so keep
+                    // reading until [DONE]
+                    if matches!(choice.finish_reason.as_deref(), Some("stop" | "tool_calls")) {
+                        finished = true;
                     }
                 }
             }
+            // The server may close the stream without [DONE] once the reply is complete.
+            Err(reqwest_eventsource::Error::StreamEnded) if finished => break,
             Err(e) => {
-                send_error(&sse, &user_id, &chat_id, &e.to_string()).await;
-                return Ok(None);
+                send_error(sse, user_id, chat_id, &e.to_string()).await;
+                return Err(e.into());
             }
         }
     }
 
-    done(&sse, &user_id, &chat_id, &msg_id).await;
+    let tool_calls: Vec<ToolCall> = pending
+        .into_iter()
+        .map(|c| ToolCall {
+            arguments: ToolCall::parse_arguments(&c.arguments),
+            id: c.id,
+            name: c.name,
+            signature: None,
+        })
+        .collect();
+
+    if tool_calls.is_empty() {
+        done(sse, user_id, chat_id, &msg_id).await;
+    }
 
     Ok(Some(StreamResult {
         msg_id,
         content: full_text,
-        reasoning: None,
+        reasoning: (!full_reasoning.is_empty()).then_some(full_reasoning),
+        reasoning_signature: None,
+        tool_calls,
+        usage,
     }))
 }
@@ -1,7 +1,14 @@
 use serde::{Deserialize, Serialize};
-use strum::{Display, EnumString};
+use strum::{Display, EnumString, VariantNames};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, Display, Serialize, Deserialize)]
+use crate::ai::{
+    pricing::ModelPrice,
+    registry::{ALL_MODALITIES, Capabilities, TEXT_IMAGE},
+};
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, EnumString, Display, VariantNames, Serialize, Deserialize,
+)]
 #[strum(ascii_case_insensitive)]
 pub enum OpenRouterModel {
     #[serde(rename = "google/gemini-2.5-flash")]
@@ -12,3 +19,20 @@ pub enum OpenRouterModel {
     #[strum(serialize = "x-ai/grok-4")]
     Grok4,
 }
+
+impl OpenRouterModel {
+    pub fn capabilities(self) -> Capabilities {
+        match self {
+            Self::GeminiFlash25 => {
+                Capabilities::new(1_048_576, ALL_MODALITIES, ModelPrice::new(0.3, 2.5))
+                    .with_max_output(65_535)
+                    .with_reasoning()
+                    .with_tools()
+            }
+            // xAI doesn't publish a separate output limit
+            Self::Grok4 => Capabilities::new(256_000, TEXT_IMAGE, ModelPrice::new(3.0, 15.0))
+                .with_reasoning()
+                .with_tools(),
+        }
+    }
+}
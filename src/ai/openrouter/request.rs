@@ -1,10 +1,82 @@
-use crate::models::message::Message;
+use crate::{
+    ai::{
+        attachment::ImageMap,
+        provider::Sampling,
+        reasoning::EffortLevel,
+        tools::{ToolDefinition, ToolTurn},
+    },
+    models::message::Message,
+};
 use serde::Serialize;
+use serde_json::Value;
 
 #[derive(Debug, Serialize)]
 pub struct OpenRouterMessage<'a> {
     pub role: &'a str,
-    pub content: &'a str,
+    pub content: MessageContent<'a>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCallMessage<'a>>,
+    // set on role "tool" messages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<&'a str>,
+}
+
+impl<'a> OpenRouterMessage<'a> {
+    fn new(role: &'a str, content: MessageContent<'a>) -> Self {
+        Self {
+            role,
+            content,
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ToolCallMessage<'a> {
+    pub id: &'a str,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub function: FunctionCall<'a>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FunctionCall<'a> {
+    pub name: &'a str,
+    pub arguments: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FunctionTool<'a> {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub function: FunctionDefinition<'a>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FunctionDefinition<'a> {
+    pub name: &'a str,
+    pub description: &'a str,
+    pub parameters: &'a Value,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum MessageContent<'a> {
+    Text(&'a str),
+    Parts(Vec<ContentPart<'a>>),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart<'a> {
+    Text { text: &'a str },
+    ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImageUrl {
+    pub url: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -15,33 +87,127 @@ pub struct OpenRouterRequest<'a> {
     pub stream: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<UsageOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<ReasoningOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<FunctionTool<'a>>,
+}
+
+// OpenRouter translates effort into a token budget for models that want one.
+#[derive(Debug, Serialize)]
+pub struct ReasoningOptions {
+    pub effort: EffortLevel,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageOptions {
+    pub include: bool,
 }
 
 impl<'a> OpenRouterRequest<'a> {
-    pub fn chat(model: &'a str, history: &'a [Message], stream: bool) -> Self {
+    pub fn chat(
+        model: &'a str,
+        history: &'a [Message],
+        images: &'a ImageMap,
+        system: &'a str,
+        stream: bool,
+        effort: Option<EffortLevel>,
+        max_tokens: Option<u32>,
+    ) -> Self {
+        let system = OpenRouterMessage::new("system", MessageContent::Text(system));
+
         Self {
             model,
-            messages: history
-                .iter()
-                .map(|m| OpenRouterMessage {
-                    role: &m.role,
-                    content: &m.body,
-                })
+            messages: std::iter::once(system)
+                .chain(history.iter().map(|m| {
+                    let content = match images.get(&m.id) {
+                        Some(images) => MessageContent::Parts(
+                            std::iter::once(ContentPart::Text { text: &m.body })
+                                .chain(images.iter().map(|i| ContentPart::ImageUrl {
+                                    image_url: ImageUrl { url: i.data_url() },
+                                }))
+                                .collect(),
+                        ),
+                        None => MessageContent::Text(&m.body),
+                    };
+                    OpenRouterMessage::new(&m.role, content)
+                }))
                 .collect(),
             stream: Some(stream),
-            max_tokens: None,
+            max_tokens,
+            usage: Some(UsageOptions { include: true }),
+            reasoning: effort.map(|effort| ReasoningOptions { effort }),
+            temperature: None,
+            top_p: None,
+            tools: Vec::new(),
         }
     }
 
-    pub fn prompt(model: &'a str, text: &'a str) -> Self {
+    // OpenRouter drops whatever the routed model doesn't support.
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.temperature = sampling.temperature;
+        self.top_p = sampling.top_p;
+        self
+    }
+
+    // Each round is an assistant message carrying the calls, then one "tool" message per result.
+    pub fn with_tools(mut self, tools: &'a [ToolDefinition], turns: &'a [ToolTurn]) -> Self {
+        self.tools = tools
+            .iter()
+            .map(|t| FunctionTool {
+                kind: "function",
+                function: FunctionDefinition {
+                    name: &t.name,
+                    description: &t.description,
+                    parameters: &t.parameters,
+                },
+            })
+            .collect();
+
+        for turn in turns {
+            self.messages.push(OpenRouterMessage {
+                tool_calls: turn
+                    .calls
+                    .iter()
+                    .map(|c| ToolCallMessage {
+                        id: &c.id,
+                        kind: "function",
+                        function: FunctionCall {
+                            name: &c.name,
+                            arguments: c.arguments.to_string(),
+                        },
+                    })
+                    .collect(),
+                ..OpenRouterMessage::new("assistant", MessageContent::Text(&turn.text))
+            });
+
+            self.messages
+                .extend(turn.outputs.iter().map(|o| OpenRouterMessage {
+                    tool_call_id: Some(&o.call_id),
+                    ..OpenRouterMessage::new("tool", MessageContent::Text(&o.content))
+                }));
+        }
+
+        self
+    }
+
+    pub fn prompt(model: &'a str, text: &'a str, max_tokens: u32) -> Self {
         Self {
             model,
-            messages: vec![OpenRouterMessage {
-                role: "user",
-                content: text,
-            }],
+            messages: vec![OpenRouterMessage::new("user", MessageContent::Text(text))],
             stream: None,
-            max_tokens: Some(32),
+            max_tokens: Some(max_tokens),
+            usage: Some(UsageOptions { include: true }),
+            reasoning: None,
+            temperature: None,
+            top_p: None,
+            tools: Vec::new(),
         }
     }
 }
@@ -0,0 +1,35 @@
+use serde::Serialize;
+
+use super::usage::TokenUsage;
+
+// USD per million tokens. Reasoning tokens are billed at the output rate.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl ModelPrice {
+    pub const fn new(input: f64, output: f64) -> Self {
+        Self { input, output }
+    }
+
+    // A price per million tokens is the same number as micro-dollars per token.
+    pub fn cost_micros(&self, usage: &TokenUsage) -> u64 {
+        let cost = f64::from(usage.input_tokens) * self.input
+            + f64::from(usage.output_tokens) * self.output;
+        cost.round() as u64
+    }
+}
+
+// What the budget charges for models without a known price, so they can't be used to get past
+// it. Deliberately on the expensive side of current models.
+pub const FALLBACK_PRICE: ModelPrice = ModelPrice::new(3.0, 15.0);
+
+pub fn micros_to_usd(micros: u64) -> f64 {
+    micros as f64 / 1_000_000.0
+}
+
+pub fn usd_to_micros(usd: f64) -> u64 {
+    (usd * 1_000_000.0).round() as u64
+}
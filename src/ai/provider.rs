@@ -1,56 +1,121 @@
-use std::str::FromStr;
-
-use anyhow::{Context, Error, Result, bail};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
 use diesel::MysqlConnection;
 use secrecy::SecretString;
+use strum::{Display, EnumString};
 use thiserror::Error;
+use tokio_util::sync::CancellationToken;
 
-use crate::app::AppState;
+use crate::{
+    app::AppState,
+    configuration::{ProviderSettings, ProvidersSettings},
+    models::{
+        custom_endpoint::CustomEndpoint,
+        message::{CompareModel, Message},
+    },
+};
 
-use super::reasoning::EffortLevel;
+use super::{
+    anthropic::provider::AnthropicProvider,
+    attachment::ImageMap,
+    custom::provider::CustomProvider,
+    gemini::provider::GeminiProvider,
+    handler::{Completion, StreamResult, StreamSink},
+    openai::provider::OpenAiProvider,
+    openrouter::provider::OpenRouterProvider,
+    pricing::micros_to_usd,
+    reasoning::EffortLevel,
+    registry::Capabilities,
+    tools::{ToolDefinition, ToolTurn},
+};
 
 #[derive(Debug, Error)]
 pub enum ProviderError {
     #[error("API key missing for provider {0}")]
     MissingApiKey(AiProvider),
 
+    #[error(
+        "Monthly budget of ${:.2} exhausted (${:.2} spent). Raise the limit to keep chatting.",
+        micros_to_usd(*limit_micros),
+        micros_to_usd(*spent_micros)
+    )]
+    BudgetExhausted {
+        limit_micros: u64,
+        spent_micros: u64,
+    },
+
+    #[error("Image generation needs an OpenAI or Google API key")]
+    NoImageProvider,
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
 pub type ProviderResult<T> = std::result::Result<T, ProviderError>;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, EnumString, Display)]
+#[strum(serialize_all = "lowercase")]
 pub enum AiProvider {
     OpenAi,
     Google,
     Anthropic,
     OpenRouter,
+    Custom,
 }
 
-impl FromStr for AiProvider {
-    type Err = Error;
+pub const MAX_TEMPERATURE: f32 = 2.0;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "openai" => Ok(Self::OpenAi),
-            "google" => Ok(Self::Google),
-            "anthropic" => Ok(Self::Anthropic),
-            "openrouter" => Ok(Self::OpenRouter),
-            _ => bail!("Invalid AI provider: '{}'", s),
+impl AiProvider {
+    pub fn max_temperature(&self) -> f32 {
+        match self {
+            AiProvider::Anthropic => 1.0,
+            _ => MAX_TEMPERATURE,
         }
     }
 }
 
-impl std::fmt::Display for AiProvider {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            AiProvider::OpenAi => write!(f, "openai"),
-            AiProvider::Google => write!(f, "google"),
-            AiProvider::Anthropic => write!(f, "anthropic"),
-            AiProvider::OpenRouter => write!(f, "openrouter"),
-        }
-    }
+// Set per chat, None leaves the provider's default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sampling {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+}
+
+pub struct StreamRequest {
+    pub api_key: SecretString,
+    pub sse: StreamSink,
+    pub user_id: String,
+    pub chat_id: String,
+    pub model: String,
+    pub effort: Option<EffortLevel>,
+    pub sampling: Sampling,
+    pub capabilities: Capabilities,
+    pub system_prompt: String,
+    pub history: Vec<Message>,
+    pub images: ImageMap,
+    // Empty when the model can't call tools.
+    pub tools: Vec<ToolDefinition>,
+    // Tool rounds of the reply being generated, sent after the history.
+    pub turns: Vec<ToolTurn>,
+    pub cancel: CancellationToken,
+}
+
+#[async_trait]
+pub trait ChatProvider: Send + Sync {
+    // Models known ahead of time, used when the provider can't be asked for its list.
+    fn models(&self) -> &'static [&'static str];
+
+    async fn list_models(&self, api_key: &SecretString) -> Result<Vec<String>>;
+
+    async fn stream(&self, req: &StreamRequest) -> Result<Option<StreamResult>>;
+
+    // Answers a single prompt with the provider's small, cheap model.
+    async fn complete(
+        &self,
+        api_key: &SecretString,
+        prompt: &str,
+        max_tokens: u32,
+    ) -> Result<Completion>;
 }
 
 #[derive(Debug)]
@@ -58,30 +123,119 @@ pub struct ProviderSetup {
     pub provider: AiProvider,
     pub model: String,
     pub effort: Option<EffortLevel>,
+    pub sampling: Sampling,
     pub api_key: SecretString,
+    pub endpoint: Option<CustomEndpoint>,
 }
 
+impl ProviderSetup {
+    pub fn resolve(&self, settings: &ProvidersSettings) -> Result<Box<dyn ChatProvider>> {
+        Ok(match self.provider {
+            AiProvider::OpenAi => Box::new(OpenAiProvider::new(settings.openai.clone())),
+            AiProvider::Google => Box::new(GeminiProvider::new(settings.google.clone())),
+            AiProvider::Anthropic => Box::new(AnthropicProvider::new(settings.anthropic.clone())),
+            AiProvider::OpenRouter => {
+                Box::new(OpenRouterProvider::new(settings.openrouter.clone()))
+            }
+            AiProvider::Custom => {
+                let endpoint = self
+                    .endpoint
+                    .as_ref()
+                    .context("custom provider without an endpoint")?;
+                let settings = ProviderSettings {
+                    base_url: endpoint.base_url.clone(),
+                    ..settings.custom.clone()
+                };
+                Box::new(CustomProvider::new(settings, self.model.clone()))
+            }
+        })
+    }
+}
+
+// The model picked for the chat wins over the user's active model.
 pub fn pick_provider(
     state: &AppState,
     conn: &mut MysqlConnection,
     user_id: &str,
+    chat_id: &str,
 ) -> ProviderResult<ProviderSetup> {
-    let mut model = "gpt-4.1-mini".to_owned();
-    let mut provider = AiProvider::OpenAi;
-    let mut effort: Option<EffortLevel> = None;
+    let chat = state
+        .service_container
+        .chat_service
+        .get(conn, chat_id, user_id)
+        .context("query chat")?;
+    let sampling = Sampling {
+        temperature: chat.temperature,
+        top_p: chat.top_p,
+    };
+
+    let (provider, model, reasoning) = match (chat.provider, chat.model) {
+        (Some(provider), Some(model)) => (provider, model, chat.reasoning),
+        _ => match state
+            .service_container
+            .active_model_service
+            .get_for_user(conn, user_id)
+            .context("query active_model")
+            .map_err(ProviderError::Other)?
+        {
+            Some(active) => (active.provider, active.model, active.reasoning),
+            None => ("openai".to_owned(), "gpt-4.1-mini".to_owned(), None),
+        },
+    };
 
-    if let Some(active) = state
+    let effort = reasoning.as_deref().and_then(|s| s.parse().ok());
+    setup_provider(state, conn, user_id, &provider, model, effort, sampling)
+}
+
+// A model picked for one of the replies of a compare, with the chat's sampling parameters.
+pub fn pick_compare_provider(
+    state: &AppState,
+    conn: &mut MysqlConnection,
+    user_id: &str,
+    chat_id: &str,
+    choice: &CompareModel,
+) -> ProviderResult<ProviderSetup> {
+    let chat = state
         .service_container
-        .active_model_service
-        .get_for_user(conn, user_id)
-        .context("query active_model")
-        .map_err(ProviderError::Other)?
-    {
-        provider = active.provider.parse()?;
-        model = active.model;
-        effort = active.reasoning.as_deref().and_then(|s| s.parse().ok());
+        .chat_service
+        .get(conn, chat_id, user_id)
+        .context("query chat")?;
+    let sampling = Sampling {
+        temperature: chat.temperature,
+        top_p: chat.top_p,
+    };
+
+    setup_provider(
+        state,
+        conn,
+        user_id,
+        &choice.provider,
+        choice.model.clone(),
+        choice.reasoning.clone(),
+        sampling,
+    )
+}
+
+fn setup_provider(
+    state: &AppState,
+    conn: &mut MysqlConnection,
+    user_id: &str,
+    provider: &str,
+    model: String,
+    effort: Option<EffortLevel>,
+    sampling: Sampling,
+) -> ProviderResult<ProviderSetup> {
+    let provider: AiProvider = provider
+        .parse()
+        .with_context(|| format!("Invalid AI provider: '{}'", provider))?;
+
+    // self hosted models are free, so the budget only guards the vendor providers
+    if provider == AiProvider::Custom {
+        return pick_custom_endpoint(state, conn, user_id, &model, effort, sampling);
     }
 
+    check_budget(state, conn, user_id)?;
+
     let api_key = state
         .service_container
         .api_key_service
@@ -92,6 +246,61 @@ pub fn pick_provider(
         provider,
         model,
         effort,
+        sampling,
+        api_key,
+        endpoint: None,
+    })
+}
+
+pub fn check_budget(
+    state: &AppState,
+    conn: &mut MysqlConnection,
+    user_id: &str,
+) -> ProviderResult<()> {
+    let budget = state
+        .service_container
+        .budget_service
+        .status(conn, user_id)
+        .context("query budget")?;
+    if let Some(limit_micros) = budget.monthly_limit_micros.filter(|_| budget.exhausted()) {
+        return Err(ProviderError::BudgetExhausted {
+            limit_micros,
+            spent_micros: budget.spent_micros,
+        });
+    }
+
+    Ok(())
+}
+
+// Custom models are stored as "<endpoint name>/<model id>". The model id itself may contain
+// slashes, endpoint names can't.
+fn pick_custom_endpoint(
+    state: &AppState,
+    conn: &mut MysqlConnection,
+    user_id: &str,
+    model: &str,
+    effort: Option<EffortLevel>,
+    sampling: Sampling,
+) -> ProviderResult<ProviderSetup> {
+    let (name, model) = model
+        .split_once('/')
+        .with_context(|| format!("Invalid custom model: '{model}'"))?;
+
+    let service = &state.service_container.custom_endpoint_service;
+    let endpoint = service
+        .get_by_name(conn, user_id, name)
+        .with_context(|| format!("Unknown custom endpoint: '{name}'"))?;
+
+    let api_key = service
+        .get_api_key(conn, user_id, &endpoint)
+        .unwrap_or_else(|| SecretString::from(String::new()));
+
+    Ok(ProviderSetup {
+        provider: AiProvider::Custom,
+        model: model.to_owned(),
+        effort,
+        sampling,
         api_key,
+        endpoint: Some(endpoint),
     })
 }
@@ -10,6 +10,18 @@ pub enum EffortLevel {
     High,
 }
 
+impl EffortLevel {
+    // Token budget for providers that size thinking in tokens rather than effort (Anthropic,
+    // Gemini).
+    pub fn thinking_budget(&self) -> u32 {
+        match self {
+            EffortLevel::Low => 2048,
+            EffortLevel::Medium => 8192,
+            EffortLevel::High => 24576,
+        }
+    }
+}
+
 impl ToString for EffortLevel {
     fn to_string(&self) -> String {
         match self {
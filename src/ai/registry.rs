@@ -0,0 +1,96 @@
+use serde::Serialize;
+
+use super::{
+    anthropic::model::AnthropicModel, gemini::model::GeminiModel, openai::model::OpenAiModel,
+    openrouter::model::OpenRouterModel, pricing::ModelPrice, provider::AiProvider,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Modality {
+    Text,
+    Image,
+    Audio,
+    Video,
+    File,
+}
+
+pub const TEXT: &[Modality] = &[Modality::Text];
+pub const TEXT_IMAGE: &[Modality] = &[Modality::Text, Modality::Image];
+pub const TEXT_IMAGE_FILE: &[Modality] = &[Modality::Text, Modality::Image, Modality::File];
+pub const ALL_MODALITIES: &[Modality] = &[
+    Modality::Text,
+    Modality::Image,
+    Modality::Audio,
+    Modality::Video,
+    Modality::File,
+];
+
+// What a model accepts and how far it can be pushed. Models we know nothing about, e.g. new
+// catalog entries or self hosted ones, get the default: text only, no known limits or price.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Capabilities {
+    pub context_window: Option<u32>,
+    pub max_output_tokens: Option<u32>,
+    pub modalities: &'static [Modality],
+    pub reasoning: bool,
+    pub tools: bool,
+    pub price: Option<ModelPrice>,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            context_window: None,
+            max_output_tokens: None,
+            modalities: TEXT,
+            reasoning: false,
+            tools: false,
+            price: None,
+        }
+    }
+}
+
+impl Capabilities {
+    pub const fn new(
+        context_window: u32,
+        modalities: &'static [Modality],
+        price: ModelPrice,
+    ) -> Self {
+        Self {
+            context_window: Some(context_window),
+            max_output_tokens: None,
+            modalities,
+            reasoning: false,
+            tools: false,
+            price: Some(price),
+        }
+    }
+
+    pub const fn with_max_output(mut self, max_output_tokens: u32) -> Self {
+        self.max_output_tokens = Some(max_output_tokens);
+        self
+    }
+
+    pub const fn with_reasoning(mut self) -> Self {
+        self.reasoning = true;
+        self
+    }
+
+    pub const fn with_tools(mut self) -> Self {
+        self.tools = true;
+        self
+    }
+}
+
+pub fn lookup(provider: &AiProvider, model: &str) -> Capabilities {
+    let known = match provider {
+        AiProvider::OpenAi => model.parse().ok().map(OpenAiModel::capabilities),
+        AiProvider::Anthropic => model.parse().ok().map(AnthropicModel::capabilities),
+        AiProvider::Google => model.parse().ok().map(GeminiModel::capabilities),
+        AiProvider::OpenRouter => model.parse().ok().map(OpenRouterModel::capabilities),
+        AiProvider::Custom => None,
+    };
+
+    known.unwrap_or_default()
+}
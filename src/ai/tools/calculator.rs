@@ -0,0 +1,368 @@
+use anyhow::{Context, Result, bail, ensure};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::{Tool, ToolContext, ToolDefinition, ToolResponse};
+
+// Deep enough for anything a person writes, shallow enough that nesting can't overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+pub struct Calculator;
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    expression: String,
+}
+
+#[async_trait]
+impl Tool for Calculator {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "calculator".to_owned(),
+            description: "Evaluate an arithmetic expression exactly instead of estimating it. \
+                Supports + - * / % ^, parentheses, the constants pi and e, and the functions \
+                sqrt, abs, exp, ln, log10, log2, floor, ceil, round, sin, cos, tan, asin, acos, \
+                atan, min and max."
+                .to_owned(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "expression": {
+                        "type": "string",
+                        "description": "The expression, e.g. \"(3.5 + 2) * sqrt(16) / 3\""
+                    }
+                },
+                "required": ["expression"]
+            }),
+        }
+    }
+
+    async fn call(&self, _ctx: &ToolContext, arguments: Value) -> Result<ToolResponse> {
+        let args: Args = serde_json::from_value(arguments).context("Invalid arguments")?;
+        let result = evaluate(&args.expression)
+            .with_context(|| format!("Could not evaluate '{}'", args.expression))?;
+
+        ensure!(result.is_finite(), "The result is not a finite number");
+
+        Ok(result.to_string().into())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(char),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "number {n}"),
+            Token::Ident(name) => write!(f, "'{name}'"),
+            Token::Op(c) => write!(f, "'{c}'"),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '0'..='9' | '.' => {
+                let mut number = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+                    number.push(c);
+                }
+                // Only an exponent when digits follow, otherwise the e is left to be reported on
+                // its own.
+                let mut ahead = chars.clone();
+                if let Some(e) = ahead.next_if(|c| *c == 'e' || *c == 'E') {
+                    let sign = ahead.next_if(|c| *c == '+' || *c == '-');
+                    if ahead.peek().is_some_and(char::is_ascii_digit) {
+                        number.push(e);
+                        number.extend(sign);
+                        while let Some(c) = ahead.next_if(char::is_ascii_digit) {
+                            number.push(c);
+                        }
+                        chars = ahead;
+                    }
+                }
+                let value = number
+                    .parse()
+                    .with_context(|| format!("Invalid number '{number}'"))?;
+                tokens.push(Token::Number(value));
+            }
+            c if c.is_alphabetic() => {
+                let mut ident = String::new();
+                while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric()) {
+                    ident.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Ident(ident.to_ascii_lowercase()));
+            }
+            '+' | '-' | '*' | '/' | '%' | '^' | '(' | ')' | ',' => {
+                tokens.push(Token::Op(c));
+                chars.next();
+            }
+            c => bail!("Unexpected character '{c}'"),
+        }
+    }
+
+    Ok(tokens)
+}
+
+// Recursive descent over
+//   expr  = term (("+" | "-") term)*
+//   term  = unary (("*" | "/" | "%") unary)*
+//   unary = "-" unary | power
+//   power = atom ("^" unary)?
+//   atom  = number | constant | function "(" expr ("," expr)* ")" | "(" expr ")"
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+fn evaluate(input: &str) -> Result<f64> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+        depth: 0,
+    };
+    let value = parser.expr()?;
+    if let Some(token) = parser.peek() {
+        bail!("Unexpected {token}");
+    }
+    Ok(value)
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: char) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, op: char) -> Result<()> {
+        ensure!(self.eat(op), "Expected '{op}'");
+        Ok(())
+    }
+
+    fn expr(&mut self) -> Result<f64> {
+        self.depth += 1;
+        ensure!(self.depth <= MAX_DEPTH, "Expression is nested too deeply");
+
+        let mut value = self.term()?;
+        loop {
+            if self.eat('+') {
+                value += self.term()?;
+            } else if self.eat('-') {
+                value -= self.term()?;
+            } else {
+                break;
+            }
+        }
+
+        self.depth -= 1;
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<f64> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat('*') {
+                value *= self.unary()?;
+            } else if self.eat('/') {
+                let divisor = self.unary()?;
+                ensure!(divisor != 0.0, "Division by zero");
+                value /= divisor;
+            } else if self.eat('%') {
+                let divisor = self.unary()?;
+                ensure!(divisor != 0.0, "Division by zero");
+                value %= divisor;
+            } else {
+                break;
+            }
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<f64> {
+        self.depth += 1;
+        ensure!(self.depth <= MAX_DEPTH, "Expression is nested too deeply");
+
+        let value = if self.eat('-') {
+            -self.unary()?
+        } else if self.eat('+') {
+            self.unary()?
+        } else {
+            self.power()?
+        };
+
+        self.depth -= 1;
+        Ok(value)
+    }
+
+    // Right associative and binding tighter than a leading minus: -2^2 is -4, 2^3^2 is 512.
+    fn power(&mut self) -> Result<f64> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            return Ok(base.powf(self.unary()?));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<f64> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(n),
+            Some(Token::Op('(')) => {
+                let value = self.expr()?;
+                self.expect(')')?;
+                Ok(value)
+            }
+            Some(Token::Ident(name)) if self.peek() == Some(&Token::Op('(')) => {
+                self.pos += 1;
+                let mut args = vec![self.expr()?];
+                while self.eat(',') {
+                    args.push(self.expr()?);
+                }
+                self.expect(')')?;
+                call(&name, &args)
+            }
+            Some(Token::Ident(name)) => match name.as_str() {
+                "pi" => Ok(std::f64::consts::PI),
+                "e" => Ok(std::f64::consts::E),
+                _ => bail!("Unknown constant '{name}'"),
+            },
+            Some(token) => bail!("Unexpected {token}"),
+            None => bail!("Unexpected end of expression"),
+        }
+    }
+}
+
+fn call(name: &str, args: &[f64]) -> Result<f64> {
+    let unary = |f: fn(f64) -> f64| -> Result<f64> {
+        match args {
+            [x] => Ok(f(*x)),
+            _ => bail!("{name} takes one argument"),
+        }
+    };
+
+    match name {
+        "sqrt" => unary(f64::sqrt),
+        "abs" => unary(f64::abs),
+        "exp" => unary(f64::exp),
+        "ln" => unary(f64::ln),
+        "log10" => unary(f64::log10),
+        "log2" => unary(f64::log2),
+        "floor" => unary(f64::floor),
+        "ceil" => unary(f64::ceil),
+        "round" => unary(f64::round),
+        "sin" => unary(f64::sin),
+        "cos" => unary(f64::cos),
+        "tan" => unary(f64::tan),
+        "asin" => unary(f64::asin),
+        "acos" => unary(f64::acos),
+        "atan" => unary(f64::atan),
+        "min" => args
+            .iter()
+            .copied()
+            .reduce(f64::min)
+            .context("min needs arguments"),
+        "max" => args
+            .iter()
+            .copied()
+            .reduce(f64::max)
+            .context("max needs arguments"),
+        _ => bail!("Unknown function '{name}'"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(input: &str) -> String {
+        evaluate(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn multiplication_binds_tighter_than_addition() {
+        assert_eq!(evaluate("2 + 3 * 4").unwrap(), 14.0);
+        assert_eq!(evaluate("10 - 4 - 3").unwrap(), 3.0);
+        assert_eq!(evaluate("7 % 4 * 2").unwrap(), 6.0);
+    }
+
+    #[test]
+    fn powers_are_right_associative_and_bind_tighter_than_minus() {
+        assert_eq!(evaluate("2^3^2").unwrap(), 512.0);
+        assert_eq!(evaluate("-2^2").unwrap(), -4.0);
+        assert_eq!(evaluate("2^-1").unwrap(), 0.5);
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(evaluate("-3").unwrap(), -3.0);
+        assert_eq!(evaluate("--3").unwrap(), 3.0);
+        assert_eq!(evaluate("4 * -2").unwrap(), -8.0);
+    }
+
+    #[test]
+    fn parentheses_and_functions() {
+        assert_eq!(evaluate("(2 + 3) * 4").unwrap(), 20.0);
+        assert_eq!(evaluate("(3.5 + 2) * sqrt(16) / 2").unwrap(), 11.0);
+        assert_eq!(evaluate("max(1, 5, 3) - min(2, -1)").unwrap(), 6.0);
+        assert_eq!(evaluate("PI").unwrap(), std::f64::consts::PI);
+    }
+
+    #[test]
+    fn exponents() {
+        assert_eq!(evaluate("1.5e3").unwrap(), 1500.0);
+        assert_eq!(evaluate("2E-2").unwrap(), 0.02);
+        assert_eq!(evaluate("1e+2 + 1").unwrap(), 101.0);
+    }
+
+    #[test]
+    fn an_e_without_digits_is_not_an_exponent() {
+        assert_eq!(error("3e"), "Unexpected 'e'");
+        assert_eq!(error("2e+"), "Unexpected 'e'");
+        assert_eq!(evaluate("2*e").unwrap(), 2.0 * std::f64::consts::E);
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert_eq!(error("1 / 0"), "Division by zero");
+        assert_eq!(error("5 % (2 - 2)"), "Division by zero");
+    }
+
+    #[test]
+    fn malformed_input() {
+        assert_eq!(error("2 +"), "Unexpected end of expression");
+        assert_eq!(error("(1 + 2"), "Expected ')'");
+        assert_eq!(error("1 + 2)"), "Unexpected ')'");
+        assert_eq!(error("3 $ 4"), "Unexpected character '$'");
+        assert_eq!(error("1..2"), "Invalid number '1..2'");
+        assert_eq!(error("foo(1)"), "Unknown function 'foo'");
+        assert_eq!(error("sqrt(1, 2)"), "sqrt takes one argument");
+        assert_eq!(error(&"(".repeat(100)), "Expression is nested too deeply");
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::{Tool, ToolContext, ToolDefinition, ToolResponse};
+
+const MAX_RESULTS: i64 = 10;
+// Enough of a message to tell whether it is the one being looked for.
+const SNIPPET_CHARS: usize = 300;
+
+pub struct ChatSearch;
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    query: String,
+}
+
+#[async_trait]
+impl Tool for ChatSearch {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "search_chats".to_owned(),
+            description: "Search the user's previous chats for messages containing some text. \
+                Use it when the user refers to something discussed in another conversation."
+                .to_owned(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Text to look for, matched case-insensitively"
+                    }
+                },
+                "required": ["query"]
+            }),
+        }
+    }
+
+    async fn call(&self, ctx: &ToolContext, arguments: Value) -> Result<ToolResponse> {
+        let args: Args = serde_json::from_value(arguments).context("Invalid arguments")?;
+        let query = args.query.trim();
+        anyhow::ensure!(!query.is_empty(), "The query is empty");
+
+        let found = {
+            let mut conn = ctx.state.db_pool.get()?;
+            ctx.state.service_container.message_service.search(
+                &mut conn,
+                &ctx.user_id,
+                query,
+                MAX_RESULTS,
+            )?
+        };
+
+        if found.is_empty() {
+            return Ok(format!("No messages found containing '{query}'.").into());
+        }
+
+        let results: Vec<Value> = found
+            .into_iter()
+            .map(|(m, title)| {
+                json!({
+                    "chat_id": m.chat_id,
+                    "chat_title": title,
+                    "current_chat": m.chat_id == ctx.chat_id,
+                    "role": m.role,
+                    "created_at": m.created_at.and_utc(),
+                    "text": m.body.chars().take(SNIPPET_CHARS).collect::<String>(),
+                })
+            })
+            .collect();
+
+        Ok(serde_json::to_string(&results)?.into())
+    }
+}
@@ -0,0 +1,53 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::{Tool, ToolContext, ToolDefinition, ToolResponse};
+
+pub struct CurrentTime;
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    timezone: Option<String>,
+}
+
+#[async_trait]
+impl Tool for CurrentTime {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "current_time".to_owned(),
+            description: "Get the current date and time, in UTC or in a given time zone."
+                .to_owned(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "timezone": {
+                        "type": "string",
+                        "description": "IANA time zone name, e.g. \"Europe/London\". Defaults to UTC."
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, _ctx: &ToolContext, arguments: Value) -> Result<ToolResponse> {
+        let args: Args = serde_json::from_value(arguments).context("Invalid arguments")?;
+        let now = Utc::now();
+
+        Ok(match args.timezone.as_deref().filter(|tz| !tz.is_empty()) {
+            Some(name) => {
+                let tz: Tz = name
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Unknown time zone '{name}'"))?;
+                now.with_timezone(&tz)
+                    .format("%A %Y-%m-%d %H:%M:%S %Z (UTC%:z)")
+            }
+            None => now.format("%A %Y-%m-%d %H:%M:%S UTC"),
+        }
+        .to_string()
+        .into())
+    }
+}
@@ -0,0 +1,199 @@
+pub mod calculator;
+pub mod chat_search;
+pub mod current_time;
+pub mod web_search;
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::{
+    app::AppState,
+    infra::search::SearchBackend,
+    models::message::{Citation, MessagePart},
+};
+
+use self::{
+    calculator::Calculator, chat_search::ChatSearch, current_time::CurrentTime,
+    web_search::WebSearch,
+};
+
+// A slow tool fails its call instead of holding up the reply.
+const TOOL_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct ToolDefinition {
+    // [a-zA-Z0-9_-]{1,64}, the intersection of what the providers accept
+    pub name: String,
+    pub description: String,
+    // JSON schema of the arguments object
+    pub parameters: Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: Value,
+    // Gemini signs the reasoning behind a call and wants the signature back with it.
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ToolOutput {
+    pub call_id: String,
+    pub name: String,
+    pub content: String,
+    pub is_error: bool,
+    pub citations: Vec<Citation>,
+}
+
+// What a tool hands back: text for the model, and the sources it came from if any.
+#[derive(Debug, Clone, Default)]
+pub struct ToolResponse {
+    pub content: String,
+    pub citations: Vec<Citation>,
+}
+
+impl From<String> for ToolResponse {
+    fn from(content: String) -> Self {
+        Self {
+            content,
+            citations: Vec::new(),
+        }
+    }
+}
+
+// One round of the agent loop: what the model said and called, and what the tools answered. The
+// provider sends these after the history on the next request.
+#[derive(Debug, Clone)]
+pub struct ToolTurn {
+    pub text: String,
+    pub reasoning: Option<String>,
+    // Anthropic rejects a tool call sent back without the signed thinking that led to it.
+    pub reasoning_signature: Option<String>,
+    pub calls: Vec<ToolCall>,
+    pub outputs: Vec<ToolOutput>,
+}
+
+impl ToolCall {
+    // Arguments that arrive as JSON text. Ones that don't parse are passed on as a string, so the
+    // tool's error tells the model what went wrong.
+    pub fn parse_arguments(raw: &str) -> Value {
+        if raw.trim().is_empty() {
+            return Value::Object(Default::default());
+        }
+        serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_owned()))
+    }
+}
+
+impl ToolTurn {
+    pub fn parts(&self) -> Vec<MessagePart> {
+        let calls = self.calls.iter().map(|c| MessagePart::ToolCall {
+            id: c.id.clone(),
+            name: c.name.clone(),
+            arguments: c.arguments.clone(),
+        });
+        let outputs = self.outputs.iter().map(|o| MessagePart::ToolResult {
+            call_id: o.call_id.clone(),
+            name: o.name.clone(),
+            content: o.content.clone(),
+            is_error: o.is_error,
+        });
+
+        calls.chain(outputs).collect()
+    }
+
+    // Sources from every round, each url once.
+    pub fn citations(turns: &[ToolTurn]) -> Vec<Citation> {
+        let mut citations: Vec<Citation> = Vec::new();
+        for citation in turns
+            .iter()
+            .flat_map(|t| &t.outputs)
+            .flat_map(|o| &o.citations)
+        {
+            if !citations.iter().any(|c| c.url == citation.url) {
+                citations.push(citation.clone());
+            }
+        }
+        citations
+    }
+}
+
+pub struct ToolContext {
+    pub state: AppState,
+    pub user_id: String,
+    pub chat_id: String,
+}
+
+#[async_trait]
+pub trait Tool: Send + Sync {
+    fn definition(&self) -> ToolDefinition;
+
+    // The returned text is handed to the model as is. Errors are reported to the model too, so
+    // it can retry with different arguments.
+    async fn call(&self, ctx: &ToolContext, arguments: Value) -> Result<ToolResponse>;
+}
+
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    pub fn builtin(search: Option<Arc<dyn SearchBackend>>) -> Self {
+        let mut registry = Self::default();
+        registry.register(Arc::new(Calculator));
+        registry.register(Arc::new(CurrentTime));
+        registry.register(Arc::new(ChatSearch));
+        if let Some(backend) = search {
+            registry.register(Arc::new(WebSearch::new(backend)));
+        }
+        registry
+    }
+
+    pub fn register(&mut self, tool: Arc<dyn Tool>) {
+        self.tools.push(tool);
+    }
+
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools.iter().map(|t| t.definition()).collect()
+    }
+
+    pub async fn execute(&self, ctx: &ToolContext, call: &ToolCall) -> ToolOutput {
+        let tool = self.tools.iter().find(|t| t.definition().name == call.name);
+
+        let result = match tool {
+            Some(tool) => {
+                match tokio::time::timeout(TOOL_TIMEOUT, tool.call(ctx, call.arguments.clone()))
+                    .await
+                {
+                    Ok(result) => result,
+                    Err(_) => Err(anyhow::anyhow!(
+                        "Tool timed out after {}s",
+                        TOOL_TIMEOUT.as_secs()
+                    )),
+                }
+            }
+            None => Err(anyhow::anyhow!("Unknown tool: {}", call.name)),
+        };
+
+        let (response, is_error) = match result {
+            Ok(response) => (response, false),
+            Err(e) => {
+                tracing::info!(tool = call.name, error = ?e, "Tool call failed");
+                (format!("Error: {e:#}").into(), true)
+            }
+        };
+
+        ToolOutput {
+            call_id: call.id.clone(),
+            name: call.name.clone(),
+            content: response.content,
+            is_error,
+            citations: response.citations,
+        }
+    }
+}
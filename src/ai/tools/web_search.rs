@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result, ensure};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{infra::search::SearchBackend, models::message::Citation};
+
+use super::{Tool, ToolContext, ToolDefinition, ToolResponse};
+
+const MAX_SNIPPET_CHARS: usize = 500;
+
+pub struct WebSearch {
+    backend: Arc<dyn SearchBackend>,
+}
+
+impl WebSearch {
+    pub fn new(backend: Arc<dyn SearchBackend>) -> Self {
+        Self { backend }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    query: String,
+}
+
+#[async_trait]
+impl Tool for WebSearch {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "web_search".to_owned(),
+            description: "Search the web for current information or facts you are unsure of. \
+                Base your answer on the results and cite the ones you use by linking their url."
+                .to_owned(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "What to search for"
+                    }
+                },
+                "required": ["query"]
+            }),
+        }
+    }
+
+    async fn call(&self, _ctx: &ToolContext, arguments: Value) -> Result<ToolResponse> {
+        let args: Args = serde_json::from_value(arguments).context("Invalid arguments")?;
+        let query = args.query.trim();
+        ensure!(!query.is_empty(), "query must not be empty");
+
+        let results = self.backend.search(query).await?;
+        if results.is_empty() {
+            return Ok(format!("No results found for '{query}'.").into());
+        }
+
+        let citations: Vec<Citation> = results
+            .into_iter()
+            .map(|r| Citation {
+                snippet: r.snippet.chars().take(MAX_SNIPPET_CHARS).collect(),
+                url: r.url,
+                title: r.title,
+            })
+            .collect();
+
+        let content = citations
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("[{}] {}\n{}\n{}", i + 1, c.title, c.url, c.snippet))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        Ok(ToolResponse { content, citations })
+    }
+}
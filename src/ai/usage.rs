@@ -0,0 +1,47 @@
+use serde::Deserialize;
+
+// output_tokens includes reasoning_tokens, matching how every provider bills them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub reasoning_tokens: u32,
+}
+
+// Totals across the requests of one reply.
+impl std::ops::AddAssign for TokenUsage {
+    fn add_assign(&mut self, rhs: Self) {
+        self.input_tokens += rhs.input_tokens;
+        self.output_tokens += rhs.output_tokens;
+        self.reasoning_tokens += rhs.reasoning_tokens;
+    }
+}
+
+// Usage block of the chat completions protocol, shared by OpenRouter and custom endpoints.
+#[derive(Debug, Deserialize)]
+pub struct CompletionUsage {
+    #[serde(default)]
+    pub prompt_tokens: u32,
+    #[serde(default)]
+    pub completion_tokens: u32,
+    pub completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompletionTokensDetails {
+    #[serde(default)]
+    pub reasoning_tokens: u32,
+}
+
+impl From<CompletionUsage> for TokenUsage {
+    fn from(value: CompletionUsage) -> Self {
+        Self {
+            input_tokens: value.prompt_tokens,
+            output_tokens: value.completion_tokens,
+            reasoning_tokens: value
+                .completion_tokens_details
+                .map(|d| d.reasoning_tokens)
+                .unwrap_or_default(),
+        }
+    }
+}
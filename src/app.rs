@@ -2,11 +2,13 @@ use axum::Router;
 use std::net::SocketAddr;
 use std::sync::Arc;
 
+use crate::ai::mcp::McpManager;
 use crate::configuration::Settings;
 use crate::infra;
-use crate::jobs::{Job, run_worker};
+use crate::jobs::run_worker;
 use crate::routes::app_routes;
 use crate::services::container::ServiceContainer;
+use crate::services::generation_registry::GenerationRegistry;
 use crate::services::sse_manager::SseManager;
 use tower_sessions_redis_store::fred::prelude::Pool;
 
@@ -33,14 +35,12 @@ impl Application {
     }
 
     pub async fn build(config: Settings) -> Result<Self, anyhow::Error> {
-        let pool = infra::db::establish_connection(config.application.database_url.clone());
-        let cache = infra::redis::establish_connection(config.application.redis_url.clone()).await;
-
         let addr = format!("{}:{}", config.application.host, config.application.port);
         let addr: SocketAddr = addr.parse()?;
         let port = addr.port();
 
-        let (listener, app) = create(addr, pool, cache, config).await?;
+        let state = build_state(config).await;
+        let (listener, app) = create(addr, state).await?;
 
         Ok(Self {
             port,
@@ -50,6 +50,27 @@ impl Application {
     }
 }
 
+// Runs queued jobs without serving any requests, so workers scale separately from the web process.
+pub struct Worker {
+    state: AppState,
+}
+
+impl Worker {
+    pub async fn build(config: Settings) -> Self {
+        Self {
+            state: build_state(config).await,
+        }
+    }
+
+    pub async fn run_until_stopped(self) {
+        tokio::spawn(self.state.generation_registry.clone().run_cancel_listener());
+        tokio::spawn(self.state.mcp_manager.clone().run_reaper());
+        tokio::spawn(self.state.mcp_manager.clone().run_close_listener());
+
+        run_worker(self.state).await;
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub db_pool: infra::db::DbPool,
@@ -57,33 +78,40 @@ pub struct AppState {
     pub config: Arc<Settings>,
     pub service_container: Arc<ServiceContainer>,
     pub sse_manager: Arc<SseManager>,
-    pub job_tx: tokio::sync::mpsc::UnboundedSender<Job>,
+    pub generation_registry: Arc<GenerationRegistry>,
+    pub mcp_manager: Arc<McpManager>,
+}
+
+async fn build_state(config: Settings) -> AppState {
+    let db_pool = infra::db::establish_connection(config.application.database_url.clone());
+    let cache: Pool =
+        infra::redis::establish_connection(config.application.redis_url.clone()).await;
+
+    let config = Arc::new(config);
+    AppState {
+        db_pool,
+        cache: cache.clone(),
+        service_container: Arc::new(ServiceContainer::new(config.clone())),
+        sse_manager: Arc::new(SseManager::new(cache.clone())),
+        generation_registry: Arc::new(GenerationRegistry::new(cache.clone())),
+        mcp_manager: Arc::new(McpManager::new(config.mcp.clone(), cache)),
+        config,
+    }
 }
 
 async fn create(
     addr: SocketAddr,
-    db_pool: infra::db::DbPool,
-    cache: Pool,
-    config: Settings,
+    app_state: AppState,
 ) -> Result<(tokio::net::TcpListener, Router), anyhow::Error> {
-    let config = Arc::new(config);
-    let service_container = Arc::new(ServiceContainer::new(config.clone()));
-    let sse_manager = Arc::new(SseManager::new());
-    let (job_tx, job_rx) = tokio::sync::mpsc::unbounded_channel::<Job>();
+    tokio::spawn(app_state.sse_manager.clone().run_relay());
 
-    let app_state = AppState {
-        db_pool,
-        cache,
-        config,
-        service_container,
-        sse_manager,
-        job_tx: job_tx.clone(),
-    };
-
-    let worker_state = app_state.clone();
-    tokio::spawn(async move {
-        run_worker(worker_state, job_rx).await;
-    });
+    if app_state.config.jobs.embedded_worker {
+        tokio::spawn(app_state.generation_registry.clone().run_cancel_listener());
+        tokio::spawn(run_worker(app_state.clone()));
+    }
+
+    tokio::spawn(app_state.mcp_manager.clone().run_reaper());
+    tokio::spawn(app_state.mcp_manager.clone().run_close_listener());
 
     let app = app_routes(app_state);
 
@@ -0,0 +1,18 @@
+use t3_clone::{
+    app::Worker,
+    configuration::get_configuration,
+    telemetry::{get_subscriber, init_subscriber},
+};
+
+#[tokio::main]
+async fn main() {
+    let subscriber = get_subscriber(
+        "t3_clone_worker".into(),
+        "info,t3_clone=info".into(),
+        std::io::stdout,
+    );
+    init_subscriber(subscriber);
+
+    let config = get_configuration().expect("Failed to read config");
+    Worker::build(config).await.run_until_stopped().await;
+}
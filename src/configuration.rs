@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use secrecy::SecretString;
 use serde::Deserialize;
 use serde_aux::field_attributes::deserialize_number_from_string;
@@ -5,6 +7,11 @@ use serde_aux::field_attributes::deserialize_number_from_string;
 #[derive(Debug, Deserialize, Clone)]
 pub struct Settings {
     pub application: ApplicationSettings,
+    pub providers: ProvidersSettings,
+    pub attachments: AttachmentSettings,
+    pub mcp: McpSettings,
+    pub search: SearchSettings,
+    pub jobs: JobSettings,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -17,6 +24,153 @@ pub struct ApplicationSettings {
     pub database_url: SecretString,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct ProvidersSettings {
+    pub openai: ProviderSettings,
+    pub anthropic: ProviderSettings,
+    pub google: ProviderSettings,
+    pub openrouter: ProviderSettings,
+    pub custom: ProviderSettings,
+    // How long a user's model list is cached before the providers are asked again.
+    pub catalog_ttl_secs: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AttachmentSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_upload_bytes: usize,
+    pub storage: StorageSettings,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum StorageSettings {
+    Local { path: String },
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum SearchSettings {
+    Disabled,
+    // Any endpoint that answers /search?q=...&format=json the way SearXNG does.
+    Searxng {
+        base_url: String,
+        #[serde(deserialize_with = "deserialize_number_from_string")]
+        request_timeout_secs: u64,
+        #[serde(deserialize_with = "deserialize_number_from_string")]
+        max_results: usize,
+    },
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct McpSettings {
+    // Covers starting the server and the initialize handshake.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub connect_timeout_secs: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub request_timeout_secs: u64,
+    // Connections unused for this long are closed, stdio servers are killed.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub idle_timeout_secs: u64,
+    // Full command lines users may start as stdio servers, the binary with every argument, e.g.
+    // "npx -y @modelcontextprotocol/server-memory@2025.4.25". Stdio servers run on this machine,
+    // so none are allowed unless the operator lists them, and a bare launcher like npx is never
+    // enough since it runs whatever package it is given.
+    #[serde(default)]
+    pub stdio_commands: Vec<String>,
+}
+
+impl McpSettings {
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout_secs)
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout_secs)
+    }
+
+    pub fn allows_stdio(&self, command: &str, args: &[String]) -> bool {
+        let line = || std::iter::once(command).chain(args.iter().map(String::as_str));
+        self.stdio_commands
+            .iter()
+            .any(|allowed| allowed.split_whitespace().eq(line()))
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct JobSettings {
+    // Runs a worker inside the web process as well, so a single process is enough locally.
+    pub embedded_worker: bool,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub poll_interval_ms: u64,
+    // How long a claimed job is held before another worker may take it over. Renewed while it
+    // runs, so this only bounds how long a crashed worker's jobs wait.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lease_secs: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attempts: i32,
+    // Jobs one worker process runs at once.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_concurrent: usize,
+    // Jobs running at once across all workers.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_running: usize,
+    // Jobs of one user running at once, across all workers.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_per_user: usize,
+}
+
+impl JobSettings {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+
+    pub fn lease(&self) -> Duration {
+        Duration::from_secs(self.lease_secs)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ProviderSettings {
+    // Left empty for custom endpoints, which use the url registered by the user.
+    #[serde(default)]
+    pub base_url: String,
+    pub api_version: Option<String>,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub connect_timeout_secs: u64,
+    // Maximum gap between two chunks of a stream before it is treated as dead.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub read_timeout_secs: u64,
+    // Total time allowed for non-streaming calls such as title generation.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub request_timeout_secs: u64,
+}
+
+impl ProviderSettings {
+    pub fn endpoint(&self, path: &str) -> String {
+        format!(
+            "{}/{}",
+            self.base_url.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+    }
+
+    pub fn client(&self) -> reqwest::Result<reqwest::Client> {
+        reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(self.connect_timeout_secs))
+            .read_timeout(Duration::from_secs(self.read_timeout_secs))
+            .build()
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+}
+
 pub enum Environment {
     Local,
     Production,
@@ -59,6 +213,9 @@ pub fn get_configuration() -> Result<Settings, config::ConfigError> {
     let environment_filename = format!("{}.yaml", environment.as_str());
 
     let settings = config::Config::builder()
+        .add_source(config::File::from(
+            configuration_directory.join("base.yaml"),
+        ))
         .add_source(config::File::from(
             configuration_directory.join(environment_filename),
         ))
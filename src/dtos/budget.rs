@@ -0,0 +1,10 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct Budget {
+    pub monthly_limit_usd: Option<f64>,
+    pub spent_usd: f64,
+    pub period_start: NaiveDate,
+    pub exhausted: bool,
+}
@@ -11,4 +11,10 @@ pub struct Chat {
     pub pinned_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub system_prompt: Option<String>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub reasoning: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
 }
@@ -0,0 +1,25 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::models::mcp_server::McpTransport;
+
+#[derive(Debug, Serialize)]
+pub struct McpServer {
+    pub id: u64,
+    pub name: String,
+    pub transport: Option<McpTransport>,
+    pub url: Option<String>,
+    pub command: Option<String>,
+    pub args: Vec<String>,
+    // null when every tool is enabled
+    pub enabled_tools: Option<Vec<String>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct McpTool {
+    pub name: String,
+    pub description: String,
+    pub enabled: bool,
+}
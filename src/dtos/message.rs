@@ -1,13 +1,21 @@
 use chrono::{DateTime, Utc};
 use serde::Serialize;
 
+use crate::models::message::{Citation, MessagePart};
+
 #[derive(Serialize)]
 pub struct Message {
     pub id: String,
     pub chat_id: String,
+    pub parent_id: Option<String>,
     pub role: String,
     pub body: String,
     pub reasoning: Option<String>,
+    pub parts: Vec<MessagePart>,
+    pub citations: Vec<Citation>,
+    pub model: Option<String>,
+    pub compare_group: Option<String>,
+    pub selected: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -1,6 +1,13 @@
 pub mod active_model;
 pub mod api_key;
+pub mod attachment;
+pub mod budget;
 pub mod chat;
+pub mod custom_endpoint;
+pub mod mcp_server;
 pub mod message;
+pub mod model_catalog;
 pub mod shared_chat;
+pub mod system_prompt;
+pub mod usage;
 pub mod user;
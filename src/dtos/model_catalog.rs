@@ -0,0 +1,10 @@
+use serde::Serialize;
+
+use crate::ai::registry::Capabilities;
+
+#[derive(Debug, Serialize)]
+pub struct CatalogModel {
+    pub provider: String,
+    pub id: String,
+    pub capabilities: Capabilities,
+}
@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
 use serde::Serialize;
 
+use crate::models::message::Citation;
+
 #[derive(Serialize)]
 pub struct SharedChat {
     pub id: String,
@@ -14,9 +16,20 @@ pub struct SharedMessage {
     pub role: String,
     pub body: String,
     pub reasoning: Option<String>,
+    pub citations: Vec<Citation>,
+    pub attachments: Vec<SharedAttachment>,
     pub created_at: DateTime<Utc>,
 }
 
+// Served from /api/shared/{shared_chat_id}/attachments/{id}.
+#[derive(Serialize)]
+pub struct SharedAttachment {
+    pub id: String,
+    pub filename: String,
+    pub content_type: String,
+    pub size_bytes: u64,
+}
+
 #[derive(Serialize)]
 pub struct SharedChatWithMessages {
     pub id: String,
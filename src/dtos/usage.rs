@@ -0,0 +1,37 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub reasoning_tokens: u64,
+    pub cost_usd: f64,
+    pub unpriced_requests: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModelUsage {
+    pub provider: String,
+    pub model: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DailyUsage {
+    pub day: NaiveDate,
+    pub provider: String,
+    pub model: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageSummary {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub models: Vec<ModelUsage>,
+    pub days: Vec<DailyUsage>,
+}
@@ -0,0 +1,203 @@
+use anyhow::{Context, Result};
+use axum::{
+    Extension, Json,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderName, StatusCode, header},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    app::AppState,
+    dtos,
+    models::attachment::{Attachment, AttachmentKind, CreateArgs},
+};
+
+const MAX_FILENAME_LEN: usize = 255;
+
+#[derive(Debug, Deserialize)]
+pub struct UploadQuery {
+    pub filename: String,
+}
+
+// The file is sent as the raw request body, its type in the Content-Type header. The body size
+// limit is applied by the route.
+#[tracing::instrument(
+    skip(state, user, headers, body),
+    fields(user_id = %user.id, size = body.len())
+)]
+pub async fn upload_attachment(
+    State(state): State<AppState>,
+    Extension(user): Extension<dtos::user::User>,
+    Query(query): Query<UploadQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<dtos::attachment::Attachment>), (StatusCode, String)> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    let filename = query.filename.trim();
+    if filename.is_empty() || filename.chars().count() > MAX_FILENAME_LEN {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("filename must be 1-{MAX_FILENAME_LEN} characters"),
+        ));
+    }
+
+    let Some(kind) = AttachmentKind::detect(&content_type, filename) else {
+        return Err((
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            format!("unsupported attachment type '{content_type}'"),
+        ));
+    };
+
+    if body.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "empty upload".into()));
+    }
+
+    let service = &state.service_container.attachment_service;
+
+    // Documents are parsed once here; later turns reuse the stored text.
+    let text = match kind {
+        AttachmentKind::Image => None,
+        AttachmentKind::Pdf | AttachmentKind::Text => Some(
+            service
+                .extract_text(kind, body.to_vec())
+                .await
+                .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?,
+        ),
+    };
+
+    let id = Uuid::new_v4().to_string();
+    let storage_key = Attachment::storage_key(&user.id, &id);
+    service
+        .store_blob(&storage_key, &body)
+        .await
+        .context("store blob")
+        .map_err(internal_error)?;
+
+    if let Some(text) = &text {
+        service
+            .store_text(&storage_key, text)
+            .await
+            .context("store text")
+            .map_err(internal_error)?;
+    }
+
+    let created = {
+        let mut conn = state
+            .db_pool
+            .get()
+            .context("DB pool")
+            .map_err(internal_error)?;
+
+        let args = CreateArgs {
+            id,
+            filename: filename.to_owned(),
+            content_type: kind.content_type(&content_type),
+            size_bytes: body.len() as u64,
+            storage_key,
+        };
+        service
+            .create(&mut conn, args, &user.id)
+            .context("service")
+            .map_err(internal_error)?
+    };
+
+    state.sse_manager.replicache_poke(&user.id).await;
+
+    Ok((StatusCode::CREATED, Json(created.into())))
+}
+
+#[tracing::instrument(skip(state, user), fields(user_id = %user.id))]
+pub async fn get_attachment(
+    State(state): State<AppState>,
+    Extension(user): Extension<dtos::user::User>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let service = &state.service_container.attachment_service;
+
+    let attachment = {
+        let mut conn = state
+            .db_pool
+            .get()
+            .context("DB pool")
+            .map_err(internal_error)?;
+
+        service
+            .get(&mut conn, &id, &user.id)
+            .map_err(|_| (StatusCode::NOT_FOUND, "attachment not found".to_owned()))?
+    };
+
+    let data = service
+        .read_blob(&attachment)
+        .await
+        .context("read blob")
+        .map_err(internal_error)?;
+
+    Ok((
+        blob_headers(&attachment, "private, max-age=31536000, immutable"),
+        data,
+    ))
+}
+
+// Uploads are served from the app's own origin, so nothing in them may run there: documents are
+// downloaded rather than opened and the sandbox covers whatever a browser renders anyway.
+pub fn blob_headers(attachment: &Attachment, cache_control: &str) -> [(HeaderName, String); 5] {
+    let disposition = if attachment.is_image() {
+        "inline"
+    } else {
+        "attachment"
+    };
+    [
+        (header::CONTENT_TYPE, attachment.served_content_type()),
+        (header::CONTENT_DISPOSITION, disposition.to_owned()),
+        (header::CONTENT_SECURITY_POLICY, "sandbox".to_owned()),
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_owned()),
+        (header::CACHE_CONTROL, cache_control.to_owned()),
+    ]
+}
+
+#[tracing::instrument(skip(state, user), fields(user_id = %user.id))]
+pub async fn delete_attachment(
+    State(state): State<AppState>,
+    Extension(user): Extension<dtos::user::User>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let service = &state.service_container.attachment_service;
+
+    let attachment = {
+        let mut conn = state
+            .db_pool
+            .get()
+            .context("DB pool")
+            .map_err(internal_error)?;
+
+        service
+            .delete(&mut conn, &id, &user.id)
+            .map_err(|_| (StatusCode::NOT_FOUND, "attachment not found".to_owned()))?
+    };
+
+    // The row is gone, so a blob left behind here is only wasted space.
+    if let Err(e) = service.delete_blob(&attachment).await {
+        tracing::warn!(
+            "Failed to delete blob for attachment {}: {e:?}",
+            attachment.id
+        );
+    }
+
+    state.sse_manager.replicache_poke(&user.id).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn internal_error<E: std::fmt::Display>(e: E) -> (StatusCode, String) {
+    tracing::error!("{e}");
+    (StatusCode::INTERNAL_SERVER_ERROR, "internal error".into())
+}
@@ -0,0 +1,73 @@
+use anyhow::{Context, Result};
+use axum::{Extension, Json, extract::State, http::StatusCode};
+use serde::Deserialize;
+
+use crate::{ai::pricing::usd_to_micros, app::AppState, dtos};
+
+#[derive(Debug, Deserialize)]
+pub struct BudgetUpdateRequest {
+    // null removes the limit
+    pub monthly_limit_usd: Option<f64>,
+}
+
+#[tracing::instrument(skip(state, user), fields(user_id = %user.id))]
+pub async fn get_budget(
+    State(state): State<AppState>,
+    Extension(user): Extension<dtos::user::User>,
+) -> Result<Json<dtos::budget::Budget>, (StatusCode, String)> {
+    let mut conn = state
+        .db_pool
+        .get()
+        .context("DB pool")
+        .map_err(internal_error)?;
+
+    let status = state
+        .service_container
+        .budget_service
+        .status(&mut conn, &user.id)
+        .context("service")
+        .map_err(internal_error)?;
+
+    Ok(Json(status.into()))
+}
+
+#[tracing::instrument(skip(state, user), fields(user_id = %user.id))]
+pub async fn update_budget(
+    State(state): State<AppState>,
+    Extension(user): Extension<dtos::user::User>,
+    Json(payload): Json<BudgetUpdateRequest>,
+) -> Result<Json<dtos::budget::Budget>, (StatusCode, String)> {
+    if payload
+        .monthly_limit_usd
+        .is_some_and(|usd| !usd.is_finite() || usd < 0.0)
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "monthly_limit_usd must be a positive amount".into(),
+        ));
+    }
+
+    let mut conn = state
+        .db_pool
+        .get()
+        .context("DB pool")
+        .map_err(internal_error)?;
+
+    let status = state
+        .service_container
+        .budget_service
+        .set(
+            &mut conn,
+            &user.id,
+            payload.monthly_limit_usd.map(usd_to_micros),
+        )
+        .context("service")
+        .map_err(internal_error)?;
+
+    Ok(Json(status.into()))
+}
+
+fn internal_error<E: std::fmt::Display>(e: E) -> (StatusCode, String) {
+    tracing::error!("{e}");
+    (StatusCode::INTERNAL_SERVER_ERROR, "internal error".into())
+}
@@ -0,0 +1,168 @@
+use anyhow::{Context, Result};
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use reqwest::Url;
+use secrecy::SecretString;
+use serde::Deserialize;
+
+use crate::{
+    ai::custom::handler::list_models, app::AppState, configuration::ProviderSettings, dtos,
+    models::custom_endpoint::CreateArgs,
+};
+
+const MAX_NAME_LEN: usize = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct CustomEndpointCreateRequest {
+    pub name: String,
+    pub base_url: String,
+    #[serde(rename = "key")]
+    pub api_key: Option<SecretString>,
+}
+
+#[tracing::instrument(
+    skip(state, user, payload),
+    fields(user_id = %user.id, name = %payload.name)
+)]
+pub async fn create_custom_endpoint(
+    State(state): State<AppState>,
+    Extension(user): Extension<dtos::user::User>,
+    Json(payload): Json<CustomEndpointCreateRequest>,
+) -> Result<(StatusCode, Json<dtos::custom_endpoint::CustomEndpoint>), (StatusCode, String)> {
+    validate(&payload).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let mut conn = state
+        .db_pool
+        .get()
+        .context("DB pool")
+        .map_err(internal_error)?;
+
+    let args = CreateArgs {
+        name: payload.name,
+        base_url: payload.base_url,
+    };
+    let created = state
+        .service_container
+        .custom_endpoint_service
+        .create(&mut conn, &user.id, args, payload.api_key)
+        .context("service")
+        .map_err(internal_error)?;
+
+    Ok((StatusCode::CREATED, Json(created.into())))
+}
+
+#[tracing::instrument(skip(state, user))]
+pub async fn list_custom_endpoints(
+    State(state): State<AppState>,
+    Extension(user): Extension<dtos::user::User>,
+) -> Result<Json<Vec<dtos::custom_endpoint::CustomEndpoint>>, (StatusCode, String)> {
+    let mut conn = state
+        .db_pool
+        .get()
+        .context("DB pool")
+        .map_err(internal_error)?;
+
+    let list = state
+        .service_container
+        .custom_endpoint_service
+        .list(&mut conn, &user.id)
+        .context("service")
+        .map_err(internal_error)?;
+
+    Ok(Json(
+        list.into_iter()
+            .map(dtos::custom_endpoint::CustomEndpoint::from)
+            .collect(),
+    ))
+}
+
+#[tracing::instrument(skip(state, user))]
+pub async fn list_custom_endpoint_models(
+    State(state): State<AppState>,
+    Extension(user): Extension<dtos::user::User>,
+    Path(id): Path<u64>,
+) -> Result<Json<Vec<String>>, (StatusCode, String)> {
+    let (endpoint, api_key) = {
+        let mut conn = state
+            .db_pool
+            .get()
+            .context("DB pool")
+            .map_err(internal_error)?;
+
+        let service = &state.service_container.custom_endpoint_service;
+        let endpoint = service
+            .get(&mut conn, id, &user.id)
+            .map_err(|_| (StatusCode::NOT_FOUND, "endpoint not found".to_owned()))?;
+        let api_key = service
+            .get_api_key(&mut conn, &user.id, &endpoint)
+            .unwrap_or_else(|| SecretString::from(String::new()));
+
+        (endpoint, api_key)
+    };
+
+    let settings = ProviderSettings {
+        base_url: endpoint.base_url,
+        ..state.config.providers.custom.clone()
+    };
+
+    let models = list_models(&settings, &api_key).await.map_err(|e| {
+        tracing::warn!("{e}");
+        (
+            StatusCode::BAD_GATEWAY,
+            "could not list models for endpoint".to_owned(),
+        )
+    })?;
+
+    Ok(Json(models))
+}
+
+#[tracing::instrument(skip(state, user))]
+pub async fn delete_custom_endpoint(
+    State(state): State<AppState>,
+    Extension(user): Extension<dtos::user::User>,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let mut conn = state
+        .db_pool
+        .get()
+        .context("DB pool")
+        .map_err(internal_error)?;
+
+    state
+        .service_container
+        .custom_endpoint_service
+        .delete(&mut conn, id, &user.id)
+        .context("service")
+        .map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// The name ends up as the prefix of the model id ("<name>/<model>") and in the api key provider
+// column, so it is kept short and free of separators.
+fn validate(payload: &CustomEndpointCreateRequest) -> Result<(), String> {
+    let name = &payload.name;
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(format!("name must be 1-{MAX_NAME_LEN} characters"));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("name may only contain letters, digits, '-' and '_'".into());
+    }
+
+    let url = Url::parse(&payload.base_url).map_err(|_| "base_url is not a valid url")?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("base_url must be http or https".into());
+    }
+    Ok(())
+}
+
+fn internal_error<E: std::fmt::Display>(e: E) -> (StatusCode, String) {
+    tracing::error!("{e}");
+    (StatusCode::INTERNAL_SERVER_ERROR, "internal error".into())
+}
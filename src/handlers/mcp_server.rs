@@ -0,0 +1,260 @@
+use anyhow::{Context, Result};
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use reqwest::Url;
+use secrecy::SecretString;
+use serde::Deserialize;
+
+use crate::{
+    app::AppState,
+    configuration::McpSettings,
+    dtos,
+    models::mcp_server::{CreateArgs, McpTransport},
+};
+
+const MAX_NAME_LEN: usize = 50;
+const MAX_SERVERS: i64 = 10;
+const MAX_ARGS: usize = 32;
+const MAX_ARG_LEN: usize = 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct McpServerCreateRequest {
+    pub name: String,
+    pub transport: McpTransport,
+    pub url: Option<String>,
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    // sent as a bearer token to http servers
+    #[serde(rename = "key")]
+    pub api_key: Option<SecretString>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct McpToolsUpdateRequest {
+    // null enables every tool
+    pub enabled_tools: Option<Vec<String>>,
+}
+
+#[tracing::instrument(
+    skip(state, user, payload),
+    fields(user_id = %user.id, name = %payload.name)
+)]
+pub async fn create_mcp_server(
+    State(state): State<AppState>,
+    Extension(user): Extension<dtos::user::User>,
+    Json(payload): Json<McpServerCreateRequest>,
+) -> Result<(StatusCode, Json<dtos::mcp_server::McpServer>), (StatusCode, String)> {
+    validate(&payload, &state.config.mcp).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let mut conn = state
+        .db_pool
+        .get()
+        .context("DB pool")
+        .map_err(internal_error)?;
+
+    let service = &state.service_container.mcp_server_service;
+    let count = service
+        .count(&mut conn, &user.id)
+        .context("service")
+        .map_err(internal_error)?;
+    if count >= MAX_SERVERS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("at most {MAX_SERVERS} MCP servers can be registered"),
+        ));
+    }
+
+    let args = CreateArgs {
+        name: payload.name,
+        transport: payload.transport,
+        url: payload.url,
+        command: payload.command,
+        args: payload.args,
+    };
+    let created = service
+        .create(&mut conn, &user.id, args, payload.api_key)
+        .context("service")
+        .map_err(internal_error)?;
+
+    Ok((StatusCode::CREATED, Json(created.into())))
+}
+
+#[tracing::instrument(skip(state, user))]
+pub async fn list_mcp_servers(
+    State(state): State<AppState>,
+    Extension(user): Extension<dtos::user::User>,
+) -> Result<Json<Vec<dtos::mcp_server::McpServer>>, (StatusCode, String)> {
+    let mut conn = state
+        .db_pool
+        .get()
+        .context("DB pool")
+        .map_err(internal_error)?;
+
+    let list = state
+        .service_container
+        .mcp_server_service
+        .list(&mut conn, &user.id)
+        .context("service")
+        .map_err(internal_error)?;
+
+    Ok(Json(
+        list.into_iter()
+            .map(dtos::mcp_server::McpServer::from)
+            .collect(),
+    ))
+}
+
+// Connects to the server if needed and asks it for its tools afresh.
+#[tracing::instrument(skip(state, user))]
+pub async fn list_mcp_server_tools(
+    State(state): State<AppState>,
+    Extension(user): Extension<dtos::user::User>,
+    Path(id): Path<u64>,
+) -> Result<Json<Vec<dtos::mcp_server::McpTool>>, (StatusCode, String)> {
+    let (server, api_key) = {
+        let mut conn = state
+            .db_pool
+            .get()
+            .context("DB pool")
+            .map_err(internal_error)?;
+
+        let service = &state.service_container.mcp_server_service;
+        let server = service
+            .get(&mut conn, id, &user.id)
+            .map_err(|_| (StatusCode::NOT_FOUND, "server not found".to_owned()))?;
+        let api_key = service.get_api_key(&mut conn, &user.id, &server);
+
+        (server, api_key)
+    };
+
+    state.mcp_manager.clear_failure(server.id);
+    let tools = async {
+        let session = state.mcp_manager.session(&server, api_key).await?;
+        session.refresh_tools().await
+    }
+    .await
+    .map_err(|e| {
+        tracing::warn!("{e:#}");
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("could not list tools for server: {e}"),
+        )
+    })?;
+
+    Ok(Json(
+        tools
+            .into_iter()
+            .map(|tool| dtos::mcp_server::McpTool {
+                enabled: server.is_enabled(&tool.name),
+                description: tool.description.unwrap_or_default(),
+                name: tool.name,
+            })
+            .collect(),
+    ))
+}
+
+#[tracing::instrument(skip(state, user, payload))]
+pub async fn update_mcp_server_tools(
+    State(state): State<AppState>,
+    Extension(user): Extension<dtos::user::User>,
+    Path(id): Path<u64>,
+    Json(payload): Json<McpToolsUpdateRequest>,
+) -> Result<Json<dtos::mcp_server::McpServer>, (StatusCode, String)> {
+    let mut conn = state
+        .db_pool
+        .get()
+        .context("DB pool")
+        .map_err(internal_error)?;
+
+    let service = &state.service_container.mcp_server_service;
+    service
+        .get(&mut conn, id, &user.id)
+        .map_err(|_| (StatusCode::NOT_FOUND, "server not found".to_owned()))?;
+
+    let updated = service
+        .set_enabled_tools(&mut conn, id, &user.id, payload.enabled_tools)
+        .context("service")
+        .map_err(internal_error)?;
+
+    Ok(Json(updated.into()))
+}
+
+#[tracing::instrument(skip(state, user))]
+pub async fn delete_mcp_server(
+    State(state): State<AppState>,
+    Extension(user): Extension<dtos::user::User>,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    {
+        let mut conn = state
+            .db_pool
+            .get()
+            .context("DB pool")
+            .map_err(internal_error)?;
+
+        state
+            .service_container
+            .mcp_server_service
+            .delete(&mut conn, id, &user.id)
+            .context("service")
+            .map_err(internal_error)?;
+    }
+
+    state.mcp_manager.close_everywhere(id).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// The name prefixes the tool names the model sees ("<name>__<tool>") and goes in the api key
+// provider column, so it is kept short and free of separators.
+fn validate(payload: &McpServerCreateRequest, settings: &McpSettings) -> Result<(), String> {
+    let name = &payload.name;
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(format!("name must be 1-{MAX_NAME_LEN} characters"));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("name may only contain letters, digits, '-' and '_'".into());
+    }
+
+    match payload.transport {
+        McpTransport::Http => {
+            let url = payload.url.as_deref().ok_or("url is required for http")?;
+            let url = Url::parse(url).map_err(|_| "url is not a valid url")?;
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err("url must be http or https".into());
+            }
+        }
+        McpTransport::Stdio => {
+            let command = payload
+                .command
+                .as_deref()
+                .ok_or("command is required for stdio")?;
+            if payload.args.len() > MAX_ARGS || payload.args.iter().any(|a| a.len() > MAX_ARG_LEN) {
+                return Err(format!(
+                    "at most {MAX_ARGS} args of up to {MAX_ARG_LEN} characters"
+                ));
+            }
+            if !settings.allows_stdio(command, &payload.args) {
+                return Err(format!(
+                    "'{command}' with these args is not an allowed command"
+                ));
+            }
+            if payload.api_key.is_some() {
+                return Err("key is only used by http servers".into());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn internal_error<E: std::fmt::Display>(e: E) -> (StatusCode, String) {
+    tracing::error!("{e}");
+    (StatusCode::INTERNAL_SERVER_ERROR, "internal error".into())
+}
@@ -1,5 +1,11 @@
 pub mod api_key;
+pub mod attachment;
 pub mod auth;
+pub mod budget;
+pub mod custom_endpoint;
+pub mod mcp_server;
+pub mod model_catalog;
 pub mod replicache;
 pub mod shared_chat;
 pub mod sse;
+pub mod usage;
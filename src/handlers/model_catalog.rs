@@ -0,0 +1,32 @@
+use anyhow::Context;
+use axum::{Extension, Json, extract::State, http::StatusCode};
+
+use crate::{app::AppState, dtos};
+
+#[tracing::instrument(skip(state, user), fields(user_id = %user.id))]
+pub async fn list_models(
+    State(state): State<AppState>,
+    Extension(user): Extension<dtos::user::User>,
+) -> Result<Json<Vec<dtos::model_catalog::CatalogModel>>, (StatusCode, String)> {
+    let catalog = &state.service_container.model_catalog_service;
+
+    let sources = {
+        let mut conn = state
+            .db_pool
+            .get()
+            .context("DB pool")
+            .map_err(internal_error)?;
+
+        catalog
+            .sources(&state, &mut conn, &user.id)
+            .context("service")
+            .map_err(internal_error)?
+    };
+
+    Ok(Json(catalog.list(&state, sources).await))
+}
+
+fn internal_error<E: std::fmt::Display>(e: E) -> (StatusCode, String) {
+    tracing::error!("{e}");
+    (StatusCode::INTERNAL_SERVER_ERROR, "internal error".into())
+}
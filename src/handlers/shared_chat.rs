@@ -3,10 +3,11 @@ use axum::{
     Extension, Json,
     extract::{Path, State},
     http::StatusCode,
+    response::IntoResponse,
 };
 use tracing::instrument;
 
-use crate::{app::AppState, dtos};
+use crate::{app::AppState, dtos, handlers::attachment::blob_headers};
 
 // Public
 #[instrument(skip(state))]
@@ -30,6 +31,37 @@ pub async fn get_shared_chat(
     Ok(Json(snapshot.into()))
 }
 
+// Public
+#[instrument(skip(state))]
+pub async fn get_shared_attachment(
+    State(state): State<AppState>,
+    Path((id, attachment_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let attachment = {
+        let mut conn = state
+            .db_pool
+            .get()
+            .context("DB pool")
+            .map_err(internal_error)?;
+
+        state
+            .service_container
+            .shared_chat_service
+            .get_attachment(&mut conn, &id, &attachment_id)
+            .map_err(|_| (StatusCode::NOT_FOUND, "attachment not found".to_owned()))?
+    };
+
+    let data = state
+        .service_container
+        .attachment_service
+        .read_blob(&attachment)
+        .await
+        .context("read blob")
+        .map_err(internal_error)?;
+
+    Ok((blob_headers(&attachment, "public, max-age=3600"), data))
+}
+
 #[instrument(skip(state, user), fields(user_id=%user.id, chat_id=%chat_id))]
 pub async fn create_shared_chat(
     State(state): State<AppState>,
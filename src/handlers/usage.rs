@@ -0,0 +1,55 @@
+use anyhow::{Context, Result};
+use axum::{
+    Extension, Json,
+    extract::{Query, State},
+    http::StatusCode,
+};
+use chrono::{Days, NaiveDate, Utc};
+use serde::Deserialize;
+
+use crate::{app::AppState, dtos};
+
+const DEFAULT_RANGE_DAYS: u64 = 30;
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[tracing::instrument(skip(state, user), fields(user_id = %user.id))]
+pub async fn get_usage(
+    State(state): State<AppState>,
+    Extension(user): Extension<dtos::user::User>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<dtos::usage::UsageSummary>, (StatusCode, String)> {
+    let to = query.to.unwrap_or_else(|| Utc::now().date_naive());
+    let from = query
+        .from
+        .or_else(|| to.checked_sub_days(Days::new(DEFAULT_RANGE_DAYS - 1)))
+        .unwrap_or(to);
+
+    if from > to {
+        return Err((StatusCode::BAD_REQUEST, "from must not be after to".into()));
+    }
+
+    let mut conn = state
+        .db_pool
+        .get()
+        .context("DB pool")
+        .map_err(internal_error)?;
+
+    let summary = state
+        .service_container
+        .usage_service
+        .summary(&mut conn, &user.id, from, to)
+        .context("service")
+        .map_err(internal_error)?;
+
+    Ok(Json(summary.into()))
+}
+
+fn internal_error<E: std::fmt::Display>(e: E) -> (StatusCode, String) {
+    tracing::error!("{e}");
+    (StatusCode::INTERNAL_SERVER_ERROR, "internal error".into())
+}
@@ -1,2 +1,4 @@
 pub mod db;
 pub mod redis;
+pub mod search;
+pub mod storage;
@@ -1,6 +1,11 @@
+use std::{future::Future, time::Duration};
+
 use secrecy::{ExposeSecret, SecretString};
+use tokio::sync::broadcast::error::RecvError;
 use tower_sessions_redis_store::fred::prelude::{Pool as RedisPool, *};
 
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
 pub async fn establish_connection(url: SecretString) -> RedisPool {
     let cfg = Config::from_url(url.expose_secret()).expect("Failed to create redis config");
     let pool = RedisPool::new(cfg, None, None, None, 6).expect("Failed to create redis pool");
@@ -12,3 +17,56 @@ pub async fn establish_connection(url: SecretString) -> RedisPool {
 
     pool
 }
+
+pub async fn publish(pool: &RedisPool, channel: &str, payload: String) -> anyhow::Result<()> {
+    pool.next().publish::<i64, _, _>(channel, payload).await?;
+    Ok(())
+}
+
+// Hands every message published on `channel` to `handle`. Runs for as long as the process does,
+// subscribing again whenever the connection drops.
+pub async fn subscribe<F, Fut>(pool: RedisPool, channel: &'static str, handle: F)
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    loop {
+        if let Err(e) = listen(&pool, channel, &handle).await {
+            tracing::warn!(channel, error = ?e, "Redis subscription dropped");
+        }
+        tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+    }
+}
+
+async fn listen<F, Fut>(pool: &RedisPool, channel: &str, handle: &F) -> anyhow::Result<()>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    // Subscribing takes over the connection, so it gets one of its own.
+    let subscriber = pool.next().clone_new();
+    let mut connection = subscriber.init().await?;
+    let mut rx = subscriber.message_rx();
+    subscriber.subscribe(channel).await?;
+    tracing::info!(channel, "Subscribed");
+
+    loop {
+        tokio::select! {
+            message = rx.recv() => match message {
+                Ok(message) => {
+                    if let Some(payload) = message.value.as_string() {
+                        handle(payload).await;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(channel, skipped, "Subscriber fell behind");
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            },
+            result = &mut connection => {
+                result??;
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::configuration::SearchSettings;
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub url: String,
+    pub title: String,
+    pub snippet: String,
+}
+
+// Where the web search tool gets its results from.
+#[async_trait]
+pub trait SearchBackend: Debug + Send + Sync {
+    async fn search(&self, query: &str) -> Result<Vec<SearchResult>>;
+}
+
+pub fn build(settings: &SearchSettings) -> Option<Arc<dyn SearchBackend>> {
+    match settings {
+        SearchSettings::Disabled => None,
+        SearchSettings::Searxng {
+            base_url,
+            request_timeout_secs,
+            max_results,
+        } => Some(Arc::new(SearxngSearch {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            timeout: Duration::from_secs(*request_timeout_secs),
+            max_results: *max_results,
+        })),
+    }
+}
+
+#[derive(Debug)]
+pub struct SearxngSearch {
+    client: reqwest::Client,
+    base_url: String,
+    timeout: Duration,
+    max_results: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearxngResponse {
+    #[serde(default)]
+    results: Vec<SearxngResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearxngResult {
+    url: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    content: String,
+}
+
+#[async_trait]
+impl SearchBackend for SearxngSearch {
+    async fn search(&self, query: &str) -> Result<Vec<SearchResult>> {
+        let res: SearxngResponse = self
+            .client
+            .get(format!("{}/search", self.base_url))
+            .query(&[("q", query), ("format", "json")])
+            .timeout(self.timeout)
+            .send()
+            .await
+            .context("Search request failed")?
+            .error_for_status()
+            .context("Search request HTTP error")?
+            .json()
+            .await
+            .context("Search JSON decode failed")?;
+
+        Ok(res
+            .results
+            .into_iter()
+            .take(self.max_results)
+            .map(|r| SearchResult {
+                url: r.url,
+                title: r.title,
+                snippet: r.content,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,76 @@
+use std::{fmt::Debug, io::ErrorKind, path::PathBuf, sync::Arc};
+
+use anyhow::{Context, Result, ensure};
+use async_trait::async_trait;
+
+use crate::configuration::StorageSettings;
+
+// Where uploaded files live. Keys are "/" separated paths picked by the caller.
+#[async_trait]
+pub trait BlobStore: Debug + Send + Sync {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()>;
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>>;
+
+    // Deleting a missing blob is not an error.
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+pub fn build(settings: &StorageSettings) -> Arc<dyn BlobStore> {
+    match settings {
+        StorageSettings::Local { path } => Arc::new(LocalBlobStore::new(path)),
+    }
+}
+
+#[derive(Debug)]
+pub struct LocalBlobStore {
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    // Keys are generated server side, but a bad one must never escape the root.
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        ensure!(
+            !key.split('/')
+                .any(|s| s.is_empty() || s == "." || s == ".."),
+            "Invalid blob key: '{key}'"
+        );
+
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let path = self.path(key)?;
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .with_context(|| format!("Failed to create directory for blob {key}"))?;
+        }
+
+        tokio::fs::write(&path, data)
+            .await
+            .with_context(|| format!("Failed to write blob {key}"))
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        tokio::fs::read(self.path(key)?)
+            .await
+            .with_context(|| format!("Failed to read blob {key}"))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Failed to delete blob {key}"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -1,68 +1,326 @@
-use anyhow::Result;
-use tokio::sync::mpsc;
-use tokio_retry2::{
-    Retry, RetryError,
-    strategy::{ExponentialBackoff, jitter},
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::Semaphore;
+use uuid::Uuid;
+
 use crate::{
-    ai::handler::{generate_response, generate_title},
+    ai::handler::{Attempt, generate_image, generate_response, generate_title},
     app::AppState,
-    models::message::Message,
+    models::{
+        job::{JobStatus, QueuedJob, WaitingJob},
+        message::CompareModel,
+    },
+    services::sse_manager::{EventType, SseMessage},
 };
 
-#[derive(Debug, Clone)]
+const POSITION_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+// Queued jobs are stored serialized, the renames keep those written under the old names readable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Job {
-    GenerateTitle {
+    #[serde(rename = "GenerateTitle")]
+    Title {
         chat_id: String,
         user_id: String,
         first_body: String,
     },
-    GenerateResponse {
+    // The history is read when the job runs, so a reply that finished while this one waited is
+    // part of it.
+    #[serde(rename = "GenerateResponse")]
+    Response {
+        chat_id: String,
+        user_id: String,
+        // The message being answered.
+        message_id: String,
+        // Models to answer side by side, empty for a single reply.
+        compare: Vec<CompareModel>,
+    },
+    #[serde(rename = "GenerateImage")]
+    Image {
         chat_id: String,
         user_id: String,
-        messages: Vec<Message>,
+        message_id: String,
+        prompt: String,
     },
 }
 
-pub async fn run_worker(state: AppState, mut rx: mpsc::UnboundedReceiver<Job>) {
-    while let Some(job) = rx.recv().await {
-        let state_cloned = state.clone();
+impl Job {
+    pub const TITLE: &str = "title";
+    pub const RESPONSE: &str = "response";
+    pub const IMAGE: &str = "image";
+    // Kinds that stream into the chat, at most one of them runs per chat at a time.
+    pub const GENERATIONS: [&str; 2] = [Self::RESPONSE, Self::IMAGE];
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Job::Title { .. } => Self::TITLE,
+            Job::Response { .. } => Self::RESPONSE,
+            Job::Image { .. } => Self::IMAGE,
+        }
+    }
+
+    pub fn user_id(&self) -> &str {
+        match self {
+            Job::Title { user_id, .. }
+            | Job::Response { user_id, .. }
+            | Job::Image { user_id, .. } => user_id,
+        }
+    }
+
+    pub fn chat_id(&self) -> &str {
+        match self {
+            Job::Title { chat_id, .. }
+            | Job::Response { chat_id, .. }
+            | Job::Image { chat_id, .. } => chat_id,
+        }
+    }
+}
 
-        tokio::spawn(async move {
-            let strategy = ExponentialBackoff::from_millis(500).map(jitter).take(3);
+// Claims jobs from the queue for as long as the process runs. Any number of workers, in the web
+// process or the worker binary, can share the queue.
+pub async fn run_worker(state: AppState) {
+    let worker_id = Uuid::new_v4().to_string();
+    let poll_interval = state.config.jobs.poll_interval();
+    let slots = Arc::new(Semaphore::new(state.config.jobs.max_concurrent));
+    let mut positions = QueuePositions::default();
+    tracing::info!(%worker_id, "Worker started");
 
-            let result = Retry::spawn(strategy, || {
-                let state_inner = state_cloned.clone();
-                let job_inner = job.clone();
-                async move {
-                    handle_job(&state_inner, job_inner)
-                        .await
-                        .map_err(RetryError::transient)
+    loop {
+        // With every slot taken the queue is left to the other workers.
+        if let Ok(slot) = slots.clone().try_acquire_owned() {
+            match claim(&state, &worker_id) {
+                // Straight on to the next one, there may be more waiting.
+                Ok(Some(job)) => {
+                    let state = state.clone();
+                    let worker_id = worker_id.clone();
+                    tokio::spawn(async move {
+                        run_job(state, worker_id, job).await;
+                        drop(slot);
+                    });
+                    continue;
                 }
-            })
-            .await;
+                Ok(None) => {}
+                Err(e) => tracing::error!(error = ?e, "Failed to claim a job"),
+            }
+        }
+
+        positions.report(&state).await;
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+// Tells users where their waiting generations are in the queue, each time that changes. Every
+// worker reports, so the same position can arrive more than once.
+#[derive(Default)]
+struct QueuePositions {
+    reported: HashMap<String, usize>,
+    last_run: Option<Instant>,
+}
+
+impl QueuePositions {
+    async fn report(&mut self, state: &AppState) {
+        if self
+            .last_run
+            .is_some_and(|t| t.elapsed() < POSITION_REPORT_INTERVAL)
+        {
+            return;
+        }
+        self.last_run = Some(Instant::now());
 
-            if let Err(e) = result {
-                tracing::error!(error = ?e, job = ?job, "Job permanently failed");
+        let positions = match read_positions(state) {
+            Ok(positions) => positions,
+            Err(e) => {
+                tracing::warn!(error = ?e, "Failed to read queue positions");
+                return;
             }
-        });
+        };
+
+        let mut reported = HashMap::new();
+        for (job, position) in positions {
+            // Titles aren't shown while they wait.
+            if job.kind == Job::TITLE {
+                continue;
+            }
+            if self.reported.get(&job.id) != Some(&position) {
+                let msg = SseMessage {
+                    event_type: EventType::Queued,
+                    data: Some(json!({ "chat_id": job.chat_id, "position": position })),
+                };
+                state.sse_manager.send_to_user(&job.user_id, msg).await;
+            }
+            reported.insert(job.id, position);
+        }
+        self.reported = reported;
     }
 }
 
-async fn handle_job(state: &AppState, job: Job) -> Result<()> {
+fn claim(state: &AppState, worker_id: &str) -> Result<Option<QueuedJob>> {
+    let mut conn = state.db_pool.get()?;
+    state
+        .service_container
+        .job_queue_service
+        .claim(&mut conn, worker_id)
+}
+
+fn read_positions(state: &AppState) -> Result<Vec<(WaitingJob, usize)>> {
+    let mut conn = state.db_pool.get()?;
+    state
+        .service_container
+        .job_queue_service
+        .positions(&mut conn)
+}
+
+async fn run_job(state: AppState, worker_id: String, queued: QueuedJob) {
+    tracing::info!(
+        job_id = queued.id,
+        kind = queued.kind,
+        attempt = queued.attempts,
+        "Running job"
+    );
+
+    let attempt = Attempt {
+        id: Uuid::new_v4().to_string(),
+        retry: queued.attempts > 1,
+    };
+
+    let result = match serde_json::from_str::<Job>(&queued.payload) {
+        Ok(job) => {
+            let mut heartbeat = tokio::spawn(renew_lease(
+                state.clone(),
+                worker_id.clone(),
+                queued.id.clone(),
+            ));
+            // Once the lease is lost another worker can claim the job, so this one stops rather
+            // than generate alongside it.
+            let result = tokio::select! {
+                result = handle_job(&state, job, &attempt) => result,
+                _ = &mut heartbeat => {
+                    tracing::warn!(job_id = queued.id, "Stopped a job after losing its lease");
+                    return;
+                }
+            };
+            heartbeat.abort();
+            result
+        }
+        Err(e) => Err(e.into()),
+    };
+
+    if let Err(e) = finish_job(&state, &worker_id, &queued, result) {
+        tracing::error!(job_id = queued.id, error = ?e, "Failed to record job result");
+    }
+}
+
+fn finish_job(
+    state: &AppState,
+    worker_id: &str,
+    queued: &QueuedJob,
+    result: Result<()>,
+) -> Result<()> {
+    let queue = &state.service_container.job_queue_service;
+    let mut conn = state.db_pool.get()?;
+
+    let Err(e) = result else {
+        return queue.complete(&mut conn, &queued.id, worker_id);
+    };
+
+    let retry = is_transient(&e);
+    match queue.fail(&mut conn, queued, worker_id, &format!("{e:#}"), retry)? {
+        JobStatus::Dead => {
+            tracing::error!(job_id = queued.id, error = ?e, "Job permanently failed")
+        }
+        _ => tracing::warn!(job_id = queued.id, error = ?e, "Job failed, will retry"),
+    }
+    Ok(())
+}
+
+// Keeps the lease while the job runs, so that only a worker that stopped loses its jobs. Returns
+// once the lease is lost.
+async fn renew_lease(state: AppState, worker_id: String, job_id: String) {
+    let period = state.config.jobs.lease() / 3;
+    let mut interval = tokio::time::interval(period.max(Duration::from_secs(1)));
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        let renewed = state
+            .db_pool
+            .get()
+            .map_err(anyhow::Error::from)
+            .and_then(|mut conn| {
+                state
+                    .service_container
+                    .job_queue_service
+                    .renew(&mut conn, &job_id, &worker_id)
+            });
+        match renewed {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => tracing::warn!(%job_id, error = ?e, "Failed to renew job lease"),
+        }
+    }
+}
+
+// Whether trying again can help: the provider or database couldn't be reached, was overloaded or
+// rate limited us. Anything else, an invalid key or a rejected request among them, fails the same
+// way every time.
+pub fn is_transient(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<reqwest_eventsource::Error>() {
+            return match e {
+                reqwest_eventsource::Error::Transport(e) => is_transient_request(e),
+                reqwest_eventsource::Error::InvalidStatusCode(status, _) => {
+                    is_transient_status(*status)
+                }
+                reqwest_eventsource::Error::StreamEnded => true,
+                _ => false,
+            };
+        }
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            return is_transient_request(e);
+        }
+        cause.is::<diesel::r2d2::PoolError>()
+    })
+}
+
+fn is_transient_request(e: &reqwest::Error) -> bool {
+    match e.status() {
+        Some(status) => is_transient_status(status),
+        None => e.is_connect() || e.is_timeout() || e.is_request() || e.is_body(),
+    }
+}
+
+fn is_transient_status(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+async fn handle_job(state: &AppState, job: Job, attempt: &Attempt) -> Result<()> {
     match job {
-        Job::GenerateTitle {
+        Job::Title {
             chat_id,
             user_id,
             first_body,
         } => generate_title(state, chat_id, user_id, first_body).await?,
 
-        Job::GenerateResponse {
+        Job::Response {
+            chat_id,
+            user_id,
+            message_id,
+            compare,
+        } => generate_response(state, chat_id, user_id, message_id, compare, attempt).await?,
+
+        Job::Image {
             chat_id,
             user_id,
-            messages,
-        } => generate_response(state, chat_id, user_id, messages).await?,
+            message_id,
+            prompt,
+        } => generate_image(state, chat_id, user_id, message_id, prompt, attempt).await?,
     }
     Ok(())
 }
@@ -0,0 +1,201 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::dtos;
+
+use super::replicache::ReplicachePullModel;
+
+// Formats every image capable provider accepts.
+pub const IMAGE_TYPES: &[&str] = &["image/png", "image/jpeg", "image/webp", "image/gif"];
+
+const PDF_TYPE: &str = "application/pdf";
+// Text files are only ever served as plain text, whatever they were sent as, so an uploaded page
+// can't run on the app's origin.
+const PLAIN_TEXT_TYPE: &str = "text/plain; charset=utf-8";
+
+const TEXT_TYPES: &[&str] = &[
+    "application/javascript",
+    "application/json",
+    "application/sql",
+    "application/toml",
+    "application/x-sh",
+    "application/x-yaml",
+    "application/xml",
+    "application/yaml",
+];
+
+const TEXT_EXTENSIONS: &[&str] = &[
+    "bash", "c", "cc", "cfg", "conf", "cpp", "cs", "css", "csv", "ex", "exs", "go", "h", "hpp",
+    "hs", "html", "ini", "java", "js", "json", "jsx", "kt", "log", "lua", "markdown", "md", "mjs",
+    "php", "py", "rb", "rs", "scala", "scss", "sh", "sql", "svelte", "swift", "toml", "ts", "tsx",
+    "txt", "vue", "xml", "yaml", "yml", "zsh",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachmentKind {
+    Image,
+    Pdf,
+    Text,
+}
+
+impl AttachmentKind {
+    // Browsers often send source files as application/octet-stream, so the extension decides
+    // when the content type doesn't.
+    pub fn detect(content_type: &str, filename: &str) -> Option<Self> {
+        if IMAGE_TYPES.contains(&content_type) {
+            return Some(Self::Image);
+        }
+        if content_type == PDF_TYPE {
+            return Some(Self::Pdf);
+        }
+        if content_type.starts_with("text/") || TEXT_TYPES.contains(&content_type) {
+            return Some(Self::Text);
+        }
+
+        let extension = filename.rsplit_once('.')?.1.to_ascii_lowercase();
+        match extension.as_str() {
+            "pdf" => Some(Self::Pdf),
+            ext if TEXT_EXTENSIONS.contains(&ext) => Some(Self::Text),
+            _ => None,
+        }
+    }
+
+    // The type stored and served back, so a file detected by extension stays readable.
+    pub fn content_type(self, sent: &str) -> String {
+        match self {
+            Self::Image => sent.to_owned(),
+            Self::Pdf => PDF_TYPE.to_owned(),
+            Self::Text => PLAIN_TEXT_TYPE.to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Queryable, Identifiable, Insertable, Serialize, Deserialize, Clone)]
+#[diesel(table_name = crate::schema::attachments)]
+pub struct Attachment {
+    pub id: String,
+    pub user_id: String,
+    pub message_id: Option<String>,
+    pub filename: String,
+    pub content_type: String,
+    pub size_bytes: u64,
+    pub storage_key: String,
+    pub version: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = crate::schema::attachments)]
+pub struct Changeset {
+    pub message_id: Option<String>,
+    pub version: i32,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateArgs {
+    pub id: String,
+    pub filename: String,
+    pub content_type: String,
+    pub size_bytes: u64,
+    pub storage_key: String,
+}
+
+impl Attachment {
+    pub fn kind(&self) -> Option<AttachmentKind> {
+        AttachmentKind::detect(&self.content_type, &self.filename)
+    }
+
+    pub fn is_image(&self) -> bool {
+        self.kind() == Some(AttachmentKind::Image)
+    }
+
+    // Rows stored before documents were forced to plain text may still hold the sent type.
+    pub fn served_content_type(&self) -> String {
+        match self.kind() {
+            Some(kind) => kind.content_type(&self.content_type),
+            None => "application/octet-stream".to_owned(),
+        }
+    }
+
+    pub fn is_document(&self) -> bool {
+        matches!(
+            self.kind(),
+            Some(AttachmentKind::Pdf | AttachmentKind::Text)
+        )
+    }
+
+    // Blobs are grouped per user so they can be cleaned up together.
+    pub fn storage_key(user_id: &str, id: &str) -> String {
+        format!("{user_id}/{id}")
+    }
+
+    // Text extracted from a document on upload is kept next to the original file.
+    pub fn text_key(storage_key: &str) -> String {
+        format!("{storage_key}.txt")
+    }
+}
+
+impl ReplicachePullModel for Attachment {
+    fn resource_prefix() -> &'static str {
+        "attachment"
+    }
+
+    fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    fn get_version(&self) -> i32 {
+        self.version
+    }
+}
+
+impl From<Attachment> for dtos::attachment::Attachment {
+    fn from(value: Attachment) -> Self {
+        dtos::attachment::Attachment {
+            id: value.id,
+            message_id: value.message_id,
+            filename: value.filename,
+            content_type: value.content_type,
+            size_bytes: value.size_bytes,
+            created_at: value.created_at.and_utc(),
+            updated_at: value.updated_at.and_utc(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    #[test]
+    fn html_uploads_are_stored_as_plain_text() {
+        let kind = AttachmentKind::detect("text/html", "page.html");
+
+        assert_eq!(kind, Some(AttachmentKind::Text));
+        assert_eq!(kind.unwrap().content_type("text/html"), PLAIN_TEXT_TYPE);
+    }
+
+    #[test]
+    fn stored_html_is_served_as_plain_text() {
+        let now = Utc::now().naive_utc();
+        let attachment = Attachment {
+            id: "a1".to_owned(),
+            user_id: "user".to_owned(),
+            message_id: None,
+            filename: "page.html".to_owned(),
+            content_type: "text/html".to_owned(),
+            size_bytes: 0,
+            storage_key: "user/a1".to_owned(),
+            version: 1,
+            created_at: now,
+            updated_at: now,
+        };
+
+        assert_eq!(attachment.served_content_type(), PLAIN_TEXT_TYPE);
+    }
+}
@@ -2,7 +2,7 @@ use chrono::{DateTime, NaiveDateTime, Utc};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::dtos;
+use crate::{ai::reasoning::EffortLevel, dtos};
 
 use super::{message::Message, replicache::ReplicachePullModel};
 
@@ -19,6 +19,17 @@ pub struct Chat {
     pub pinned_at: Option<NaiveDateTime>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub system_prompt: Option<String>,
+    // Rolling summary of the messages too old to fit the context window, up to and including
+    // summary_message_id. Server side only.
+    pub summary: Option<String>,
+    pub summary_message_id: Option<String>,
+    // Model picked for this chat, when unset the user's active model is used.
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub reasoning: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
 }
 
 #[derive(AsChangeset)]
@@ -30,6 +41,21 @@ pub struct Changeset {
     pub version: i32,
     pub updated_at: NaiveDateTime,
     pub pinned_at: Option<NaiveDateTime>,
+    // Some(None) clears the override
+    pub system_prompt: Option<Option<String>>,
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = crate::schema::chats)]
+#[diesel(treat_none_as_null = true)]
+pub struct ModelChangeset {
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub reasoning: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub version: i32,
+    pub updated_at: NaiveDateTime,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,6 +76,25 @@ pub struct UpdateArgs {
     pub updated_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateSystemPromptArgs {
+    pub id: String,
+    pub system_prompt: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// Leaving provider and model unset goes back to the user's active model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateModelArgs {
+    pub id: String,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub reasoning: Option<EffortLevel>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub updated_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeleteArgs {
     pub id: String,
@@ -58,6 +103,13 @@ pub struct DeleteArgs {
 #[derive(Debug, Clone, Deserialize)]
 pub struct ForkArgs {
     pub new_id: String,
+    // The chat being forked, its model settings and system prompt are copied to the new one.
+    #[serde(default)]
+    pub source_id: Option<String>,
+    // The last message copied. Tool calls and citations aren't sent by the client, they come from
+    // the stored messages leading up to this one.
+    #[serde(default)]
+    pub until_id: Option<String>,
     pub title: String,
     pub time: DateTime<Utc>,
     pub msgs: Vec<super::message::CreateArgs>,
@@ -75,6 +127,14 @@ pub struct ChatWithMessages {
     pub pinned_at: Option<NaiveDateTime>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub system_prompt: Option<String>,
+    pub summary: Option<String>,
+    pub summary_message_id: Option<String>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub reasoning: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
     pub messages: Vec<Message>,
 }
 
@@ -103,6 +163,12 @@ impl From<Chat> for dtos::chat::Chat {
             pinned_at: value.pinned_at.and_then(|v| Some(v.and_utc())),
             created_at: value.created_at.and_utc(),
             updated_at: value.updated_at.and_utc(),
+            system_prompt: value.system_prompt,
+            provider: value.provider,
+            model: value.model,
+            reasoning: value.reasoning,
+            temperature: value.temperature,
+            top_p: value.top_p,
         }
     }
 }
@@ -0,0 +1,65 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::dtos;
+
+#[derive(Debug, Queryable, Identifiable, Clone, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::custom_endpoints)]
+pub struct CustomEndpoint {
+    pub id: u64,
+    pub user_id: String,
+    pub name: String,
+    pub base_url: String,
+    pub version: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::custom_endpoints)]
+pub struct NewCustomEndpoint {
+    pub user_id: String,
+    pub name: String,
+    pub base_url: String,
+    pub version: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateArgs {
+    pub name: String,
+    pub base_url: String,
+}
+
+impl CustomEndpoint {
+    pub fn build_new(user_id: String, args: CreateArgs) -> NewCustomEndpoint {
+        NewCustomEndpoint {
+            user_id,
+            name: args.name,
+            base_url: args.base_url.trim_end_matches('/').to_owned(),
+            version: 1,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+        }
+    }
+
+    // Keys for custom endpoints are stored through the api key service under this provider name,
+    // so that each endpoint can have its own (optional) key.
+    pub fn key_provider(name: &str) -> String {
+        format!("custom:{name}")
+    }
+}
+
+impl From<CustomEndpoint> for dtos::custom_endpoint::CustomEndpoint {
+    fn from(value: CustomEndpoint) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            base_url: value.base_url,
+            created_at: value.created_at.and_utc(),
+            updated_at: value.updated_at.and_utc(),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use strum::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    // Out of attempts, kept for inspection and never picked up again.
+    Dead,
+}
+
+// A job waiting in or claimed from the queue. Finished jobs are deleted.
+#[derive(Debug, Queryable, Identifiable, Insertable, Clone)]
+#[diesel(table_name = crate::schema::jobs)]
+pub struct QueuedJob {
+    pub id: String,
+    pub kind: String,
+    pub user_id: String,
+    pub chat_id: String,
+    // The serialized job
+    pub payload: String,
+    pub status: String,
+    // Including the one running, counted when claimed.
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_at: NaiveDateTime,
+    // The worker holding the lease, which it has to renew before locked_until.
+    pub locked_by: Option<String>,
+    pub locked_until: Option<NaiveDateTime>,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+// What scheduling needs to know of a job that hasn't started yet.
+#[derive(Debug, Queryable, Clone)]
+pub struct WaitingJob {
+    pub id: String,
+    pub kind: String,
+    pub user_id: String,
+    pub chat_id: String,
+    pub run_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+impl WaitingJob {
+    // Generations stream into their chat one at a time and in the order they were queued, each
+    // answering from the history the one before it left. So only the oldest waiting generation of
+    // a chat can start, and only while none of the chat's is running. Other jobs aren't held back.
+    pub fn startable(
+        jobs: Vec<WaitingJob>,
+        busy_chats: &HashSet<String>,
+        generations: &[&str],
+    ) -> Vec<WaitingJob> {
+        let mut oldest: HashMap<&str, &WaitingJob> = HashMap::new();
+        for job in jobs
+            .iter()
+            .filter(|j| generations.contains(&j.kind.as_str()))
+        {
+            oldest
+                .entry(&job.chat_id)
+                .and_modify(|o| {
+                    if job.created_at < o.created_at {
+                        *o = job;
+                    }
+                })
+                .or_insert(job);
+        }
+        let next: HashSet<String> = oldest.values().map(|j| j.id.clone()).collect();
+
+        jobs.into_iter()
+            .filter(|j| {
+                !generations.contains(&j.kind.as_str())
+                    || (next.contains(&j.id) && !busy_chats.contains(&j.chat_id))
+            })
+            .collect()
+    }
+
+    // The order the jobs are claimed in. Users take turns, the one with the fewest jobs running or
+    // ahead in the queue goes next with their oldest job, so one user's burst doesn't hold up
+    // everyone else.
+    pub fn fair_order(
+        mut jobs: Vec<WaitingJob>,
+        running: &HashMap<String, usize>,
+    ) -> Vec<WaitingJob> {
+        jobs.sort_by_key(|j| j.run_at);
+
+        let mut taken = running.clone();
+        let mut ordered = Vec::with_capacity(jobs.len());
+        while let Some(i) = (0..jobs.len()).min_by_key(|&i| {
+            (
+                taken.get(&jobs[i].user_id).copied().unwrap_or(0),
+                jobs[i].run_at,
+            )
+        }) {
+            let job = jobs.remove(i);
+            *taken.entry(job.user_id.clone()).or_default() += 1;
+            ordered.push(job);
+        }
+        ordered
+    }
+}
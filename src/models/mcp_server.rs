@@ -0,0 +1,127 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+
+use crate::dtos;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, EnumString, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum McpTransport {
+    // a process on this server, spoken to over stdin and stdout
+    Stdio,
+    // streamable HTTP
+    Http,
+}
+
+#[derive(Debug, Queryable, Identifiable, Clone, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::mcp_servers)]
+pub struct McpServer {
+    pub id: u64,
+    pub user_id: String,
+    pub name: String,
+    pub transport: String,
+    pub url: Option<String>,
+    pub command: Option<String>,
+    // JSON encoded list of arguments for the command
+    pub args: Option<String>,
+    // JSON encoded list of tool names, null enables every tool the server has
+    pub enabled_tools: Option<String>,
+    pub version: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::mcp_servers)]
+pub struct NewMcpServer {
+    pub user_id: String,
+    pub name: String,
+    pub transport: String,
+    pub url: Option<String>,
+    pub command: Option<String>,
+    pub args: Option<String>,
+    pub enabled_tools: Option<String>,
+    pub version: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateArgs {
+    pub name: String,
+    pub transport: McpTransport,
+    pub url: Option<String>,
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl McpServer {
+    pub fn build_new(user_id: String, args: CreateArgs) -> NewMcpServer {
+        let command_args = match args.transport {
+            McpTransport::Stdio => serde_json::to_string(&args.args).ok(),
+            McpTransport::Http => None,
+        };
+
+        NewMcpServer {
+            user_id,
+            name: args.name,
+            transport: args.transport.to_string(),
+            url: args.url.filter(|_| args.transport == McpTransport::Http),
+            command: args
+                .command
+                .filter(|_| args.transport == McpTransport::Stdio),
+            args: command_args,
+            enabled_tools: None,
+            version: 1,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+        }
+    }
+
+    pub fn transport(&self) -> Option<McpTransport> {
+        self.transport.parse().ok()
+    }
+
+    pub fn args(&self) -> Vec<String> {
+        self.args
+            .as_deref()
+            .and_then(|args| serde_json::from_str(args).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn enabled_tools(&self) -> Option<Vec<String>> {
+        self.enabled_tools
+            .as_deref()
+            .and_then(|tools| serde_json::from_str(tools).ok())
+    }
+
+    pub fn is_enabled(&self, tool: &str) -> bool {
+        self.enabled_tools()
+            .is_none_or(|enabled| enabled.iter().any(|t| t == tool))
+    }
+
+    // Tokens for http servers are stored through the api key service under this provider name,
+    // the same way custom endpoint keys are.
+    pub fn key_provider(name: &str) -> String {
+        format!("mcp:{name}")
+    }
+}
+
+impl From<McpServer> for dtos::mcp_server::McpServer {
+    fn from(value: McpServer) -> Self {
+        Self {
+            transport: value.transport(),
+            args: value.args(),
+            enabled_tools: value.enabled_tools(),
+            id: value.id,
+            name: value.name,
+            url: value.url,
+            command: value.command,
+            created_at: value.created_at.and_utc(),
+            updated_at: value.updated_at.and_utc(),
+        }
+    }
+}
@@ -1,8 +1,11 @@
+use std::collections::HashMap;
+
 use chrono::{DateTime, NaiveDateTime, Utc};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use crate::{dtos, models::chat::Chat};
+use crate::{ai::reasoning::EffortLevel, dtos, models::chat::Chat};
 
 use super::replicache::ReplicachePullModel;
 
@@ -22,10 +25,23 @@ use super::replicache::ReplicachePullModel;
 pub struct Message {
     pub id: String,
     pub chat_id: String,
+    // The message this one follows. Messages sharing a parent are alternative branches.
+    pub parent_id: Option<String>,
     pub user_id: String,
     pub role: String,
     pub body: String,
     pub reasoning: Option<String>,
+    // JSON encoded MessageParts, see Message::parts
+    pub parts: Option<String>,
+    // JSON encoded Citations, see Message::citations
+    pub citations: Option<String>,
+    // Set on the replies of a compare, "<provider>:<model>" of the model that wrote it.
+    pub model: Option<String>,
+    // The user message a compare's replies answer, shared by all of them.
+    pub compare_group: Option<String>,
+    // The branch shown among the messages sharing a parent. The replies of a compare wait for one
+    // of them to be picked.
+    pub selected: bool,
     pub version: i32,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
@@ -36,21 +52,127 @@ pub struct Message {
 pub struct Changeset {
     pub body: Option<String>,
     pub reasoning: Option<String>,
+    pub selected: Option<bool>,
+    // Some(None) makes it a first message
+    pub parent_id: Option<Option<String>>,
     pub version: i32,
     pub updated_at: NaiveDateTime,
 }
 
+// Tool calls an assistant reply made on the way to its answer, in the order they happened.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MessagePart {
+    ToolCall {
+        id: String,
+        name: String,
+        arguments: Value,
+    },
+    ToolResult {
+        call_id: String,
+        name: String,
+        content: String,
+        is_error: bool,
+    },
+}
+
+// A source the reply was grounded in, shown alongside it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Citation {
+    pub url: String,
+    pub title: String,
+    pub snippet: String,
+}
+
+// What the assistant answers a user message with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageMode {
+    #[default]
+    Chat,
+    Image,
+}
+
+// A model asked to answer alongside others in a compare.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompareModel {
+    pub provider: String,
+    pub model: String,
+    pub reasoning: Option<EffortLevel>,
+}
+
+impl CompareModel {
+    // Tags the SSE events and the saved reply of this model.
+    pub fn tag(&self) -> String {
+        format!("{}:{}", self.provider, self.model)
+    }
+}
+
+// The message a reply answers, and for the replies of a compare the model that wrote it.
+#[derive(Debug, Clone)]
+pub struct ReplyTo {
+    pub parent_id: String,
+    pub compare_model: Option<String>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct CreateArgs {
     pub id: String,
     pub chat_id: String,
+    // Defaults to the last message of the branch being shown.
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    // Only set by the server, for a message that starts a new branch at the top of the chat
+    // rather than following the branch being shown.
+    #[serde(skip)]
+    pub root: bool,
     pub role: String,
     pub body: String,
     pub reasoning: Option<String>,
+    // Uploaded attachments sent along with the message.
+    #[serde(default)]
+    pub attachment_ids: Vec<String>,
+    #[serde(default)]
+    pub mode: MessageMode,
+    // Tool calls and citations are only set by the server when saving a reply, clients can't
+    // send them.
+    #[serde(skip)]
+    pub parts: Vec<MessagePart>,
+    #[serde(skip)]
+    pub citations: Vec<Citation>,
+    // Models to answer a user message side by side, empty for a normal reply.
+    #[serde(default)]
+    pub compare: Vec<CompareModel>,
+    // Only set by the server when saving the replies of a compare.
+    #[serde(skip)]
+    pub compare_model: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl CreateArgs {
+    // An edit of `original`, placed next to it.
+    pub fn resubmit(original: Message, args: &ResubmitArgs) -> Self {
+        Self {
+            id: args.new_id.clone(),
+            chat_id: original.chat_id,
+            root: original.parent_id.is_none(),
+            parent_id: original.parent_id,
+            role: original.role,
+            body: args.body.clone(),
+            reasoning: None,
+            attachment_ids: args.attachment_ids.clone(),
+            mode: MessageMode::Chat,
+            parts: Vec::new(),
+            citations: Vec::new(),
+            compare: Vec::new(),
+            compare_model: None,
+            created_at: args.updated_at,
+            updated_at: args.updated_at,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct UpdateArgs {
     pub id: String,
@@ -59,11 +181,130 @@ pub struct UpdateArgs {
     pub updated_at: DateTime<Utc>,
 }
 
+// An edit of a past user message, sent as a new branch next to it. The original and the replies
+// that followed it are kept on theirs.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResubmitArgs {
+    pub id: String,
+    // of the edited message
+    pub new_id: String,
+    pub body: String,
+    // Attachments live on a single message, so the original's stay with it.
+    #[serde(default)]
+    pub attachment_ids: Vec<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegenerateArgs {
+    // the assistant message to answer again
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SelectArgs {
+    pub id: String,
+    pub updated_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeleteArgs {
     pub id: String,
 }
 
+impl Message {
+    // The conversation as the user sees it: from the first message down, taking the selected one
+    // of each set of siblings, or the newest when none is. Expects messages oldest first.
+    pub fn active_path(messages: Vec<Message>) -> Vec<Message> {
+        let mut children: HashMap<Option<String>, Vec<Message>> = HashMap::new();
+        for message in messages {
+            children
+                .entry(message.parent_id.clone())
+                .or_default()
+                .push(message);
+        }
+
+        let mut path = Vec::new();
+        let mut parent = None;
+        while let Some(mut siblings) = children.remove(&parent) {
+            let i = siblings
+                .iter()
+                .position(|m| m.selected)
+                .unwrap_or(siblings.len() - 1);
+            let next = siblings.swap_remove(i);
+            parent = Some(next.id.clone());
+            path.push(next);
+        }
+        path
+    }
+
+    // Which message to select once a deleted message's replies have moved up next to its siblings,
+    // both oldest first. A sibling is preferred so the path keeps alternating roles: the one already
+    // selected, or the newest when the deleted message was the one shown. The replies only take its
+    // place when it had no siblings.
+    pub fn selected_after_delete<'a>(
+        siblings: &'a [Message],
+        replies: &'a [Message],
+    ) -> Option<&'a str> {
+        let group = if siblings.is_empty() {
+            replies
+        } else {
+            siblings
+        };
+        group
+            .iter()
+            .find(|m| m.selected)
+            .or(group.last())
+            .map(|m| m.id.as_str())
+    }
+
+    // The messages leading up to and including `leaf`, oldest first.
+    pub fn thread(messages: Vec<Message>, leaf: &str) -> Vec<Message> {
+        let mut by_id: HashMap<String, Message> =
+            messages.into_iter().map(|m| (m.id.clone(), m)).collect();
+
+        let mut thread = Vec::new();
+        let mut next = Some(leaf.to_owned());
+        while let Some(message) = next.and_then(|id| by_id.remove(&id)) {
+            next = message.parent_id.clone();
+            thread.push(message);
+        }
+        thread.reverse();
+        thread
+    }
+
+    pub fn parts(&self) -> Vec<MessagePart> {
+        self.parts
+            .as_deref()
+            .and_then(|p| serde_json::from_str(p).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn encode_parts(parts: &[MessagePart]) -> Option<String> {
+        if parts.is_empty() {
+            return None;
+        }
+        serde_json::to_string(parts).ok()
+    }
+
+    pub fn citations(&self) -> Vec<Citation> {
+        decode_citations(self.citations.as_deref())
+    }
+
+    pub fn encode_citations(citations: &[Citation]) -> Option<String> {
+        if citations.is_empty() {
+            return None;
+        }
+        serde_json::to_string(citations).ok()
+    }
+}
+
+pub fn decode_citations(encoded: Option<&str>) -> Vec<Citation> {
+    encoded
+        .and_then(|c| serde_json::from_str(c).ok())
+        .unwrap_or_default()
+}
+
 impl ReplicachePullModel for Message {
     fn resource_prefix() -> &'static str {
         "message"
@@ -80,14 +321,127 @@ impl ReplicachePullModel for Message {
 
 impl From<Message> for dtos::message::Message {
     fn from(value: Message) -> Self {
+        let parts = value.parts();
+        let citations = value.citations();
         dtos::message::Message {
+            parts,
+            citations,
             id: value.id,
             chat_id: value.chat_id,
+            parent_id: value.parent_id,
             role: value.role,
             body: value.body,
             reasoning: value.reasoning,
+            model: value.model,
+            compare_group: value.compare_group,
+            selected: value.selected,
             created_at: value.created_at.and_utc(),
             updated_at: value.updated_at.and_utc(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, parent_id: Option<&str>, role: &str) -> Message {
+        let now = Utc::now().naive_utc();
+        Message {
+            id: id.to_owned(),
+            chat_id: "chat".to_owned(),
+            parent_id: parent_id.map(str::to_owned),
+            user_id: "user".to_owned(),
+            role: role.to_owned(),
+            body: id.to_owned(),
+            reasoning: None,
+            parts: None,
+            citations: None,
+            model: None,
+            compare_group: None,
+            selected: true,
+            version: 1,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn edit(id: &str) -> ResubmitArgs {
+        ResubmitArgs {
+            id: id.to_owned(),
+            new_id: format!("{id}-edit"),
+            body: "edited".to_owned(),
+            attachment_ids: Vec::new(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn editing_the_first_message_starts_a_new_root() {
+        let first = message("m1", None, "user");
+        let args = CreateArgs::resubmit(first, &edit("m1"));
+
+        assert!(args.root);
+        assert_eq!(args.parent_id, None);
+    }
+
+    #[test]
+    fn editing_a_later_message_stays_next_to_it() {
+        let second = message("m2", Some("r1"), "user");
+        let args = CreateArgs::resubmit(second, &edit("m2"));
+
+        assert!(!args.root);
+        assert_eq!(args.parent_id.as_deref(), Some("r1"));
+    }
+
+    #[test]
+    fn a_new_root_is_the_path_shown_once_selected() {
+        let mut original = message("m1", None, "user");
+        original.selected = false;
+        let reply = message("r1", Some("m1"), "assistant");
+        let edited = message("m1-edit", None, "user");
+
+        let path = Message::active_path(vec![original, reply, edited]);
+        let ids: Vec<&str> = path.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["m1-edit"]);
+    }
+
+    #[test]
+    fn deleting_the_shown_message_shows_its_newest_sibling() {
+        let mut older = message("m1", Some("r0"), "user");
+        older.selected = false;
+        let mut newer = message("m2", Some("r0"), "user");
+        newer.selected = false;
+        let replies = [message("r1", Some("r0"), "assistant")];
+
+        let siblings = [older, newer];
+        assert_eq!(
+            Message::selected_after_delete(&siblings, &replies),
+            Some("m2")
+        );
+    }
+
+    #[test]
+    fn deleting_a_hidden_message_keeps_the_selected_sibling() {
+        let shown = message("m1", Some("r0"), "user");
+        let replies = [message("r2", Some("r0"), "assistant")];
+
+        let siblings = [shown];
+        assert_eq!(
+            Message::selected_after_delete(&siblings, &replies),
+            Some("m1")
+        );
+    }
+
+    #[test]
+    fn an_only_child_is_replaced_by_its_selected_reply() {
+        let mut first = message("r1", Some("m1"), "assistant");
+        first.selected = false;
+        let second = message("r2", Some("m1"), "assistant");
+        let mut third = message("r3", Some("m1"), "assistant");
+        third.selected = false;
+
+        let replies = [first, second, third];
+        assert_eq!(Message::selected_after_delete(&[], &replies), Some("r2"));
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum::Display;
+
+use crate::{
+    ai::{
+        pricing::{FALLBACK_PRICE, ModelPrice, micros_to_usd},
+        provider::AiProvider,
+        usage::TokenUsage,
+    },
+    dtos,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum UsageKind {
+    Reply,
+    Title,
+    Image,
+    Summary,
+}
+
+#[derive(Debug, Queryable, Identifiable, Clone, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::message_usage)]
+pub struct MessageUsage {
+    pub id: u64,
+    pub user_id: String,
+    pub chat_id: String,
+    pub message_id: Option<String>,
+    pub kind: String,
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub reasoning_tokens: u32,
+    pub cost_micros: Option<u64>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::message_usage)]
+pub struct NewMessageUsage {
+    pub user_id: String,
+    pub chat_id: String,
+    pub message_id: Option<String>,
+    pub kind: String,
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub reasoning_tokens: u32,
+    pub cost_micros: Option<u64>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateArgs {
+    pub chat_id: String,
+    pub message_id: Option<String>,
+    pub kind: UsageKind,
+    pub provider: String,
+    pub model: String,
+    pub usage: TokenUsage,
+    pub price: Option<ModelPrice>,
+}
+
+impl MessageUsage {
+    pub fn build_new(user_id: String, args: CreateArgs) -> NewMessageUsage {
+        NewMessageUsage {
+            user_id,
+            chat_id: args.chat_id,
+            message_id: args.message_id,
+            kind: args.kind.to_string(),
+            provider: args.provider,
+            model: args.model,
+            input_tokens: args.usage.input_tokens,
+            output_tokens: args.usage.output_tokens,
+            reasoning_tokens: args.usage.reasoning_tokens,
+            cost_micros: args.price.map(|p| p.cost_micros(&args.usage)),
+            created_at: Utc::now().naive_utc(),
+        }
+    }
+
+    // Unpriced rows stay NULL so reports can tell them apart, the budget counts them at the
+    // fallback price. Self hosted models are free and left out of it altogether.
+    pub fn budget_cost_micros(&self) -> u64 {
+        if self.provider == AiProvider::Custom.to_string() {
+            return 0;
+        }
+        self.cost_micros.unwrap_or_else(|| {
+            FALLBACK_PRICE.cost_micros(&TokenUsage {
+                input_tokens: self.input_tokens,
+                output_tokens: self.output_tokens,
+                reasoning_tokens: self.reasoning_tokens,
+            })
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub reasoning_tokens: u64,
+    pub cost_micros: u64,
+    // requests without a known price, their cost is not part of cost_micros
+    pub unpriced_requests: u64,
+}
+
+impl UsageTotals {
+    pub fn add(&mut self, row: &MessageUsage) {
+        self.requests += 1;
+        self.input_tokens += u64::from(row.input_tokens);
+        self.output_tokens += u64::from(row.output_tokens);
+        self.reasoning_tokens += u64::from(row.reasoning_tokens);
+        match row.cost_micros {
+            Some(cost) => self.cost_micros += cost,
+            None => self.unpriced_requests += 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UsageSummary {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub by_model: BTreeMap<(String, String), UsageTotals>,
+    pub by_day: BTreeMap<(NaiveDate, String, String), UsageTotals>,
+}
+
+impl UsageSummary {
+    pub fn build(from: NaiveDate, to: NaiveDate, rows: &[MessageUsage]) -> Self {
+        let mut by_model: BTreeMap<(String, String), UsageTotals> = BTreeMap::new();
+        let mut by_day: BTreeMap<(NaiveDate, String, String), UsageTotals> = BTreeMap::new();
+
+        for row in rows {
+            by_model
+                .entry((row.provider.clone(), row.model.clone()))
+                .or_default()
+                .add(row);
+            by_day
+                .entry((
+                    row.created_at.date(),
+                    row.provider.clone(),
+                    row.model.clone(),
+                ))
+                .or_default()
+                .add(row);
+        }
+
+        Self {
+            from,
+            to,
+            by_model,
+            by_day,
+        }
+    }
+}
+
+impl From<UsageTotals> for dtos::usage::UsageTotals {
+    fn from(value: UsageTotals) -> Self {
+        Self {
+            requests: value.requests,
+            input_tokens: value.input_tokens,
+            output_tokens: value.output_tokens,
+            reasoning_tokens: value.reasoning_tokens,
+            cost_usd: micros_to_usd(value.cost_micros),
+            unpriced_requests: value.unpriced_requests,
+        }
+    }
+}
+
+impl From<UsageSummary> for dtos::usage::UsageSummary {
+    fn from(value: UsageSummary) -> Self {
+        Self {
+            from: value.from,
+            to: value.to,
+            models: value
+                .by_model
+                .into_iter()
+                .map(|((provider, model), totals)| dtos::usage::ModelUsage {
+                    provider,
+                    model,
+                    totals: totals.into(),
+                })
+                .collect(),
+            days: value
+                .by_day
+                .into_iter()
+                .map(|((day, provider, model), totals)| dtos::usage::DailyUsage {
+                    day,
+                    provider,
+                    model,
+                    totals: totals.into(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage_row(provider: &str, cost_micros: Option<u64>) -> MessageUsage {
+        MessageUsage {
+            id: 1,
+            user_id: "user".to_owned(),
+            chat_id: "chat".to_owned(),
+            message_id: None,
+            kind: UsageKind::Reply.to_string(),
+            provider: provider.to_owned(),
+            model: "model".to_owned(),
+            input_tokens: 1_000,
+            output_tokens: 1_000,
+            reasoning_tokens: 0,
+            cost_micros,
+            created_at: Utc::now().naive_utc(),
+        }
+    }
+
+    #[test]
+    fn priced_rows_count_their_cost() {
+        assert_eq!(usage_row("openai", Some(42)).budget_cost_micros(), 42);
+    }
+
+    #[test]
+    fn unpriced_vendor_rows_count_at_the_fallback_price() {
+        assert_eq!(usage_row("openrouter", None).budget_cost_micros(), 18_000);
+    }
+
+    #[test]
+    fn self_hosted_rows_are_free() {
+        assert_eq!(usage_row("custom", None).budget_cost_micros(), 0);
+    }
+}
@@ -1,11 +1,18 @@
 pub mod active_model;
 pub mod api_key;
+pub mod attachment;
 pub mod chat;
+pub mod custom_endpoint;
+pub mod job;
+pub mod mcp_server;
 pub mod message;
+pub mod message_usage;
 pub mod replicache;
 pub mod replicache_client;
 pub mod replicache_client_group;
 pub mod session;
 pub mod shared_chat;
 pub mod shared_message;
+pub mod system_prompt;
 pub mod user;
+pub mod user_budget;
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::dtos;
 
-use super::shared_message::SharedMessage;
+use super::{attachment::Attachment, shared_message::SharedMessage};
 
 #[derive(Queryable, Identifiable, Insertable, Serialize, Deserialize, Clone)]
 #[diesel(table_name = crate::schema::shared_chats)]
@@ -42,6 +42,7 @@ pub struct SharedChatWithMessages {
     pub title: Option<String>,
     pub created_at: NaiveDateTime,
     pub messages: Vec<SharedMessage>,
+    pub attachments: Vec<Attachment>,
 }
 
 impl From<SharedChat> for dtos::shared_chat::SharedChat {
@@ -63,7 +64,24 @@ impl From<SharedChatWithMessages> for dtos::shared_chat::SharedChatWithMessages
             messages: src
                 .messages
                 .into_iter()
-                .map(dtos::shared_chat::SharedMessage::from)
+                .map(|m| {
+                    let attachments = src
+                        .attachments
+                        .iter()
+                        .filter(|a| a.message_id.is_some() && a.message_id == m.source_message_id)
+                        .map(|a| dtos::shared_chat::SharedAttachment {
+                            id: a.id.clone(),
+                            filename: a.filename.clone(),
+                            content_type: a.content_type.clone(),
+                            size_bytes: a.size_bytes,
+                        })
+                        .collect();
+
+                    dtos::shared_chat::SharedMessage {
+                        attachments,
+                        ..m.into()
+                    }
+                })
                 .collect(),
         }
     }
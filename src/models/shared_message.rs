@@ -3,7 +3,7 @@ use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::dtos;
-use crate::models::shared_chat::SharedChat;
+use crate::models::{message::decode_citations, shared_chat::SharedChat};
 
 #[derive(
     Debug, Queryable, Identifiable, Associations, Insertable, Serialize, Deserialize, Clone,
@@ -13,9 +13,13 @@ use crate::models::shared_chat::SharedChat;
 pub struct SharedMessage {
     pub id: String,
     pub shared_chat_id: String,
+    // The message this was copied from, its attachments are served with the snapshot.
+    pub source_message_id: Option<String>,
     pub role: String,
     pub body: String,
     pub reasoning: Option<String>,
+    // copied from the message, see Message::citations
+    pub citations: Option<String>,
     pub created_at: NaiveDateTime,
 }
 
@@ -35,7 +39,9 @@ impl From<super::shared_message::SharedMessage> for dtos::shared_chat::SharedMes
             id: src.id,
             role: src.role,
             body: src.body,
+            citations: decode_citations(src.citations.as_deref()),
             reasoning: src.reasoning,
+            attachments: Vec::new(),
             created_at: src.created_at.and_utc(),
         }
     }
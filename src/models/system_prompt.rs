@@ -0,0 +1,71 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::dtos;
+
+use super::replicache::ReplicachePullModel;
+
+#[derive(Queryable, Identifiable, Insertable, Serialize, Deserialize, Clone)]
+#[diesel(table_name = crate::schema::system_prompts)]
+pub struct SystemPrompt {
+    pub id: String,
+    pub user_id: String,
+    pub body: String,
+    pub version: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = crate::schema::system_prompts)]
+pub struct Changeset {
+    pub body: String,
+    pub version: i32,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateArgs {
+    pub id: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateArgs {
+    pub id: String,
+    pub body: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteArgs {
+    pub id: String,
+}
+
+impl ReplicachePullModel for SystemPrompt {
+    fn resource_prefix() -> &'static str {
+        "systemPrompt"
+    }
+
+    fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    fn get_version(&self) -> i32 {
+        self.version
+    }
+}
+
+impl From<SystemPrompt> for dtos::system_prompt::SystemPrompt {
+    fn from(value: SystemPrompt) -> Self {
+        dtos::system_prompt::SystemPrompt {
+            id: value.id,
+            body: value.body,
+            created_at: value.created_at.and_utc(),
+            updated_at: value.updated_at.and_utc(),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{ai::pricing::micros_to_usd, dtos};
+
+#[derive(Debug, Queryable, Identifiable, Clone, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::user_budgets, primary_key(user_id))]
+pub struct UserBudget {
+    pub user_id: String,
+    pub monthly_limit_micros: u64,
+    pub version: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::user_budgets)]
+pub struct NewUserBudget {
+    pub user_id: String,
+    pub monthly_limit_micros: u64,
+    pub version: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl UserBudget {
+    pub fn build_new(user_id: String, monthly_limit_micros: u64) -> NewUserBudget {
+        NewUserBudget {
+            user_id,
+            monthly_limit_micros,
+            version: 1,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BudgetStatus {
+    pub monthly_limit_micros: Option<u64>,
+    pub spent_micros: u64,
+    pub period_start: NaiveDate,
+}
+
+impl BudgetStatus {
+    pub fn exhausted(&self) -> bool {
+        self.monthly_limit_micros
+            .is_some_and(|limit| self.spent_micros >= limit)
+    }
+}
+
+impl From<BudgetStatus> for dtos::budget::Budget {
+    fn from(value: BudgetStatus) -> Self {
+        Self {
+            monthly_limit_usd: value.monthly_limit_micros.map(micros_to_usd),
+            spent_usd: micros_to_usd(value.spent_micros),
+            period_start: value.period_start,
+            exhausted: value.exhausted(),
+        }
+    }
+}
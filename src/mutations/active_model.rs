@@ -21,6 +21,18 @@ pub enum ActiveModelMutation {
 impl ActiveModelMutation {}
 
 impl Mutation for ActiveModelMutation {
+    fn prepare(&self, state: &AppState, conn: &mut MysqlConnection, user_id: &str) -> Result<()> {
+        let (provider, model) = match self {
+            ActiveModelMutation::Create(args) => (&args.provider, &args.model),
+            ActiveModelMutation::Update(args) => (&args.provider, &args.model),
+            ActiveModelMutation::Delete(_) => return Ok(()),
+        };
+        state
+            .service_container
+            .model_catalog_service
+            .ensure_listed(state, conn, user_id, provider, model)
+    }
+
     fn process(
         &self,
         state: AppState,
@@ -3,7 +3,9 @@ use diesel::prelude::*;
 use serde::Deserialize;
 
 use crate::app::AppState;
-use crate::models::chat::{CreateArgs, DeleteArgs, ForkArgs, UpdateArgs};
+use crate::models::chat::{
+    CreateArgs, DeleteArgs, ForkArgs, UpdateArgs, UpdateModelArgs, UpdateSystemPromptArgs,
+};
 
 use super::handler::Mutation;
 
@@ -18,11 +20,29 @@ pub enum ChatMutation {
     Delete(DeleteArgs),
     #[serde(rename = "forkChat")]
     Fork(ForkArgs),
+    #[serde(rename = "updateChatSystemPrompt")]
+    UpdateSystemPrompt(UpdateSystemPromptArgs),
+    #[serde(rename = "updateChatModel")]
+    UpdateModel(UpdateModelArgs),
 }
 
 impl ChatMutation {}
 
 impl Mutation for ChatMutation {
+    fn prepare(&self, state: &AppState, conn: &mut MysqlConnection, user_id: &str) -> Result<()> {
+        let ChatMutation::UpdateModel(args) = self else {
+            return Ok(());
+        };
+        let model = args.model.as_deref().filter(|m| !m.trim().is_empty());
+        if let (Some(provider), Some(model)) = (&args.provider, model) {
+            state
+                .service_container
+                .model_catalog_service
+                .ensure_listed(state, conn, user_id, provider, model)?;
+        }
+        Ok(())
+    }
+
     fn process(
         &self,
         state: AppState,
@@ -60,6 +80,22 @@ impl Mutation for ChatMutation {
                     .fork(conn, args, user_id)?;
                 Ok(Some(chat.id))
             }
+            ChatMutation::UpdateSystemPrompt(args) => {
+                let chat = state.service_container.chat_service.update_system_prompt(
+                    conn,
+                    args.clone(),
+                    user_id,
+                )?;
+                Ok(Some(chat.id))
+            }
+            ChatMutation::UpdateModel(args) => {
+                let chat = state.service_container.chat_service.update_model(
+                    conn,
+                    args.clone(),
+                    user_id,
+                )?;
+                Ok(Some(chat.id))
+            }
         }
     }
 }
@@ -0,0 +1,54 @@
+use anyhow::{Result, bail};
+use diesel::prelude::*;
+use serde::Deserialize;
+
+use crate::app::AppState;
+
+use super::handler::Mutation;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CancelArgs {
+    pub chat_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "name", content = "args")]
+pub enum GenerationMutation {
+    #[serde(rename = "cancelGeneration")]
+    Cancel(CancelArgs),
+}
+
+impl Mutation for GenerationMutation {
+    fn process(
+        &self,
+        state: AppState,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+    ) -> Result<Option<String>> {
+        match self {
+            GenerationMutation::Cancel(args) => {
+                let chat =
+                    state
+                        .service_container
+                        .chat_service
+                        .get(conn, &args.chat_id, user_id)?;
+
+                if !state.generation_registry.cancel(&chat.id, user_id) {
+                    // It may be running in another process.
+                    let running = state
+                        .service_container
+                        .job_queue_service
+                        .is_generating(conn, &chat.id)?;
+                    if !running {
+                        bail!("No running generation for chat {}", chat.id);
+                    }
+                    state
+                        .generation_registry
+                        .cancel_elsewhere(&chat.id, user_id);
+                }
+
+                Ok(Some(chat.id))
+            }
+        }
+    }
+}
@@ -5,7 +5,10 @@ use serde_json::json;
 
 use crate::app::AppState;
 
-use super::{active_model::ActiveModelMutation, chat::ChatMutation, message::MessageMutation};
+use super::{
+    active_model::ActiveModelMutation, chat::ChatMutation, generation::GenerationMutation,
+    message::MessageMutation, system_prompt::SystemPromptMutation,
+};
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -19,6 +22,17 @@ pub struct RawMutation {
 }
 
 pub trait Mutation {
+    // Checks that go over the network, run before the mutation's transaction so it isn't held
+    // open while they wait. A failure skips the mutation like one failing in process.
+    fn prepare(
+        &self,
+        _state: &AppState,
+        _conn: &mut MysqlConnection,
+        _user_id: &str,
+    ) -> Result<()> {
+        Ok(())
+    }
+
     fn process(
         &self,
         state: AppState,
@@ -29,14 +43,20 @@ pub trait Mutation {
 
 pub fn parse_mutation(raw: RawMutation) -> Result<Box<dyn Mutation>, serde_json::Error> {
     match raw.name.as_str() {
-        "createChat" | "updateChat" | "deleteChat" | "forkChat" => {
+        "createChat"
+        | "updateChat"
+        | "deleteChat"
+        | "forkChat"
+        | "updateChatSystemPrompt"
+        | "updateChatModel" => {
             let chat_mutation: ChatMutation = serde_json::from_value(json!({
                 "name": raw.name,
                 "args": raw.args
             }))?;
             Ok(Box::new(chat_mutation))
         }
-        "createMessage" | "updateMessage" | "deleteMessage" => {
+        "createMessage" | "updateMessage" | "deleteMessage" | "selectMessage"
+        | "resubmitMessage" | "regenerateMessage" => {
             let msg_mutation: MessageMutation = serde_json::from_value(json!({
                 "name": raw.name,
                 "args": raw.args
@@ -50,6 +70,20 @@ pub fn parse_mutation(raw: RawMutation) -> Result<Box<dyn Mutation>, serde_json:
             }))?;
             Ok(Box::new(active_model_mutation))
         }
+        "createSystemPrompt" | "updateSystemPrompt" | "deleteSystemPrompt" => {
+            let system_prompt_mutation: SystemPromptMutation = serde_json::from_value(json!({
+                "name": raw.name,
+                "args": raw.args
+            }))?;
+            Ok(Box::new(system_prompt_mutation))
+        }
+        "cancelGeneration" => {
+            let generation_mutation: GenerationMutation = serde_json::from_value(json!({
+                "name": raw.name,
+                "args": raw.args
+            }))?;
+            Ok(Box::new(generation_mutation))
+        }
         _ => Err(serde_json::Error::custom(format!(
             "Unknown mutation type: {}",
             raw.name
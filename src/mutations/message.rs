@@ -4,7 +4,9 @@ use serde::Deserialize;
 
 use crate::ai;
 use crate::app::AppState;
-use crate::models::message::{CreateArgs, DeleteArgs, UpdateArgs};
+use crate::models::message::{
+    CreateArgs, DeleteArgs, MessageMode, RegenerateArgs, ResubmitArgs, SelectArgs, UpdateArgs,
+};
 
 use super::handler::Mutation;
 
@@ -12,14 +14,33 @@ use super::handler::Mutation;
 #[serde(tag = "name", content = "args")]
 pub enum MessageMutation {
     #[serde(rename = "createMessage")]
-    Create(CreateArgs),
+    Create(Box<CreateArgs>),
     #[serde(rename = "updateMessage")]
     Update(UpdateArgs),
     #[serde(rename = "deleteMessage")]
     Delete(DeleteArgs),
+    #[serde(rename = "selectMessage")]
+    Select(SelectArgs),
+    #[serde(rename = "resubmitMessage")]
+    Resubmit(ResubmitArgs),
+    #[serde(rename = "regenerateMessage")]
+    Regenerate(RegenerateArgs),
 }
 
 impl Mutation for MessageMutation {
+    fn prepare(&self, state: &AppState, conn: &mut MysqlConnection, user_id: &str) -> Result<()> {
+        let MessageMutation::Create(args) = self else {
+            return Ok(());
+        };
+        for choice in &args.compare {
+            state
+                .service_container
+                .model_catalog_service
+                .ensure_listed(state, conn, user_id, &choice.provider, &choice.model)?;
+        }
+        Ok(())
+    }
+
     fn process(
         &self,
         state: AppState,
@@ -32,23 +53,42 @@ impl Mutation for MessageMutation {
                     state
                         .service_container
                         .message_service
-                        .create(conn, args.clone(), user_id)?;
+                        .create(conn, *args.clone(), user_id)?;
+
+                let attachment_service = &state.service_container.attachment_service;
+                if !args.attachment_ids.is_empty() {
+                    attachment_service.attach(conn, &args.attachment_ids, &msg.id, user_id)?;
+                }
 
                 if args.role == "user" {
-                    let messages = state.service_container.message_service.list_for_chat(
-                        conn,
-                        &args.chat_id,
-                        user_id,
-                    )?;
                     ai::handler::enqueue_ai_jobs(
                         &state,
-                        user_id.to_string(),
-                        args.chat_id.clone(),
-                        args.body.clone(),
-                        messages,
+                        conn,
+                        &msg,
+                        args.mode,
+                        args.compare.clone(),
+                    )?;
+                }
+
+                Ok(Some(msg.id))
+            }
+            MessageMutation::Resubmit(args) => {
+                let msg = state
+                    .service_container
+                    .message_service
+                    .resubmit(conn, args, user_id)?;
+
+                if !args.attachment_ids.is_empty() {
+                    state.service_container.attachment_service.attach(
+                        conn,
+                        &args.attachment_ids,
+                        &msg.id,
+                        user_id,
                     )?;
                 }
 
+                ai::handler::enqueue_ai_jobs(&state, conn, &msg, MessageMode::Chat, Vec::new())?;
+
                 Ok(Some(msg.id))
             }
             MessageMutation::Update(args) => {
@@ -66,6 +106,22 @@ impl Mutation for MessageMutation {
                     .delete(conn, &args.id, user_id)?;
                 Ok(Some(msg.id))
             }
+            MessageMutation::Select(args) => {
+                let msg = state
+                    .service_container
+                    .message_service
+                    .select(conn, args, user_id)?;
+                Ok(Some(msg.id))
+            }
+            MessageMutation::Regenerate(args) => {
+                let parent = state
+                    .service_container
+                    .message_service
+                    .regenerate_target(conn, &args.id, user_id)?;
+                ai::handler::enqueue_regenerate(&state, conn, &parent)?;
+
+                Ok(Some(args.id.clone()))
+            }
         }
     }
 }
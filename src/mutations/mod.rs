@@ -1,4 +1,6 @@
 pub mod active_model;
 pub mod chat;
+pub mod generation;
 pub mod handler;
 pub mod message;
+pub mod system_prompt;
@@ -0,0 +1,54 @@
+use anyhow::Result;
+use diesel::prelude::*;
+use serde::Deserialize;
+
+use crate::app::AppState;
+use crate::models::system_prompt::{CreateArgs, DeleteArgs, UpdateArgs};
+
+use super::handler::Mutation;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "name", content = "args")]
+pub enum SystemPromptMutation {
+    #[serde(rename = "createSystemPrompt")]
+    Create(CreateArgs),
+    #[serde(rename = "updateSystemPrompt")]
+    Update(UpdateArgs),
+    #[serde(rename = "deleteSystemPrompt")]
+    Delete(DeleteArgs),
+}
+
+impl Mutation for SystemPromptMutation {
+    fn process(
+        &self,
+        state: AppState,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+    ) -> Result<Option<String>> {
+        match self {
+            SystemPromptMutation::Create(args) => {
+                let sp = state.service_container.system_prompt_service.create(
+                    conn,
+                    args.clone(),
+                    user_id,
+                )?;
+                Ok(Some(sp.id))
+            }
+            SystemPromptMutation::Update(args) => {
+                let sp = state.service_container.system_prompt_service.update(
+                    conn,
+                    args.clone(),
+                    user_id,
+                )?;
+                Ok(Some(sp.id))
+            }
+            SystemPromptMutation::Delete(args) => {
+                let sp = state
+                    .service_container
+                    .system_prompt_service
+                    .delete(conn, &args.id, user_id)?;
+                Ok(Some(sp.id))
+            }
+        }
+    }
+}
@@ -43,4 +43,17 @@ impl ApiKeyRepository {
         )
         .execute(conn)?)
     }
+
+    pub fn delete_for_provider(
+        conn: &mut MysqlConnection,
+        user_id: &str,
+        provider: &str,
+    ) -> Result<usize> {
+        Ok(diesel::delete(
+            api_keys::table
+                .filter(api_keys::user_id.eq(user_id))
+                .filter(api_keys::provider.eq(provider)),
+        )
+        .execute(conn)?)
+    }
 }
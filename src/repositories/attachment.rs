@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use diesel::prelude::*;
+
+use crate::models::attachment::{Attachment, Changeset};
+
+use super::Repository;
+
+#[derive(Debug, Clone)]
+pub struct AttachmentRepository;
+
+impl Repository<Attachment, Changeset> for AttachmentRepository {
+    fn find_by_id(&self, conn: &mut MysqlConnection, id: &str) -> Result<Option<Attachment>> {
+        use crate::schema::attachments::dsl::attachments;
+
+        match attachments.find(id).first::<Attachment>(conn) {
+            Ok(a) => Ok(Some(a)),
+            Err(diesel::result::Error::NotFound) => Ok(None),
+            Err(e) => Err(e).context(format!("Error finding attachment with id {}", id)),
+        }
+    }
+
+    fn find_by_ids(&self, conn: &mut MysqlConnection, ids: &[&str]) -> Result<Vec<Attachment>> {
+        use crate::schema::attachments::dsl::{attachments, id};
+
+        attachments
+            .filter(id.eq_any(ids))
+            .load(conn)
+            .context("Failed to find attachments by IDs")
+    }
+
+    fn find_by_id_for_update(
+        &self,
+        conn: &mut MysqlConnection,
+        id: &str,
+    ) -> Result<Option<Attachment>> {
+        use crate::schema::attachments::dsl::attachments;
+
+        match attachments.find(id).for_update().first::<Attachment>(conn) {
+            Ok(a) => Ok(Some(a)),
+            Err(diesel::result::Error::NotFound) => Ok(None),
+            Err(e) => Err(e).context(format!("Error finding attachment {} for update", id)),
+        }
+    }
+
+    fn find_by_user(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id_param: &str,
+    ) -> Result<Vec<Attachment>> {
+        use crate::schema::attachments::dsl::{attachments, user_id};
+
+        attachments
+            .filter(user_id.eq(user_id_param))
+            .load(conn)
+            .context(format!(
+                "Error finding attachments for user {}",
+                user_id_param
+            ))
+    }
+
+    fn create(&self, conn: &mut MysqlConnection, entity: &Attachment) -> Result<Attachment> {
+        use crate::schema::attachments::dsl::attachments;
+
+        diesel::insert_into(attachments)
+            .values(entity)
+            .execute(conn)
+            .context(format!("Error creating attachment {}", entity.id))?;
+
+        Ok(entity.clone())
+    }
+
+    fn update(
+        &self,
+        conn: &mut MysqlConnection,
+        id: &str,
+        changeset: Changeset,
+    ) -> Result<Attachment> {
+        use crate::schema::attachments::dsl::attachments;
+
+        diesel::update(attachments.find(id))
+            .set(changeset)
+            .execute(conn)
+            .context(format!("Error updating attachment {}", id))?;
+
+        self.find_by_id(conn, id)?
+            .context(format!("Attachment {} not found after update", id))
+    }
+
+    fn delete(&self, conn: &mut MysqlConnection, id: &str) -> Result<()> {
+        use crate::schema::attachments::dsl::attachments;
+
+        diesel::delete(attachments.find(id))
+            .execute(conn)
+            .context(format!("Error deleting attachment {}", id))?;
+
+        Ok(())
+    }
+}
+
+impl AttachmentRepository {
+    pub fn find_by_message_ids(
+        &self,
+        conn: &mut MysqlConnection,
+        message_ids: &[&str],
+    ) -> Result<Vec<Attachment>> {
+        use crate::schema::attachments::dsl::{attachments, created_at, message_id};
+
+        attachments
+            .filter(message_id.eq_any(message_ids))
+            .order_by(created_at.asc())
+            .load(conn)
+            .context("Failed to find attachments by message IDs")
+    }
+}
@@ -3,7 +3,7 @@ use diesel::BelongingToDsl;
 use diesel::prelude::*;
 
 use crate::models::{
-    chat::{Changeset, Chat, ChatWithMessages},
+    chat::{Changeset, Chat, ChatWithMessages, ModelChangeset},
     message::Message,
 };
 
@@ -111,9 +111,55 @@ impl ChatRepository {
             pinned_at: chat.pinned_at,
             created_at: chat.created_at,
             updated_at: chat.updated_at,
+            system_prompt: chat.system_prompt,
+            summary: chat.summary,
+            summary_message_id: chat.summary_message_id,
+            provider: chat.provider,
+            model: chat.model,
+            reasoning: chat.reasoning,
+            temperature: chat.temperature,
+            top_p: chat.top_p,
             messages,
         };
 
         Ok(Some(result))
     }
+
+    pub fn update_model(
+        &self,
+        conn: &mut MysqlConnection,
+        id: &str,
+        changeset: ModelChangeset,
+    ) -> Result<Chat> {
+        use crate::schema::chats::dsl::chats;
+
+        diesel::update(chats.find(id))
+            .set(changeset)
+            .execute(conn)
+            .context(format!("Error updating model of chat {}", id))?;
+
+        self.find_by_id(conn, id)?
+            .context(format!("Chat {} not found after update", id))
+    }
+
+    // Not part of what clients sync, so the version is left alone.
+    pub fn set_summary(
+        &self,
+        conn: &mut MysqlConnection,
+        chat_id: &str,
+        summary: &str,
+        message_id: &str,
+    ) -> Result<()> {
+        use crate::schema::chats::dsl;
+
+        diesel::update(dsl::chats.find(chat_id))
+            .set((
+                dsl::summary.eq(summary),
+                dsl::summary_message_id.eq(message_id),
+            ))
+            .execute(conn)
+            .context(format!("Error saving summary for chat {}", chat_id))?;
+
+        Ok(())
+    }
 }
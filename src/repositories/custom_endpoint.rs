@@ -0,0 +1,54 @@
+use anyhow::Result;
+use diesel::{RunQueryDsl, prelude::*};
+
+use crate::models::custom_endpoint::{CustomEndpoint, NewCustomEndpoint};
+use crate::schema::custom_endpoints;
+
+pub struct CustomEndpointRepository;
+
+impl CustomEndpointRepository {
+    pub fn create(conn: &mut MysqlConnection, new: &NewCustomEndpoint) -> Result<CustomEndpoint> {
+        diesel::insert_into(custom_endpoints::table)
+            .values(new)
+            .execute(conn)?;
+
+        let inserted = custom_endpoints::table
+            .order(custom_endpoints::id.desc())
+            .first::<CustomEndpoint>(conn)?;
+        Ok(inserted)
+    }
+
+    pub fn list_for_user(conn: &mut MysqlConnection, user_id: &str) -> Result<Vec<CustomEndpoint>> {
+        Ok(custom_endpoints::table
+            .filter(custom_endpoints::user_id.eq(user_id))
+            .order(custom_endpoints::name.asc())
+            .load::<CustomEndpoint>(conn)?)
+    }
+
+    pub fn get(conn: &mut MysqlConnection, id: u64, user_id: &str) -> Result<CustomEndpoint> {
+        Ok(custom_endpoints::table
+            .filter(custom_endpoints::id.eq(id))
+            .filter(custom_endpoints::user_id.eq(user_id))
+            .first::<CustomEndpoint>(conn)?)
+    }
+
+    pub fn get_by_name(
+        conn: &mut MysqlConnection,
+        user_id: &str,
+        name: &str,
+    ) -> Result<CustomEndpoint> {
+        Ok(custom_endpoints::table
+            .filter(custom_endpoints::user_id.eq(user_id))
+            .filter(custom_endpoints::name.eq(name))
+            .first::<CustomEndpoint>(conn)?)
+    }
+
+    pub fn delete(conn: &mut MysqlConnection, id: u64, user_id: &str) -> Result<usize> {
+        Ok(diesel::delete(
+            custom_endpoints::table
+                .filter(custom_endpoints::id.eq(id))
+                .filter(custom_endpoints::user_id.eq(user_id)),
+        )
+        .execute(conn)?)
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::{RunQueryDsl, prelude::*};
+
+use crate::models::job::{JobStatus, QueuedJob, WaitingJob};
+use crate::schema::jobs;
+
+pub struct JobRepository;
+
+impl JobRepository {
+    pub fn create(conn: &mut MysqlConnection, job: &QueuedJob) -> Result<()> {
+        diesel::insert_into(jobs::table).values(job).execute(conn)?;
+        Ok(())
+    }
+
+    // Jobs not started yet, including those due later, and those whose worker stopped renewing
+    // the lease. In the order they were queued.
+    pub fn find_waiting(
+        conn: &mut MysqlConnection,
+        now: NaiveDateTime,
+        limit: i64,
+    ) -> Result<Vec<WaitingJob>> {
+        Ok(jobs::table
+            .filter(
+                jobs::status
+                    .eq(JobStatus::Pending.to_string())
+                    .nullable()
+                    .or(jobs::status
+                        .eq(JobStatus::Running.to_string())
+                        .and(jobs::locked_until.lt(now))
+                        .and(jobs::attempts.lt(jobs::max_attempts))),
+            )
+            .order(jobs::created_at.asc())
+            .limit(limit)
+            .select((
+                jobs::id,
+                jobs::kind,
+                jobs::user_id,
+                jobs::chat_id,
+                jobs::run_at,
+                jobs::created_at,
+            ))
+            .load::<WaitingJob>(conn)?)
+    }
+
+    // Locks the job unless another worker is claiming it at the same time, and returns it only if
+    // it can still be claimed.
+    pub fn find_claimable_by_id_for_update(
+        conn: &mut MysqlConnection,
+        id: &str,
+        now: NaiveDateTime,
+    ) -> Result<Option<QueuedJob>> {
+        Ok(jobs::table
+            .find(id)
+            .filter(
+                jobs::status
+                    .eq(JobStatus::Pending.to_string())
+                    .and(jobs::run_at.le(now))
+                    .nullable()
+                    .or(jobs::status
+                        .eq(JobStatus::Running.to_string())
+                        .and(jobs::locked_until.lt(now))
+                        .and(jobs::attempts.lt(jobs::max_attempts))),
+            )
+            .for_update()
+            .skip_locked()
+            .first::<QueuedJob>(conn)
+            .optional()?)
+    }
+
+    // Jobs with a live lease, per user.
+    pub fn count_running_by_user(
+        conn: &mut MysqlConnection,
+        now: NaiveDateTime,
+    ) -> Result<Vec<(String, i64)>> {
+        Ok(jobs::table
+            .filter(jobs::status.eq(JobStatus::Running.to_string()))
+            .filter(jobs::locked_until.ge(now))
+            .group_by(jobs::user_id)
+            .select((jobs::user_id, diesel::dsl::count_star()))
+            .load::<(String, i64)>(conn)?)
+    }
+
+    // Chats with a job of one of `kinds` holding a live lease.
+    pub fn find_busy_chats(
+        conn: &mut MysqlConnection,
+        now: NaiveDateTime,
+        kinds: &[&str],
+    ) -> Result<Vec<String>> {
+        Ok(jobs::table
+            .filter(jobs::status.eq(JobStatus::Running.to_string()))
+            .filter(jobs::locked_until.ge(now))
+            .filter(jobs::kind.eq_any(kinds))
+            .select(jobs::chat_id)
+            .distinct()
+            .load::<String>(conn)?)
+    }
+
+    pub fn lease(
+        conn: &mut MysqlConnection,
+        id: &str,
+        worker_id: &str,
+        until: NaiveDateTime,
+    ) -> Result<QueuedJob> {
+        diesel::update(jobs::table.find(id))
+            .set((
+                jobs::status.eq(JobStatus::Running.to_string()),
+                jobs::attempts.eq(jobs::attempts + 1),
+                jobs::locked_by.eq(worker_id),
+                jobs::locked_until.eq(until),
+            ))
+            .execute(conn)?;
+
+        Ok(jobs::table.find(id).first::<QueuedJob>(conn)?)
+    }
+
+    // Only while the lease is still this worker's, returns whether it was.
+    pub fn renew_lease(
+        conn: &mut MysqlConnection,
+        id: &str,
+        worker_id: &str,
+        until: NaiveDateTime,
+    ) -> Result<bool> {
+        let updated = diesel::update(
+            jobs::table
+                .find(id)
+                .filter(jobs::locked_by.eq(worker_id))
+                .filter(jobs::status.eq(JobStatus::Running.to_string())),
+        )
+        .set(jobs::locked_until.eq(until))
+        .execute(conn)?;
+        Ok(updated > 0)
+    }
+
+    pub fn delete_leased(conn: &mut MysqlConnection, id: &str, worker_id: &str) -> Result<()> {
+        diesel::delete(jobs::table.find(id).filter(jobs::locked_by.eq(worker_id))).execute(conn)?;
+        Ok(())
+    }
+
+    // Hands the job back, to run again at `run_at` or, with the status dead, never.
+    pub fn release(
+        conn: &mut MysqlConnection,
+        id: &str,
+        worker_id: &str,
+        status: JobStatus,
+        run_at: NaiveDateTime,
+        error: &str,
+    ) -> Result<()> {
+        diesel::update(jobs::table.find(id).filter(jobs::locked_by.eq(worker_id)))
+            .set((
+                jobs::status.eq(status.to_string()),
+                jobs::run_at.eq(run_at),
+                jobs::locked_by.eq(None::<String>),
+                jobs::locked_until.eq(None::<NaiveDateTime>),
+                jobs::last_error.eq(error),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    // Jobs whose worker died on the last attempt.
+    pub fn bury_expired(conn: &mut MysqlConnection, now: NaiveDateTime) -> Result<usize> {
+        Ok(diesel::update(
+            jobs::table
+                .filter(jobs::status.eq(JobStatus::Running.to_string()))
+                .filter(jobs::locked_until.lt(now))
+                .filter(jobs::attempts.ge(jobs::max_attempts)),
+        )
+        .set((
+            jobs::status.eq(JobStatus::Dead.to_string()),
+            jobs::locked_by.eq(None::<String>),
+            jobs::locked_until.eq(None::<NaiveDateTime>),
+            jobs::last_error.eq("Lease expired on the last attempt"),
+        ))
+        .execute(conn)?)
+    }
+
+    pub fn exists_running_for_chat(
+        conn: &mut MysqlConnection,
+        chat_id: &str,
+        kinds: &[&str],
+    ) -> Result<bool> {
+        Ok(diesel::select(diesel::dsl::exists(
+            jobs::table
+                .filter(jobs::chat_id.eq(chat_id))
+                .filter(jobs::kind.eq_any(kinds))
+                .filter(jobs::status.eq(JobStatus::Running.to_string())),
+        ))
+        .get_result(conn)?)
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::Result;
+use chrono::Utc;
+use diesel::{RunQueryDsl, prelude::*};
+
+use crate::models::mcp_server::{McpServer, NewMcpServer};
+use crate::schema::mcp_servers;
+
+pub struct McpServerRepository;
+
+impl McpServerRepository {
+    pub fn create(conn: &mut MysqlConnection, new: &NewMcpServer) -> Result<McpServer> {
+        diesel::insert_into(mcp_servers::table)
+            .values(new)
+            .execute(conn)?;
+
+        let inserted = mcp_servers::table
+            .order(mcp_servers::id.desc())
+            .first::<McpServer>(conn)?;
+        Ok(inserted)
+    }
+
+    pub fn list_for_user(conn: &mut MysqlConnection, user_id: &str) -> Result<Vec<McpServer>> {
+        Ok(mcp_servers::table
+            .filter(mcp_servers::user_id.eq(user_id))
+            .order(mcp_servers::name.asc())
+            .load::<McpServer>(conn)?)
+    }
+
+    pub fn count_for_user(conn: &mut MysqlConnection, user_id: &str) -> Result<i64> {
+        Ok(mcp_servers::table
+            .filter(mcp_servers::user_id.eq(user_id))
+            .count()
+            .get_result(conn)?)
+    }
+
+    pub fn get(conn: &mut MysqlConnection, id: u64, user_id: &str) -> Result<McpServer> {
+        Ok(mcp_servers::table
+            .filter(mcp_servers::id.eq(id))
+            .filter(mcp_servers::user_id.eq(user_id))
+            .first::<McpServer>(conn)?)
+    }
+
+    pub fn set_enabled_tools(
+        conn: &mut MysqlConnection,
+        id: u64,
+        user_id: &str,
+        enabled_tools: Option<String>,
+    ) -> Result<usize> {
+        Ok(diesel::update(
+            mcp_servers::table
+                .filter(mcp_servers::id.eq(id))
+                .filter(mcp_servers::user_id.eq(user_id)),
+        )
+        .set((
+            mcp_servers::enabled_tools.eq(enabled_tools),
+            mcp_servers::version.eq(mcp_servers::version + 1),
+            mcp_servers::updated_at.eq(Utc::now().naive_utc()),
+        ))
+        .execute(conn)?)
+    }
+
+    pub fn delete(conn: &mut MysqlConnection, id: u64, user_id: &str) -> Result<usize> {
+        Ok(diesel::delete(
+            mcp_servers::table
+                .filter(mcp_servers::id.eq(id))
+                .filter(mcp_servers::user_id.eq(user_id)),
+        )
+        .execute(conn)?)
+    }
+}
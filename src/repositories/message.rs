@@ -109,6 +109,61 @@ impl MessageRepository {
             .context(format!("Error finding messages for chat {}", chat_id_param))
     }
 
+    // Messages sharing a parent, the first messages of the chat when there is none.
+    pub fn find_siblings_for_update(
+        &self,
+        conn: &mut MysqlConnection,
+        chat_id_param: &str,
+        parent: Option<&str>,
+    ) -> Result<Vec<Message>> {
+        use crate::schema::messages::dsl::{chat_id, created_at, messages, parent_id};
+
+        let in_chat = messages
+            .filter(chat_id.eq(chat_id_param))
+            .order_by(created_at.asc());
+        match parent {
+            Some(parent) => in_chat.filter(parent_id.eq(parent)).for_update().load(conn),
+            None => in_chat.filter(parent_id.is_null()).for_update().load(conn),
+        }
+        .context(format!("Error finding siblings in chat {}", chat_id_param))
+    }
+
+    pub fn find_children(&self, conn: &mut MysqlConnection, parent: &str) -> Result<Vec<Message>> {
+        use crate::schema::messages::dsl::{messages, parent_id};
+
+        messages
+            .filter(parent_id.eq(parent))
+            .load(conn)
+            .context(format!("Error finding replies to message {}", parent))
+    }
+
+    // Newest first. The query is matched literally, LIKE wildcards in it are escaped.
+    pub fn search(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id_param: &str,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<Message>> {
+        use crate::schema::messages::dsl::{body, created_at, messages, user_id};
+
+        let escaped = query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+
+        messages
+            .filter(user_id.eq(user_id_param))
+            .filter(body.like(format!("%{escaped}%")))
+            .order_by(created_at.desc())
+            .limit(limit)
+            .load(conn)
+            .context(format!(
+                "Error searching messages for user {}",
+                user_id_param
+            ))
+    }
+
     pub fn delete_by_chat_id(&self, conn: &mut MysqlConnection, chat_id_param: &str) -> Result<()> {
         use crate::schema::messages::dsl::{chat_id, messages};
 
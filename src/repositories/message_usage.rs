@@ -0,0 +1,42 @@
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::{RunQueryDsl, prelude::*};
+
+use crate::models::message_usage::{MessageUsage, NewMessageUsage};
+use crate::schema::message_usage;
+
+pub struct MessageUsageRepository;
+
+impl MessageUsageRepository {
+    pub fn create(conn: &mut MysqlConnection, new: &NewMessageUsage) -> Result<()> {
+        diesel::insert_into(message_usage::table)
+            .values(new)
+            .execute(conn)?;
+        Ok(())
+    }
+
+    pub fn list_between(
+        conn: &mut MysqlConnection,
+        user_id: &str,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> Result<Vec<MessageUsage>> {
+        Ok(message_usage::table
+            .filter(message_usage::user_id.eq(user_id))
+            .filter(message_usage::created_at.ge(from))
+            .filter(message_usage::created_at.lt(to))
+            .order(message_usage::created_at.asc())
+            .load::<MessageUsage>(conn)?)
+    }
+
+    pub fn list_since(
+        conn: &mut MysqlConnection,
+        user_id: &str,
+        since: NaiveDateTime,
+    ) -> Result<Vec<MessageUsage>> {
+        Ok(message_usage::table
+            .filter(message_usage::user_id.eq(user_id))
+            .filter(message_usage::created_at.ge(since))
+            .load::<MessageUsage>(conn)?)
+    }
+}
@@ -0,0 +1,122 @@
+use anyhow::{Context, Result};
+use diesel::prelude::*;
+
+use crate::models::system_prompt::{Changeset, SystemPrompt};
+
+use super::Repository;
+
+#[derive(Debug, Clone)]
+pub struct SystemPromptRepository;
+
+impl SystemPromptRepository {
+    pub fn find_by_user_for_update(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id_param: &str,
+    ) -> Result<Option<SystemPrompt>> {
+        use crate::schema::system_prompts::dsl::{system_prompts, user_id};
+
+        system_prompts
+            .filter(user_id.eq(user_id_param))
+            .for_update()
+            .first::<SystemPrompt>(conn)
+            .optional()
+            .context(format!(
+                "Error finding system prompt of user {} for update",
+                user_id_param
+            ))
+    }
+}
+
+impl Repository<SystemPrompt, Changeset> for SystemPromptRepository {
+    fn find_by_id(&self, conn: &mut MysqlConnection, id: &str) -> Result<Option<SystemPrompt>> {
+        use crate::schema::system_prompts::dsl::system_prompts;
+
+        match system_prompts.find(id).first::<SystemPrompt>(conn) {
+            Ok(sp) => Ok(Some(sp)),
+            Err(diesel::result::Error::NotFound) => Ok(None),
+            Err(e) => Err(e).context(format!("Error finding system prompt with id {}", id)),
+        }
+    }
+
+    fn find_by_ids(&self, conn: &mut MysqlConnection, ids: &[&str]) -> Result<Vec<SystemPrompt>> {
+        use crate::schema::system_prompts::dsl::{id, system_prompts};
+
+        system_prompts
+            .filter(id.eq_any(ids))
+            .load(conn)
+            .context("Failed to find system prompts by IDs")
+    }
+
+    fn find_by_id_for_update(
+        &self,
+        conn: &mut MysqlConnection,
+        id: &str,
+    ) -> Result<Option<SystemPrompt>> {
+        use crate::schema::system_prompts::dsl::system_prompts;
+
+        match system_prompts
+            .find(id)
+            .for_update()
+            .first::<SystemPrompt>(conn)
+        {
+            Ok(sp) => Ok(Some(sp)),
+            Err(diesel::result::Error::NotFound) => Ok(None),
+            Err(e) => Err(e).context(format!("Error finding system prompt {} for update", id)),
+        }
+    }
+
+    fn find_by_user(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id_param: &str,
+    ) -> Result<Vec<SystemPrompt>> {
+        use crate::schema::system_prompts::dsl::{system_prompts, user_id};
+
+        system_prompts
+            .filter(user_id.eq(user_id_param))
+            .load(conn)
+            .context(format!(
+                "Error finding system prompts for user {}",
+                user_id_param
+            ))
+    }
+
+    fn create(&self, conn: &mut MysqlConnection, entity: &SystemPrompt) -> Result<SystemPrompt> {
+        use crate::schema::system_prompts::dsl::system_prompts;
+
+        diesel::insert_into(system_prompts)
+            .values(entity)
+            .execute(conn)
+            .context(format!("Error creating system prompt {}", entity.id))?;
+
+        Ok(entity.clone())
+    }
+
+    fn update(
+        &self,
+        conn: &mut MysqlConnection,
+        id: &str,
+        changeset: Changeset,
+    ) -> Result<SystemPrompt> {
+        use crate::schema::system_prompts::dsl::system_prompts;
+
+        diesel::update(system_prompts.find(id))
+            .set(changeset)
+            .execute(conn)
+            .context(format!("Error updating system prompt {}", id))?;
+
+        self.find_by_id(conn, id)?
+            .context(format!("SystemPrompt {} not found after update", id))
+    }
+
+    fn delete(&self, conn: &mut MysqlConnection, id: &str) -> Result<()> {
+        use crate::schema::system_prompts::dsl::system_prompts;
+
+        diesel::delete(system_prompts.find(id))
+            .execute(conn)
+            .context(format!("Error deleting system prompt {}", id))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use anyhow::Result;
+use chrono::Utc;
+use diesel::{RunQueryDsl, prelude::*};
+
+use crate::models::user_budget::{NewUserBudget, UserBudget};
+use crate::schema::user_budgets;
+
+pub struct UserBudgetRepository;
+
+impl UserBudgetRepository {
+    pub fn get(conn: &mut MysqlConnection, user_id: &str) -> Result<Option<UserBudget>> {
+        Ok(user_budgets::table
+            .find(user_id)
+            .first::<UserBudget>(conn)
+            .optional()?)
+    }
+
+    pub fn upsert(conn: &mut MysqlConnection, new: &NewUserBudget) -> Result<()> {
+        let updated = diesel::update(user_budgets::table.find(&new.user_id))
+            .set((
+                user_budgets::monthly_limit_micros.eq(new.monthly_limit_micros),
+                user_budgets::version.eq(user_budgets::version + 1),
+                user_budgets::updated_at.eq(Utc::now().naive_utc()),
+            ))
+            .execute(conn)?;
+
+        if updated == 0 {
+            diesel::insert_into(user_budgets::table)
+                .values(new)
+                .execute(conn)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn delete(conn: &mut MysqlConnection, user_id: &str) -> Result<usize> {
+        Ok(diesel::delete(user_budgets::table.find(user_id)).execute(conn)?)
+    }
+}
@@ -1,3 +1,4 @@
+use axum::extract::DefaultBodyLimit;
 use axum::routing::{delete, get, post};
 use axum::{Router, middleware};
 use reqwest::StatusCode;
@@ -15,10 +16,24 @@ use tower_sessions::{
 use tower_sessions_redis_store::RedisStore;
 
 use crate::handlers::api_key::{create_api_key, delete_api_key, list_api_keys};
+use crate::handlers::attachment::{delete_attachment, get_attachment, upload_attachment};
 use crate::handlers::auth::get_current_user;
+use crate::handlers::budget::{get_budget, update_budget};
+use crate::handlers::custom_endpoint::{
+    create_custom_endpoint, delete_custom_endpoint, list_custom_endpoint_models,
+    list_custom_endpoints,
+};
+use crate::handlers::mcp_server::{
+    create_mcp_server, delete_mcp_server, list_mcp_server_tools, list_mcp_servers,
+    update_mcp_server_tools,
+};
+use crate::handlers::model_catalog::list_models;
 use crate::handlers::replicache::{replicache_pull, replicache_push};
-use crate::handlers::shared_chat::{create_shared_chat, delete_shared_chat, get_shared_chat};
+use crate::handlers::shared_chat::{
+    create_shared_chat, delete_shared_chat, get_shared_attachment, get_shared_chat,
+};
 use crate::handlers::sse::sse_handler;
+use crate::handlers::usage::get_usage;
 use crate::{
     app::AppState,
     handlers::auth::{login, logout, register},
@@ -46,6 +61,10 @@ pub fn app_routes(app_state: AppState) -> Router {
     Router::new()
         .route("/up", get(health))
         .route("/api/shared/{id}", get(get_shared_chat))
+        .route(
+            "/api/shared/{id}/attachments/{attachment_id}",
+            get(get_shared_attachment),
+        )
         .nest("/api", protected_routes(app_state.clone()))
         .nest("/api/auth", auth_routes())
         .fallback_service(
@@ -93,9 +112,40 @@ pub fn protected_routes(state: AppState) -> Router<AppState> {
                 .route("/", post(create_api_key).get(list_api_keys))
                 .route("/{id}", delete(delete_api_key)),
         )
+        .nest(
+            "/custom-endpoints",
+            Router::new()
+                .route("/", post(create_custom_endpoint).get(list_custom_endpoints))
+                .route("/{id}", delete(delete_custom_endpoint))
+                .route("/{id}/models", get(list_custom_endpoint_models)),
+        )
+        .nest(
+            "/mcp-servers",
+            Router::new()
+                .route("/", post(create_mcp_server).get(list_mcp_servers))
+                .route("/{id}", delete(delete_mcp_server))
+                .route(
+                    "/{id}/tools",
+                    get(list_mcp_server_tools).put(update_mcp_server_tools),
+                ),
+        )
+        .nest(
+            "/attachments",
+            Router::new()
+                .route(
+                    "/",
+                    post(upload_attachment).layer(DefaultBodyLimit::max(
+                        state.config.attachments.max_upload_bytes,
+                    )),
+                )
+                .route("/{id}", get(get_attachment).delete(delete_attachment)),
+        )
+        .route("/models", get(list_models))
         .route("/chats/{chat_id}/share", post(create_shared_chat))
         .route("/shared/{id}", delete(delete_shared_chat))
         .route("/sse", get(sse_handler))
+        .route("/usage", get(get_usage))
+        .route("/budget", get(get_budget).put(update_budget))
         .route_layer(middleware::from_fn_with_state(
             state,
             crate::middleware::auth::auth,
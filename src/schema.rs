@@ -33,6 +33,27 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    attachments (id) {
+        #[max_length = 255]
+        id -> Varchar,
+        #[max_length = 255]
+        user_id -> Varchar,
+        #[max_length = 255]
+        message_id -> Nullable<Varchar>,
+        #[max_length = 255]
+        filename -> Varchar,
+        #[max_length = 255]
+        content_type -> Varchar,
+        size_bytes -> Unsigned<Bigint>,
+        #[max_length = 512]
+        storage_key -> Varchar,
+        version -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     chats (id) {
         #[max_length = 255]
@@ -48,6 +69,102 @@ diesel::table! {
         pinned_at -> Nullable<Timestamp>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        system_prompt -> Nullable<Text>,
+        summary -> Nullable<Text>,
+        #[max_length = 255]
+        summary_message_id -> Nullable<Varchar>,
+        #[max_length = 255]
+        provider -> Nullable<Varchar>,
+        #[max_length = 255]
+        model -> Nullable<Varchar>,
+        #[max_length = 255]
+        reasoning -> Nullable<Varchar>,
+        temperature -> Nullable<Float>,
+        top_p -> Nullable<Float>,
+    }
+}
+
+diesel::table! {
+    custom_endpoints (id) {
+        id -> Unsigned<Bigint>,
+        #[max_length = 255]
+        user_id -> Varchar,
+        #[max_length = 50]
+        name -> Varchar,
+        #[max_length = 1024]
+        base_url -> Varchar,
+        version -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    jobs (id) {
+        #[max_length = 255]
+        id -> Varchar,
+        #[max_length = 32]
+        kind -> Varchar,
+        #[max_length = 255]
+        user_id -> Varchar,
+        #[max_length = 255]
+        chat_id -> Varchar,
+        payload -> Longtext,
+        #[max_length = 16]
+        status -> Varchar,
+        attempts -> Integer,
+        max_attempts -> Integer,
+        run_at -> Timestamp,
+        #[max_length = 255]
+        locked_by -> Nullable<Varchar>,
+        locked_until -> Nullable<Timestamp>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    mcp_servers (id) {
+        id -> Unsigned<Bigint>,
+        #[max_length = 255]
+        user_id -> Varchar,
+        #[max_length = 50]
+        name -> Varchar,
+        #[max_length = 16]
+        transport -> Varchar,
+        #[max_length = 1024]
+        url -> Nullable<Varchar>,
+        #[max_length = 255]
+        command -> Nullable<Varchar>,
+        args -> Nullable<Text>,
+        enabled_tools -> Nullable<Text>,
+        version -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    message_usage (id) {
+        id -> Unsigned<Bigint>,
+        #[max_length = 255]
+        user_id -> Varchar,
+        #[max_length = 255]
+        chat_id -> Varchar,
+        #[max_length = 255]
+        message_id -> Nullable<Varchar>,
+        #[max_length = 32]
+        kind -> Varchar,
+        #[max_length = 64]
+        provider -> Varchar,
+        #[max_length = 255]
+        model -> Varchar,
+        input_tokens -> Unsigned<Integer>,
+        output_tokens -> Unsigned<Integer>,
+        reasoning_tokens -> Unsigned<Integer>,
+        cost_micros -> Nullable<Unsigned<Bigint>>,
+        created_at -> Timestamp,
     }
 }
 
@@ -58,11 +175,20 @@ diesel::table! {
         #[max_length = 255]
         chat_id -> Varchar,
         #[max_length = 255]
+        parent_id -> Nullable<Varchar>,
+        #[max_length = 255]
         user_id -> Varchar,
         #[max_length = 255]
         role -> Varchar,
         body -> Text,
         reasoning -> Nullable<Text>,
+        parts -> Nullable<Text>,
+        citations -> Nullable<Text>,
+        #[max_length = 255]
+        model -> Nullable<Varchar>,
+        #[max_length = 255]
+        compare_group -> Nullable<Varchar>,
+        selected -> Bool,
         version -> Integer,
         created_at -> Timestamp,
         updated_at -> Timestamp,
@@ -126,13 +252,40 @@ diesel::table! {
         #[max_length = 255]
         shared_chat_id -> Varchar,
         #[max_length = 255]
+        source_message_id -> Nullable<Varchar>,
+        #[max_length = 255]
         role -> Varchar,
         body -> Text,
         reasoning -> Nullable<Text>,
+        citations -> Nullable<Text>,
         created_at -> Timestamp,
     }
 }
 
+diesel::table! {
+    system_prompts (id) {
+        #[max_length = 255]
+        id -> Varchar,
+        #[max_length = 255]
+        user_id -> Varchar,
+        body -> Text,
+        version -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    user_budgets (user_id) {
+        #[max_length = 255]
+        user_id -> Varchar,
+        monthly_limit_micros -> Unsigned<Bigint>,
+        version -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     users (id) {
         #[max_length = 255]
@@ -150,12 +303,19 @@ diesel::table! {
 diesel::allow_tables_to_appear_in_same_query!(
     active_models,
     api_keys,
+    attachments,
     chats,
+    custom_endpoints,
+    jobs,
+    mcp_servers,
+    message_usage,
     messages,
     replicache_client_groups,
     replicache_clients,
     sessions,
     shared_chats,
     shared_messages,
+    system_prompts,
+    user_budgets,
     users,
 );
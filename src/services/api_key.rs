@@ -64,6 +64,16 @@ impl ApiKeyService {
         anyhow::ensure!(affected == 1, "nothing deleted");
         Ok(())
     }
+
+    pub fn delete_for_provider(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+        provider: &str,
+    ) -> Result<()> {
+        ApiKeyRepository::delete_for_provider(conn, user_id, provider)?;
+        Ok(())
+    }
 }
 
 fn parse_master_key(b64: &str) -> Result<SecretSlice<u8>> {
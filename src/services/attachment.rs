@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail, ensure};
+use chrono::Utc;
+use diesel::prelude::*;
+
+use crate::{
+    infra::storage::BlobStore,
+    models::attachment::{Attachment, AttachmentKind, Changeset, CreateArgs},
+    repositories::{Repository, attachment::AttachmentRepository},
+};
+
+#[derive(Debug, Clone)]
+pub struct AttachmentService {
+    repository: AttachmentRepository,
+    store: Arc<dyn BlobStore>,
+}
+
+impl AttachmentService {
+    fn check_ownership(
+        &self,
+        conn: &mut MysqlConnection,
+        id: &str,
+        user_id: &str,
+    ) -> Result<Attachment> {
+        let attachment = self
+            .repository
+            .find_by_id(conn, id)?
+            .ok_or_else(|| anyhow::anyhow!("Failed to find attachment: {}", id))?;
+
+        if attachment.user_id != user_id {
+            bail!("Forbidden: You do not have access to this attachment.");
+        }
+
+        Ok(attachment)
+    }
+
+    pub fn new(repository: AttachmentRepository, store: Arc<dyn BlobStore>) -> Self {
+        Self { repository, store }
+    }
+
+    pub async fn store_blob(&self, key: &str, data: &[u8]) -> Result<()> {
+        self.store.put(key, data).await
+    }
+
+    // For a blob whose attachment was never saved.
+    pub async fn delete_stored(&self, key: &str) -> Result<()> {
+        self.store.delete(key).await
+    }
+
+    pub async fn read_blob(&self, attachment: &Attachment) -> Result<Vec<u8>> {
+        self.store.get(&attachment.storage_key).await
+    }
+
+    pub async fn store_text(&self, storage_key: &str, text: &str) -> Result<()> {
+        self.store
+            .put(&Attachment::text_key(storage_key), text.as_bytes())
+            .await
+    }
+
+    pub async fn read_text(&self, attachment: &Attachment) -> Result<String> {
+        let data = self
+            .store
+            .get(&Attachment::text_key(&attachment.storage_key))
+            .await?;
+        String::from_utf8(data).context("Extracted text is not UTF-8")
+    }
+
+    pub async fn delete_blob(&self, attachment: &Attachment) -> Result<()> {
+        if attachment.is_document() {
+            self.store
+                .delete(&Attachment::text_key(&attachment.storage_key))
+                .await?;
+        }
+        self.store.delete(&attachment.storage_key).await
+    }
+
+    // PDF parsing is CPU bound and the parser panics on some malformed files, so it runs on the
+    // blocking pool where a panic only fails this upload.
+    pub async fn extract_text(&self, kind: AttachmentKind, data: Vec<u8>) -> Result<String> {
+        let text = match kind {
+            AttachmentKind::Text => String::from_utf8(data).context("Text files must be UTF-8")?,
+            AttachmentKind::Pdf => {
+                tokio::task::spawn_blocking(move || pdf_extract::extract_text_from_mem(&data))
+                    .await
+                    .context("Failed to read PDF")?
+                    .context("Failed to read PDF")?
+            }
+            AttachmentKind::Image => bail!("Images have no text to extract"),
+        };
+
+        let text = text.trim();
+        ensure!(!text.is_empty(), "No text could be extracted from the file");
+
+        Ok(text.to_owned())
+    }
+
+    pub fn create(
+        &self,
+        conn: &mut MysqlConnection,
+        args: CreateArgs,
+        user_id: &str,
+    ) -> Result<Attachment> {
+        let now = Utc::now().naive_utc();
+
+        let attachment = Attachment {
+            id: args.id,
+            user_id: user_id.to_owned(),
+            message_id: None,
+            filename: args.filename,
+            content_type: args.content_type,
+            size_bytes: args.size_bytes,
+            storage_key: args.storage_key,
+            version: 1,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.repository.create(conn, &attachment)
+    }
+
+    // Links uploaded attachments to the message they were sent with. An attachment belongs to a
+    // single message, so one that was already sent can't be reused.
+    pub fn attach(
+        &self,
+        conn: &mut MysqlConnection,
+        ids: &[String],
+        message_id: &str,
+        user_id: &str,
+    ) -> Result<Vec<Attachment>> {
+        conn.transaction(|conn| {
+            ids.iter()
+                .map(|id| {
+                    let existing = self
+                        .repository
+                        .find_by_id_for_update(conn, id)?
+                        .ok_or_else(|| anyhow::anyhow!("Failed to find attachment: {}", id))?;
+
+                    if existing.user_id != user_id {
+                        bail!("Forbidden: You do not have access to this attachment.");
+                    }
+                    if existing.message_id.is_some() {
+                        bail!("Attachment {} was already sent", id);
+                    }
+
+                    let changeset = Changeset {
+                        message_id: Some(message_id.to_owned()),
+                        version: existing.version + 1,
+                        updated_at: Utc::now().naive_utc(),
+                    };
+
+                    self.repository.update(conn, id, changeset)
+                })
+                .collect()
+        })
+    }
+
+    pub fn get(&self, conn: &mut MysqlConnection, id: &str, user_id: &str) -> Result<Attachment> {
+        self.check_ownership(conn, id, user_id)
+    }
+
+    pub fn list_for_user(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+    ) -> Result<Vec<Attachment>> {
+        self.repository.find_by_user(conn, user_id)
+    }
+
+    pub fn list_for_messages(
+        &self,
+        conn: &mut MysqlConnection,
+        message_ids: &[&str],
+    ) -> Result<Vec<Attachment>> {
+        self.repository.find_by_message_ids(conn, message_ids)
+    }
+
+    pub fn delete(
+        &self,
+        conn: &mut MysqlConnection,
+        id: &str,
+        user_id: &str,
+    ) -> Result<Attachment> {
+        conn.transaction(|conn| {
+            let attachment = self.check_ownership(conn, id, user_id)?;
+            self.repository.delete(conn, id)?;
+            Ok(attachment)
+        })
+    }
+}
@@ -0,0 +1,59 @@
+use anyhow::{Context, Result};
+use chrono::{Datelike, NaiveDate, Utc};
+use diesel::prelude::*;
+
+use crate::{
+    models::{
+        message_usage::MessageUsage,
+        user_budget::{BudgetStatus, UserBudget},
+    },
+    repositories::{message_usage::MessageUsageRepository, user_budget::UserBudgetRepository},
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct BudgetService;
+
+impl BudgetService {
+    pub fn new() -> Self {
+        Self
+    }
+
+    // Budgets run per calendar month (UTC).
+    pub fn status(&self, conn: &mut MysqlConnection, user_id: &str) -> Result<BudgetStatus> {
+        let today = Utc::now().date_naive();
+        let period_start =
+            NaiveDate::from_ymd_opt(today.year(), today.month(), 1).context("start of month")?;
+        let since = period_start.and_hms_opt(0, 0, 0).context("start of day")?;
+
+        let budget = UserBudgetRepository::get(conn, user_id)?;
+        let spent_micros = MessageUsageRepository::list_since(conn, user_id, since)?
+            .iter()
+            .map(MessageUsage::budget_cost_micros)
+            .sum();
+
+        Ok(BudgetStatus {
+            monthly_limit_micros: budget.map(|b| b.monthly_limit_micros),
+            spent_micros,
+            period_start,
+        })
+    }
+
+    pub fn set(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+        monthly_limit_micros: Option<u64>,
+    ) -> Result<BudgetStatus> {
+        match monthly_limit_micros {
+            Some(limit) => {
+                let new_budget = UserBudget::build_new(user_id.to_owned(), limit);
+                UserBudgetRepository::upsert(conn, &new_budget)?;
+            }
+            None => {
+                UserBudgetRepository::delete(conn, user_id)?;
+            }
+        }
+
+        self.status(conn, user_id)
+    }
+}
@@ -1,6 +1,10 @@
 use crate::{
+    ai::provider::{AiProvider, MAX_TEMPERATURE},
     models::{
-        chat::{Changeset, Chat, ChatWithMessages, CreateArgs, ForkArgs, UpdateArgs},
+        chat::{
+            Changeset, Chat, ChatWithMessages, CreateArgs, ForkArgs, ModelChangeset, UpdateArgs,
+            UpdateModelArgs, UpdateSystemPromptArgs,
+        },
         message::Message,
     },
     repositories::{Repository, chat::ChatRepository, message::MessageRepository},
@@ -58,6 +62,14 @@ impl ChatService {
             pinned_at: None,
             created_at: args.created_at.naive_utc(),
             updated_at: args.updated_at.naive_utc(),
+            system_prompt: None,
+            summary: None,
+            summary_message_id: None,
+            provider: None,
+            model: None,
+            reasoning: None,
+            temperature: None,
+            top_p: None,
         };
 
         self.repository.create(conn, &chat)
@@ -84,6 +96,7 @@ impl ChatService {
                 pinned: args.pinned,
                 pinned_at: args.pinned_at.and_then(|p| Some(p.naive_utc())),
                 archived: args.archived,
+                system_prompt: None,
                 version: existing.version + 1,
                 updated_at: args.updated_at.naive_utc(),
             };
@@ -92,6 +105,97 @@ impl ChatService {
         })
     }
 
+    pub fn update_system_prompt(
+        &self,
+        conn: &mut MysqlConnection,
+        args: UpdateSystemPromptArgs,
+        user_id: &str,
+    ) -> Result<Chat> {
+        conn.transaction(|conn| {
+            let existing = self
+                .repository
+                .find_by_id_for_update(conn, &args.id)?
+                .ok_or_else(|| {
+                    anyhow::anyhow!(format!("Failed to find existing chat: {}", args.id))
+                })?;
+
+            self.check_ownership(conn, &args.id, user_id)?;
+
+            let system_prompt = args.system_prompt.filter(|p| !p.trim().is_empty());
+
+            let changeset = Changeset {
+                title: None,
+                pinned: None,
+                pinned_at: None,
+                archived: None,
+                system_prompt: Some(system_prompt),
+                version: existing.version + 1,
+                updated_at: args.updated_at.naive_utc(),
+            };
+
+            self.repository.update(conn, &args.id, changeset)
+        })
+    }
+
+    pub fn update_model(
+        &self,
+        conn: &mut MysqlConnection,
+        args: UpdateModelArgs,
+        user_id: &str,
+    ) -> Result<Chat> {
+        let model = args.model.filter(|m| !m.trim().is_empty());
+        let provider = match (&args.provider, &model) {
+            (Some(provider), Some(_)) => Some(
+                provider
+                    .parse::<AiProvider>()
+                    .with_context(|| format!("Invalid AI provider: '{}'", provider))?,
+            ),
+            (None, None) => None,
+            _ => bail!("provider and model must be set together"),
+        };
+        let max_temperature = provider
+            .as_ref()
+            .map_or(MAX_TEMPERATURE, AiProvider::max_temperature);
+        if args
+            .temperature
+            .is_some_and(|t| !(0.0..=max_temperature).contains(&t))
+        {
+            bail!("temperature must be between 0 and {max_temperature}");
+        }
+        if args.top_p.is_some_and(|p| !(0.0..=1.0).contains(&p)) {
+            bail!("top_p must be between 0 and 1");
+        }
+
+        conn.transaction(|conn| {
+            let existing = self
+                .repository
+                .find_by_id_for_update(conn, &args.id)?
+                .ok_or_else(|| {
+                    anyhow::anyhow!(format!("Failed to find existing chat: {}", args.id))
+                })?;
+
+            self.check_ownership(conn, &args.id, user_id)?;
+
+            // effort only means something for the model it was picked with
+            let reasoning = args
+                .reasoning
+                .filter(|_| model.is_some())
+                .map(|r| r.to_string());
+
+            let changeset = ModelChangeset {
+                provider: model.as_ref().and(args.provider),
+                model,
+                reasoning,
+                temperature: args.temperature,
+                top_p: args.top_p,
+                version: existing.version + 1,
+                updated_at: args.updated_at.naive_utc(),
+            };
+
+            self.repository.update_model(conn, &args.id, changeset)
+        })
+    }
+
     pub fn delete(&self, conn: &mut MysqlConnection, id: &str, user_id: &str) -> Result<Chat> {
         conn.transaction(|conn| {
             let chat = self
@@ -110,6 +214,11 @@ impl ChatService {
 
     pub fn fork(&self, conn: &mut MysqlConnection, args: &ForkArgs, user_id: &str) -> Result<Chat> {
         conn.transaction(|conn| {
+            let source = match &args.source_id {
+                Some(id) => Some(self.check_ownership(conn, id, user_id)?),
+                None => None,
+            };
+
             let chat = Chat {
                 id: args.new_id.clone(),
                 user_id: user_id.to_string(),
@@ -121,25 +230,60 @@ impl ChatService {
                 version: 1,
                 created_at: args.time.naive_utc(),
                 updated_at: args.time.naive_utc(),
+                system_prompt: source.as_ref().and_then(|c| c.system_prompt.clone()),
+                summary: None,
+                summary_message_id: None,
+                // The fork answers with the same model as the chat it came from.
+                provider: source.as_ref().and_then(|c| c.provider.clone()),
+                model: source.as_ref().and_then(|c| c.model.clone()),
+                reasoning: source.as_ref().and_then(|c| c.reasoning.clone()),
+                temperature: source.as_ref().and_then(|c| c.temperature),
+                top_p: source.as_ref().and_then(|c| c.top_p),
             };
 
             let chat_id = self.repository.create(conn, &chat);
 
-            args.msgs.iter().for_each(|m| {
+            // The client copies the chat's messages, oldest first, up to the one forked from.
+            let mut stored = vec![None; args.msgs.len()];
+            if let (Some(source), Some(until_id)) = (&source, &args.until_id) {
+                let mut messages = self.msg_repo.find_by_chat(conn, &source.id)?;
+                let end = messages
+                    .iter()
+                    .position(|m| &m.id == until_id)
+                    .context(format!(
+                        "Message {until_id} not found in chat {}",
+                        source.id
+                    ))?;
+                messages.truncate(end + 1);
+                if messages.len() != args.msgs.len() {
+                    bail!("Forked messages don't match chat {}", source.id);
+                }
+                stored = messages.into_iter().map(Some).collect();
+            }
+
+            // The copied messages are the branch that was shown, so they form a single one here.
+            let mut parent_id = None;
+            for (m, stored) in args.msgs.iter().zip(stored) {
                 let message = Message {
                     id: m.id.clone(),
                     chat_id: args.new_id.clone(),
+                    parent_id: parent_id.replace(m.id.clone()),
                     user_id: user_id.to_string(),
                     role: m.role.clone(),
                     body: m.body.clone(),
                     reasoning: m.reasoning.clone(),
+                    parts: stored.as_ref().and_then(|s| s.parts.clone()),
+                    citations: stored.as_ref().and_then(|s| s.citations.clone()),
+                    model: None,
+                    compare_group: None,
+                    selected: true,
                     version: 1,
                     created_at: m.created_at.naive_utc(),
                     updated_at: m.updated_at.naive_utc(),
                 };
 
                 let _ = self.msg_repo.create(conn, &message);
-            });
+            }
 
             chat_id
         })
@@ -197,6 +341,14 @@ impl ChatService {
                 pinned_at: chat.pinned_at,
                 created_at: chat.created_at,
                 updated_at: chat.updated_at,
+                system_prompt: chat.system_prompt,
+                summary: chat.summary,
+                summary_message_id: chat.summary_message_id,
+                provider: chat.provider,
+                model: chat.model,
+                reasoning: chat.reasoning,
+                temperature: chat.temperature,
+                top_p: chat.top_p,
                 messages,
             })
             .collect();
@@ -224,6 +376,7 @@ impl ChatService {
                 pinned: None,
                 pinned_at: None,
                 archived: None,
+                system_prompt: None,
                 version: existing.version + 1,
                 updated_at: Utc::now().naive_utc(),
             };
@@ -231,4 +384,17 @@ impl ChatService {
             self.repository.update(conn, chat_id, changeset)
         })
     }
+
+    pub fn save_summary(
+        &self,
+        conn: &mut MysqlConnection,
+        chat_id: &str,
+        summary: &str,
+        message_id: &str,
+        user_id: &str,
+    ) -> Result<()> {
+        self.check_ownership(conn, chat_id, user_id)?;
+        self.repository
+            .set_summary(conn, chat_id, summary, message_id)
+    }
 }
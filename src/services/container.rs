@@ -2,14 +2,19 @@ use std::sync::Arc;
 
 use crate::{
     configuration::Settings,
+    infra::{self, search::SearchBackend},
     repositories::{
-        active_model::ActiveModelRepository, chat::ChatRepository, message::MessageRepository,
+        active_model::ActiveModelRepository, attachment::AttachmentRepository,
+        chat::ChatRepository, message::MessageRepository, system_prompt::SystemPromptRepository,
     },
 };
 
 use super::{
-    active_model::ActiveModelService, api_key::ApiKeyService, chat::ChatService,
-    message::MessageService, shared_chat::SharedChatService,
+    active_model::ActiveModelService, api_key::ApiKeyService, attachment::AttachmentService,
+    budget::BudgetService, chat::ChatService, custom_endpoint::CustomEndpointService,
+    job_queue::JobQueueService, mcp_server::McpServerService, message::MessageService,
+    model_catalog::ModelCatalogService, shared_chat::SharedChatService,
+    system_prompt::SystemPromptService, usage::UsageService,
 };
 
 #[derive(Debug, Clone)]
@@ -18,20 +23,46 @@ pub struct ServiceContainer {
     pub message_service: MessageService,
     pub active_model_service: ActiveModelService,
     pub api_key_service: ApiKeyService,
+    pub custom_endpoint_service: CustomEndpointService,
+    pub mcp_server_service: McpServerService,
     pub shared_chat_service: SharedChatService,
+    pub usage_service: UsageService,
+    pub budget_service: BudgetService,
+    pub system_prompt_service: SystemPromptService,
+    pub model_catalog_service: ModelCatalogService,
+    pub attachment_service: AttachmentService,
+    pub job_queue_service: JobQueueService,
+    // None when web search is disabled
+    pub search_backend: Option<Arc<dyn SearchBackend>>,
 }
 
 impl ServiceContainer {
     pub fn new(config: Arc<Settings>) -> Self {
         let chat_service = ChatService::new(ChatRepository, MessageRepository);
         let message_service = MessageService::new(MessageRepository, ChatRepository);
+        let api_key_service = ApiKeyService::new(config.application.secret.clone());
+        let blob_store = infra::storage::build(&config.attachments.storage);
+        let attachment_service = AttachmentService::new(AttachmentRepository, blob_store);
 
         Self {
             chat_service: chat_service.clone(),
             message_service: message_service.clone(),
             active_model_service: ActiveModelService::new(ActiveModelRepository),
-            api_key_service: ApiKeyService::new(config.application.secret.clone()),
-            shared_chat_service: SharedChatService::new(chat_service, message_service),
+            api_key_service: api_key_service.clone(),
+            custom_endpoint_service: CustomEndpointService::new(api_key_service.clone()),
+            mcp_server_service: McpServerService::new(api_key_service),
+            shared_chat_service: SharedChatService::new(
+                chat_service,
+                message_service,
+                attachment_service.clone(),
+            ),
+            usage_service: UsageService::new(),
+            budget_service: BudgetService::new(),
+            system_prompt_service: SystemPromptService::new(SystemPromptRepository),
+            model_catalog_service: ModelCatalogService::new(config.providers.catalog_ttl_secs),
+            attachment_service,
+            job_queue_service: JobQueueService::new(config.jobs.clone()),
+            search_backend: infra::search::build(&config.search),
         }
     }
 }
@@ -0,0 +1,95 @@
+use anyhow::{Context, Result};
+use diesel::prelude::*;
+use secrecy::SecretString;
+
+use crate::{
+    models::{
+        api_key,
+        custom_endpoint::{CreateArgs, CustomEndpoint},
+    },
+    repositories::custom_endpoint::CustomEndpointRepository,
+};
+
+use super::api_key::ApiKeyService;
+
+#[derive(Debug, Clone)]
+pub struct CustomEndpointService {
+    api_key_service: ApiKeyService,
+}
+
+impl CustomEndpointService {
+    pub fn new(api_key_service: ApiKeyService) -> Self {
+        Self { api_key_service }
+    }
+
+    pub fn create(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+        args: CreateArgs,
+        api_key: Option<SecretString>,
+    ) -> Result<CustomEndpoint> {
+        conn.transaction(|conn| {
+            let new_endpoint = CustomEndpoint::build_new(user_id.to_owned(), args);
+            let endpoint = CustomEndpointRepository::create(conn, &new_endpoint)?;
+
+            if let Some(api_key) = api_key {
+                let args = api_key::CreateArgs {
+                    provider: CustomEndpoint::key_provider(&endpoint.name),
+                    api_key,
+                };
+                self.api_key_service
+                    .create(conn, user_id, args)
+                    .context("store api key")?;
+            }
+
+            Ok(endpoint)
+        })
+    }
+
+    pub fn list(&self, conn: &mut MysqlConnection, user_id: &str) -> Result<Vec<CustomEndpoint>> {
+        CustomEndpointRepository::list_for_user(conn, user_id)
+    }
+
+    pub fn get(
+        &self,
+        conn: &mut MysqlConnection,
+        id: u64,
+        user_id: &str,
+    ) -> Result<CustomEndpoint> {
+        CustomEndpointRepository::get(conn, id, user_id)
+    }
+
+    pub fn get_by_name(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+        name: &str,
+    ) -> Result<CustomEndpoint> {
+        CustomEndpointRepository::get_by_name(conn, user_id, name)
+    }
+
+    // Keys are optional for custom endpoints; local servers usually don't need one.
+    pub fn get_api_key(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+        endpoint: &CustomEndpoint,
+    ) -> Option<SecretString> {
+        self.api_key_service
+            .get_and_decrypt(conn, user_id, &CustomEndpoint::key_provider(&endpoint.name))
+            .ok()
+    }
+
+    pub fn delete(&self, conn: &mut MysqlConnection, id: u64, user_id: &str) -> Result<()> {
+        conn.transaction(|conn| {
+            let endpoint = CustomEndpointRepository::get(conn, id, user_id)?;
+            CustomEndpointRepository::delete(conn, id, user_id)?;
+            self.api_key_service.delete_for_provider(
+                conn,
+                user_id,
+                &CustomEndpoint::key_provider(&endpoint.name),
+            )
+        })
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+use tower_sessions_redis_store::fred::prelude::Pool;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::infra;
+
+// Cancellations are sent to every process, the generation may be running in any of the workers.
+const CANCEL_CHANNEL: &str = "generation-cancel";
+
+#[derive(Debug, Clone)]
+struct RunningGeneration {
+    id: String,
+    user_id: String,
+    token: CancellationToken,
+}
+
+// Keyed by chat id. Uses a std mutex rather than tokio's so that it can be reached from the
+// (blocking) replicache mutation handlers as well as from the async worker.
+#[derive(Debug, Clone)]
+pub struct GenerationRegistry {
+    inner: Arc<Mutex<HashMap<String, RunningGeneration>>>,
+    cache: Pool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CancelRequest {
+    chat_id: String,
+    user_id: String,
+}
+
+impl GenerationRegistry {
+    pub fn new(cache: Pool) -> Self {
+        Self {
+            inner: Arc::default(),
+            cache,
+        }
+    }
+
+    pub fn start(&self, chat_id: &str, user_id: &str) -> GenerationHandle {
+        let running = RunningGeneration {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_id.to_owned(),
+            token: CancellationToken::new(),
+        };
+
+        let mut guard = self.inner.lock().expect("generation registry poisoned");
+        if let Some(previous) = guard.insert(chat_id.to_owned(), running.clone()) {
+            previous.token.cancel();
+        }
+
+        GenerationHandle {
+            registry: self.clone(),
+            chat_id: chat_id.to_owned(),
+            id: running.id,
+            token: running.token,
+        }
+    }
+
+    pub fn cancel(&self, chat_id: &str, user_id: &str) -> bool {
+        let guard = self.inner.lock().expect("generation registry poisoned");
+        match guard.get(chat_id) {
+            Some(running) if running.user_id == user_id => {
+                running.token.cancel();
+                info!(%chat_id, "Generation cancelled");
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // For a generation running in another process. Sent in the background since the mutation
+    // handlers calling this can't wait on it.
+    pub fn cancel_elsewhere(&self, chat_id: &str, user_id: &str) {
+        let request = CancelRequest {
+            chat_id: chat_id.to_owned(),
+            user_id: user_id.to_owned(),
+        };
+        let payload = serde_json::to_string(&request).expect("Failed to serialize CancelRequest");
+        let cache = self.cache.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = infra::redis::publish(&cache, CANCEL_CHANNEL, payload).await {
+                warn!(error = ?e, "Failed to send cancellation");
+            }
+        });
+    }
+
+    // Run by every process with a worker.
+    pub async fn run_cancel_listener(self: Arc<Self>) {
+        infra::redis::subscribe(self.cache.clone(), CANCEL_CHANNEL, |payload| {
+            let registry = self.clone();
+            async move {
+                match serde_json::from_str::<CancelRequest>(&payload) {
+                    Ok(request) => {
+                        registry.cancel(&request.chat_id, &request.user_id);
+                    }
+                    Err(e) => warn!(error = ?e, "Dropping malformed cancellation"),
+                }
+            }
+        })
+        .await;
+    }
+
+    fn finish(&self, chat_id: &str, id: &str) {
+        let mut guard = self.inner.lock().expect("generation registry poisoned");
+        if guard.get(chat_id).is_some_and(|r| r.id == id) {
+            guard.remove(chat_id);
+        }
+    }
+}
+
+// Removes the entry from the registry when the generation goes out of scope, unless a newer
+// generation for the same chat has already replaced it.
+#[derive(Debug)]
+pub struct GenerationHandle {
+    registry: GenerationRegistry,
+    chat_id: String,
+    id: String,
+    token: CancellationToken,
+}
+
+impl GenerationHandle {
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+}
+
+impl Drop for GenerationHandle {
+    fn drop(&mut self) {
+        self.registry.finish(&self.chat_id, &self.id);
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use anyhow::Result;
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use tokio_retry2::strategy::jitter;
+use uuid::Uuid;
+
+use crate::{
+    configuration::JobSettings,
+    jobs::Job,
+    models::job::{JobStatus, QueuedJob, WaitingJob},
+    repositories::job::JobRepository,
+};
+
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+// Jobs looked at when scheduling. Anything past it waits for those ahead to clear.
+const WAITING_LIMIT: i64 = 500;
+
+#[derive(Debug, Clone)]
+pub struct JobQueueService {
+    settings: JobSettings,
+}
+
+impl JobQueueService {
+    pub fn new(settings: JobSettings) -> Self {
+        Self { settings }
+    }
+
+    // Saved with the caller's transaction, so a job is only picked up once what it works on has
+    // been committed.
+    pub fn enqueue(&self, conn: &mut MysqlConnection, job: &Job) -> Result<()> {
+        let now = Utc::now().naive_utc();
+        let queued = QueuedJob {
+            id: Uuid::new_v4().to_string(),
+            kind: job.kind().to_owned(),
+            user_id: job.user_id().to_owned(),
+            chat_id: job.chat_id().to_owned(),
+            payload: serde_json::to_string(job)?,
+            status: JobStatus::Pending.to_string(),
+            attempts: 0,
+            max_attempts: self.settings.max_attempts,
+            run_at: now,
+            locked_by: None,
+            locked_until: None,
+            last_error: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        JobRepository::create(conn, &queued)
+    }
+
+    // Takes the first due job in the fair order whose user is under the per user limit and whose
+    // chat isn't already generating, while fewer than max_running jobs run in total. Workers
+    // claiming at the same moment can each see a limit not yet reached, so it can be passed by
+    // as many jobs as there are workers, briefly.
+    pub fn claim(&self, conn: &mut MysqlConnection, worker_id: &str) -> Result<Option<QueuedJob>> {
+        conn.transaction(|conn| {
+            let now = Utc::now().naive_utc();
+            let buried = JobRepository::bury_expired(conn, now)?;
+            if buried > 0 {
+                tracing::error!(count = buried, "Jobs dead after their worker stopped");
+            }
+
+            let running = self.running_by_user(conn, now)?;
+            if running.values().sum::<usize>() >= self.settings.max_running {
+                return Ok(None);
+            }
+
+            let due = self
+                .startable(conn, now)?
+                .into_iter()
+                .filter(|j| j.run_at <= now)
+                .collect();
+
+            for job in WaitingJob::fair_order(due, &running) {
+                if running.get(&job.user_id).copied().unwrap_or(0) >= self.settings.max_per_user {
+                    continue;
+                }
+                if JobRepository::find_claimable_by_id_for_update(conn, &job.id, now)?.is_some() {
+                    return JobRepository::lease(conn, &job.id, worker_id, self.lease_until())
+                        .map(Some);
+                }
+            }
+            Ok(None)
+        })
+    }
+
+    // The jobs waiting to run with their place in the queue, counted from 1. Generations held
+    // back behind another in their chat get no place, they wait on that chat rather than the
+    // queue.
+    pub fn positions(&self, conn: &mut MysqlConnection) -> Result<Vec<(WaitingJob, usize)>> {
+        let now = Utc::now().naive_utc();
+        let running = self.running_by_user(conn, now)?;
+        let waiting = self.startable(conn, now)?;
+
+        Ok(WaitingJob::fair_order(waiting, &running)
+            .into_iter()
+            .zip(1..)
+            .collect())
+    }
+
+    // False once the lease was lost to another worker.
+    pub fn renew(&self, conn: &mut MysqlConnection, id: &str, worker_id: &str) -> Result<bool> {
+        JobRepository::renew_lease(conn, id, worker_id, self.lease_until())
+    }
+
+    pub fn complete(&self, conn: &mut MysqlConnection, id: &str, worker_id: &str) -> Result<()> {
+        JobRepository::delete_leased(conn, id, worker_id)
+    }
+
+    // Schedules another attempt with an exponential backoff, or marks the job dead when it has
+    // none left or retrying can't help.
+    pub fn fail(
+        &self,
+        conn: &mut MysqlConnection,
+        job: &QueuedJob,
+        worker_id: &str,
+        error: &str,
+        retry: bool,
+    ) -> Result<JobStatus> {
+        let status = if !retry || job.attempts >= job.max_attempts {
+            JobStatus::Dead
+        } else {
+            JobStatus::Pending
+        };
+
+        let exponent = (job.attempts - 1).clamp(0, 16) as u32;
+        let delay = jitter(RETRY_BASE_DELAY * 2u32.pow(exponent));
+        let run_at = Utc::now().naive_utc() + delay;
+
+        JobRepository::release(conn, &job.id, worker_id, status, run_at, error)?;
+        Ok(status)
+    }
+
+    // Titles aren't included, they have no stream to cancel.
+    pub fn is_generating(&self, conn: &mut MysqlConnection, chat_id: &str) -> Result<bool> {
+        JobRepository::exists_running_for_chat(conn, chat_id, &Job::GENERATIONS)
+    }
+
+    // Waiting jobs that could start once due, see WaitingJob::startable.
+    fn startable(&self, conn: &mut MysqlConnection, now: NaiveDateTime) -> Result<Vec<WaitingJob>> {
+        let busy_chats: HashSet<String> =
+            JobRepository::find_busy_chats(conn, now, &Job::GENERATIONS)?
+                .into_iter()
+                .collect();
+        let waiting = JobRepository::find_waiting(conn, now, WAITING_LIMIT)?;
+        Ok(WaitingJob::startable(
+            waiting,
+            &busy_chats,
+            &Job::GENERATIONS,
+        ))
+    }
+
+    fn running_by_user(
+        &self,
+        conn: &mut MysqlConnection,
+        now: NaiveDateTime,
+    ) -> Result<HashMap<String, usize>> {
+        Ok(JobRepository::count_running_by_user(conn, now)?
+            .into_iter()
+            .map(|(user_id, count)| (user_id, count as usize))
+            .collect())
+    }
+
+    fn lease_until(&self) -> NaiveDateTime {
+        Utc::now().naive_utc() + self.settings.lease()
+    }
+}
@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use diesel::prelude::*;
+use secrecy::SecretString;
+
+use crate::{
+    models::{
+        api_key,
+        mcp_server::{CreateArgs, McpServer},
+    },
+    repositories::mcp_server::McpServerRepository,
+};
+
+use super::api_key::ApiKeyService;
+
+#[derive(Debug, Clone)]
+pub struct McpServerService {
+    api_key_service: ApiKeyService,
+}
+
+impl McpServerService {
+    pub fn new(api_key_service: ApiKeyService) -> Self {
+        Self { api_key_service }
+    }
+
+    pub fn create(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+        args: CreateArgs,
+        api_key: Option<SecretString>,
+    ) -> Result<McpServer> {
+        conn.transaction(|conn| {
+            let new_server = McpServer::build_new(user_id.to_owned(), args);
+            let server = McpServerRepository::create(conn, &new_server)?;
+
+            if let Some(api_key) = api_key {
+                let args = api_key::CreateArgs {
+                    provider: McpServer::key_provider(&server.name),
+                    api_key,
+                };
+                self.api_key_service
+                    .create(conn, user_id, args)
+                    .context("store api key")?;
+            }
+
+            Ok(server)
+        })
+    }
+
+    pub fn list(&self, conn: &mut MysqlConnection, user_id: &str) -> Result<Vec<McpServer>> {
+        McpServerRepository::list_for_user(conn, user_id)
+    }
+
+    pub fn count(&self, conn: &mut MysqlConnection, user_id: &str) -> Result<i64> {
+        McpServerRepository::count_for_user(conn, user_id)
+    }
+
+    pub fn get(&self, conn: &mut MysqlConnection, id: u64, user_id: &str) -> Result<McpServer> {
+        McpServerRepository::get(conn, id, user_id)
+    }
+
+    pub fn get_api_key(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+        server: &McpServer,
+    ) -> Option<SecretString> {
+        self.api_key_service
+            .get_and_decrypt(conn, user_id, &McpServer::key_provider(&server.name))
+            .ok()
+    }
+
+    // None enables every tool the server offers, including ones it adds later.
+    pub fn set_enabled_tools(
+        &self,
+        conn: &mut MysqlConnection,
+        id: u64,
+        user_id: &str,
+        enabled_tools: Option<Vec<String>>,
+    ) -> Result<McpServer> {
+        let encoded = enabled_tools
+            .map(|tools| serde_json::to_string(&tools))
+            .transpose()?;
+
+        conn.transaction(|conn| {
+            McpServerRepository::set_enabled_tools(conn, id, user_id, encoded)?;
+            McpServerRepository::get(conn, id, user_id)
+        })
+    }
+
+    pub fn delete(&self, conn: &mut MysqlConnection, id: u64, user_id: &str) -> Result<()> {
+        conn.transaction(|conn| {
+            let server = McpServerRepository::get(conn, id, user_id)?;
+            McpServerRepository::delete(conn, id, user_id)?;
+            self.api_key_service.delete_for_provider(
+                conn,
+                user_id,
+                &McpServer::key_provider(&server.name),
+            )
+        })
+    }
+}
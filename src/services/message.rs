@@ -1,10 +1,13 @@
 use anyhow::{Context, Result, bail};
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
 
 use crate::{
-    ai::{handler::StreamResult, provider::AiProvider},
-    models::message::{Changeset, CreateArgs, Message, UpdateArgs},
+    ai::handler::StreamResult,
+    models::message::{
+        Changeset, Citation, CreateArgs, Message, MessageMode, MessagePart, ReplyTo, ResubmitArgs,
+        SelectArgs, UpdateArgs,
+    },
     repositories::{Repository, chat::ChatRepository, message::MessageRepository},
 };
 
@@ -63,19 +66,54 @@ impl MessageService {
             bail!("Forbidden: You cannot post messages in this chat.");
         }
 
+        let parent_id = match args.parent_id {
+            Some(parent_id) => {
+                let parent = self
+                    .message_repo
+                    .find_by_id(conn, &parent_id)?
+                    .context(format!("Message {} not found", parent_id))?;
+                if parent.chat_id != args.chat_id {
+                    bail!("Message {} is in another chat", parent_id);
+                }
+                Some(parent_id)
+            }
+            None if args.root => None,
+            None => {
+                let messages = self.message_repo.find_by_chat(conn, &args.chat_id)?;
+                Message::active_path(messages).pop().map(|m| m.id)
+            }
+        };
+        let compare_group = args.compare_model.as_ref().and(parent_id.clone());
+
         let message = Message {
             id: args.id,
             chat_id: args.chat_id,
+            parent_id,
             user_id: user_id.to_string(),
             role: args.role,
             body: args.body,
             reasoning: args.reasoning,
+            parts: Message::encode_parts(&args.parts),
+            citations: Message::encode_citations(&args.citations),
+            // a compare's replies wait for one of them to be picked
+            selected: compare_group.is_none(),
+            model: args.compare_model,
+            compare_group,
             version: 1,
             created_at: args.created_at.naive_utc(),
             updated_at: args.updated_at.naive_utc(),
         };
 
-        self.message_repo.create(conn, &message)
+        let message = self.message_repo.create(conn, &message)?;
+        // A new branch is the one shown.
+        if message.selected {
+            if message.role == "assistant" {
+                self.adopt_follow_ups(conn, &message, message.created_at)?;
+            }
+            self.deselect_siblings(conn, &message, message.created_at)?;
+        }
+
+        Ok(message)
     }
 
     pub fn update(
@@ -91,6 +129,8 @@ impl MessageService {
                 body: args.body,
                 version: existing.version + 1,
                 reasoning: None,
+                selected: None,
+                parent_id: None,
                 updated_at: args.updated_at.naive_utc(),
             };
 
@@ -106,12 +146,172 @@ impl MessageService {
         conn.transaction(|conn| {
             let message = self.check_ownership(conn, id, user_id)?;
 
+            // Its replies move up to its parent rather than being cut off from the chat, after which
+            // one message at that level is left selected.
+            let now = Utc::now().naive_utc();
+            let siblings: Vec<Message> = self
+                .message_repo
+                .find_siblings_for_update(conn, &message.chat_id, message.parent_id.as_deref())?
+                .into_iter()
+                .filter(|m| m.id != message.id)
+                .collect();
+            let mut replies = Vec::new();
+            for child in self.message_repo.find_children(conn, id)? {
+                replies.push(self.set_parent(conn, &child, message.parent_id.clone(), now)?);
+            }
+            replies.sort_by_key(|m| m.created_at);
+
             self.message_repo.delete(conn, id)?;
 
+            let keep = Message::selected_after_delete(&siblings, &replies).map(str::to_owned);
+            for other in siblings.iter().chain(&replies) {
+                let pick = keep.as_deref() == Some(other.id.as_str());
+                if other.selected != pick {
+                    self.set_selected(conn, other, pick, now)?;
+                }
+            }
+
             Ok(message)
         })
     }
 
+    // Switches to another branch, e.g. an earlier answer or one of the replies of a compare.
+    pub fn select(
+        &self,
+        conn: &mut MysqlConnection,
+        args: &SelectArgs,
+        user_id: &str,
+    ) -> Result<Message> {
+        conn.transaction(|conn| {
+            self.select_in_group(conn, &args.id, args.updated_at.naive_utc(), false, user_id)
+        })
+    }
+
+    // Picks a reply once every model of a compare has answered, unless the user got there first.
+    pub fn select_default(
+        &self,
+        conn: &mut MysqlConnection,
+        id: &str,
+        user_id: &str,
+    ) -> Result<Message> {
+        conn.transaction(|conn| {
+            self.select_in_group(conn, id, Utc::now().naive_utc(), true, user_id)
+        })
+    }
+
+    fn select_in_group(
+        &self,
+        conn: &mut MysqlConnection,
+        id: &str,
+        now: NaiveDateTime,
+        keep_existing: bool,
+        user_id: &str,
+    ) -> Result<Message> {
+        let message = self.check_ownership(conn, id, user_id)?;
+        if message.role == "assistant" {
+            self.adopt_follow_ups(conn, &message, now)?;
+        }
+
+        let siblings = self.message_repo.find_siblings_for_update(
+            conn,
+            &message.chat_id,
+            message.parent_id.as_deref(),
+        )?;
+        if keep_existing && siblings.iter().any(|m| m.selected) {
+            return Ok(message);
+        }
+
+        let mut selected = message;
+        for sibling in siblings {
+            let pick = sibling.id == id;
+            if sibling.selected == pick {
+                continue;
+            }
+
+            let updated = self.set_selected(conn, &sibling, pick, now)?;
+            if pick {
+                selected = updated;
+            }
+        }
+
+        Ok(selected)
+    }
+
+    // A user message sent while the reply to the one before it was still being written hangs off
+    // that message, next to where the reply ends up. Once the reply is in, the message moves under
+    // it so the conversation reads in order.
+    fn adopt_follow_ups(
+        &self,
+        conn: &mut MysqlConnection,
+        reply: &Message,
+        now: NaiveDateTime,
+    ) -> Result<()> {
+        let Some(parent) = reply.parent_id.as_deref() else {
+            return Ok(());
+        };
+        for sibling in self.message_repo.find_children(conn, parent)? {
+            if sibling.role == "user" {
+                self.set_parent(conn, &sibling, Some(reply.id.clone()), now)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_parent(
+        &self,
+        conn: &mut MysqlConnection,
+        message: &Message,
+        parent_id: Option<String>,
+        now: NaiveDateTime,
+    ) -> Result<Message> {
+        let changeset = Changeset {
+            body: None,
+            reasoning: None,
+            selected: None,
+            parent_id: Some(parent_id),
+            version: message.version + 1,
+            updated_at: now,
+        };
+        self.message_repo.update(conn, &message.id, changeset)
+    }
+
+    fn deselect_siblings(
+        &self,
+        conn: &mut MysqlConnection,
+        message: &Message,
+        now: NaiveDateTime,
+    ) -> Result<()> {
+        let siblings = self.message_repo.find_siblings_for_update(
+            conn,
+            &message.chat_id,
+            message.parent_id.as_deref(),
+        )?;
+        for sibling in siblings {
+            if sibling.selected && sibling.id != message.id {
+                self.set_selected(conn, &sibling, false, now)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_selected(
+        &self,
+        conn: &mut MysqlConnection,
+        message: &Message,
+        selected: bool,
+        now: NaiveDateTime,
+    ) -> Result<Message> {
+        let changeset = Changeset {
+            body: None,
+            reasoning: None,
+            selected: Some(selected),
+            parent_id: None,
+            version: message.version + 1,
+            updated_at: now,
+        };
+        self.message_repo.update(conn, &message.id, changeset)
+    }
+
     pub fn list_for_chat(
         &self,
         conn: &mut MysqlConnection,
@@ -130,13 +330,95 @@ impl MessageService {
         self.message_repo.find_by_chat(conn, chat_id)
     }
 
+    // Saves the edit as a sibling of the original, which makes it the branch shown.
+    pub fn resubmit(
+        &self,
+        conn: &mut MysqlConnection,
+        args: &ResubmitArgs,
+        user_id: &str,
+    ) -> Result<Message> {
+        let original = self.check_ownership(conn, &args.id, user_id)?;
+        if original.role != "user" {
+            bail!("Only user messages can be edited and resubmitted");
+        }
+
+        self.create(conn, CreateArgs::resubmit(original, args), user_id)
+    }
+
+    // The message an assistant message answered, for a new answer to be generated to.
+    pub fn regenerate_target(
+        &self,
+        conn: &mut MysqlConnection,
+        id: &str,
+        user_id: &str,
+    ) -> Result<Message> {
+        let message = self.check_ownership(conn, id, user_id)?;
+        if message.role != "assistant" {
+            bail!("Only assistant messages can be regenerated");
+        }
+        let parent_id = message
+            .parent_id
+            .context(format!("Message {} has nothing to answer", id))?;
+
+        self.check_ownership(conn, &parent_id, user_id)
+    }
+
+    pub fn exists(&self, conn: &mut MysqlConnection, id: &str) -> Result<bool> {
+        Ok(self.message_repo.find_by_id(conn, id)?.is_some())
+    }
+
+    // The messages leading up to and including `leaf`, oldest first.
+    pub fn thread(
+        &self,
+        conn: &mut MysqlConnection,
+        chat_id: &str,
+        leaf: &str,
+        user_id: &str,
+    ) -> Result<Vec<Message>> {
+        let messages = self.list_for_chat(conn, chat_id, user_id)?;
+        Ok(Message::thread(messages, leaf))
+    }
+
+    // Matching messages with the title of the chat they are in.
+    pub fn search(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<(Message, Option<String>)>> {
+        let found = self.message_repo.search(conn, user_id, query, limit)?;
+
+        let chat_ids: Vec<&str> = found.iter().map(|m| m.chat_id.as_str()).collect();
+        let chats = self.chat_repo.find_by_ids(conn, &chat_ids)?;
+
+        Ok(found
+            .into_iter()
+            .map(|m| {
+                let title = chats
+                    .iter()
+                    .find(|c| c.id == m.chat_id)
+                    .and_then(|c| c.title.clone());
+                (m, title)
+            })
+            .collect())
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn save_assistant_reply(
         &self,
         conn: &mut MysqlConnection,
         chat_id: &str,
         reply: StreamResult,
+        parts: Vec<MessagePart>,
+        citations: Vec<Citation>,
+        reply_to: Option<ReplyTo>,
         user_id: &str,
     ) -> Result<Message> {
+        let (parent_id, compare_model) = match reply_to {
+            Some(reply_to) => (Some(reply_to.parent_id), reply_to.compare_model),
+            None => (None, None),
+        };
         let now = Utc::now();
 
         let args = CreateArgs {
@@ -145,6 +427,14 @@ impl MessageService {
             role: "assistant".to_owned(),
             body: reply.content,
             reasoning: reply.reasoning,
+            attachment_ids: Vec::new(),
+            mode: MessageMode::Chat,
+            parts,
+            citations,
+            parent_id,
+            root: false,
+            compare: Vec::new(),
+            compare_model,
             created_at: now,
             updated_at: now,
         };
@@ -156,17 +446,30 @@ impl MessageService {
         &self,
         conn: &mut MysqlConnection,
         chat_id: &str,
-        provider: AiProvider,
+        reason: &str,
+        reply_to: Option<ReplyTo>,
         user_id: &str,
     ) -> Result<Message> {
+        let (parent_id, compare_model) = match reply_to {
+            Some(reply_to) => (Some(reply_to.parent_id), reply_to.compare_model),
+            None => (None, None),
+        };
         let now = Utc::now();
 
         let args = CreateArgs {
             id: uuid::Uuid::new_v4().to_string(),
             chat_id: chat_id.to_owned(),
             role: "assistant".into(),
-            body: format!("Error: Missing API key for {}", provider),
+            body: format!("Error: {reason}"),
             reasoning: None,
+            attachment_ids: Vec::new(),
+            mode: MessageMode::Chat,
+            parts: Vec::new(),
+            citations: Vec::new(),
+            parent_id,
+            root: false,
+            compare: Vec::new(),
+            compare_model,
             created_at: now,
             updated_at: now,
         };
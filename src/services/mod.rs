@@ -1,8 +1,17 @@
 pub mod active_model;
 pub mod api_key;
+pub mod attachment;
+pub mod budget;
 pub mod chat;
 pub mod container;
+pub mod custom_endpoint;
+pub mod generation_registry;
+pub mod job_queue;
+pub mod mcp_server;
 pub mod message;
+pub mod model_catalog;
 pub mod replicache;
 pub mod shared_chat;
 pub mod sse_manager;
+pub mod system_prompt;
+pub mod usage;
@@ -0,0 +1,226 @@
+use anyhow::{Context, Result, bail};
+use diesel::MysqlConnection;
+use futures::future::join_all;
+use secrecy::SecretString;
+use tokio::runtime::Handle;
+use tower_sessions_redis_store::fred::prelude::{Expiration, KeysInterface};
+
+use crate::{
+    ai::{
+        provider::{AiProvider, ProviderSetup, Sampling},
+        registry,
+    },
+    app::AppState,
+    dtos::model_catalog::CatalogModel,
+    models::custom_endpoint::CustomEndpoint,
+};
+
+const VENDORS: [AiProvider; 4] = [
+    AiProvider::OpenAi,
+    AiProvider::Anthropic,
+    AiProvider::Google,
+    AiProvider::OpenRouter,
+];
+
+// A place the user can list models from. Custom endpoint ids are prefixed with the endpoint
+// name, the same way they are stored on active models.
+pub struct CatalogSource {
+    cache_key: String,
+    prefix: Option<String>,
+    setup: ProviderSetup,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModelCatalogService {
+    ttl_secs: i64,
+}
+
+impl ModelCatalogService {
+    pub fn new(ttl_secs: u64) -> Self {
+        Self {
+            ttl_secs: ttl_secs as i64,
+        }
+    }
+
+    // Vendors the user stored a key for, plus all of their custom endpoints.
+    pub fn sources(
+        &self,
+        state: &AppState,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+    ) -> Result<Vec<CatalogSource>> {
+        let mut sources = Vec::new();
+
+        for provider in VENDORS {
+            if let Ok(api_key) = state.service_container.api_key_service.get_and_decrypt(
+                conn,
+                user_id,
+                &provider.to_string(),
+            ) {
+                sources.push(vendor_source(user_id, provider, api_key));
+            }
+        }
+
+        let endpoints = state
+            .service_container
+            .custom_endpoint_service
+            .list(conn, user_id)?;
+        for endpoint in endpoints {
+            sources.push(custom_source(state, conn, user_id, endpoint));
+        }
+
+        Ok(sources)
+    }
+
+    pub async fn list(&self, state: &AppState, sources: Vec<CatalogSource>) -> Vec<CatalogModel> {
+        let lists = join_all(sources.iter().map(|s| self.models(state, s))).await;
+
+        sources
+            .iter()
+            .zip(lists)
+            .filter_map(|(source, list)| match list {
+                Ok(models) => Some(models.into_iter().map(|id| CatalogModel {
+                    provider: source.setup.provider.to_string(),
+                    capabilities: registry::lookup(&source.setup.provider, &id),
+                    id,
+                })),
+                Err(e) => {
+                    tracing::warn!(source = source.cache_key, "Failed to list models: {e:?}");
+                    None
+                }
+            })
+            .flatten()
+            .collect()
+    }
+
+    // Called from the blocking push path, so the lookup is driven on the current runtime. It can go
+    // over the network, so mutations call it from prepare, outside their transaction.
+    pub fn ensure_listed(
+        &self,
+        state: &AppState,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+        provider: &str,
+        model: &str,
+    ) -> Result<()> {
+        let provider: AiProvider = provider
+            .parse()
+            .with_context(|| format!("Invalid AI provider: '{provider}'"))?;
+
+        let source = match provider {
+            AiProvider::Custom => {
+                let (name, _) = model
+                    .split_once('/')
+                    .with_context(|| format!("Invalid custom model: '{model}'"))?;
+                let endpoint = state
+                    .service_container
+                    .custom_endpoint_service
+                    .get_by_name(conn, user_id, name)
+                    .with_context(|| format!("Unknown custom endpoint: '{name}'"))?;
+                custom_source(state, conn, user_id, endpoint)
+            }
+            provider => {
+                let api_key = state
+                    .service_container
+                    .api_key_service
+                    .get_and_decrypt(conn, user_id, &provider.to_string())
+                    .with_context(|| format!("API key missing for provider {provider}"))?;
+                vendor_source(user_id, provider, api_key)
+            }
+        };
+
+        let models = Handle::current().block_on(self.models(state, &source))?;
+        if !models.iter().any(|m| m == model) {
+            bail!("Unknown {} model: '{model}'", source.setup.provider);
+        }
+
+        Ok(())
+    }
+
+    async fn models(&self, state: &AppState, source: &CatalogSource) -> Result<Vec<String>> {
+        let cached: Option<String> = state
+            .cache
+            .get(&source.cache_key)
+            .await
+            .context("Failed to read model list from cache")?;
+        if let Some(json) = cached {
+            return serde_json::from_str(&json).context("Failed to decode cached model list");
+        }
+
+        let provider = source.setup.resolve(&state.config.providers)?;
+        let models: Vec<String> = match provider.list_models(&source.setup.api_key).await {
+            Ok(models) => models
+                .into_iter()
+                .map(|id| match &source.prefix {
+                    Some(prefix) => format!("{prefix}/{id}"),
+                    None => id,
+                })
+                .collect(),
+            // Not cached, so the provider is asked again on the next lookup.
+            Err(e) => {
+                tracing::warn!(
+                    source = source.cache_key,
+                    "Model listing failed, using known models: {e:?}"
+                );
+                return Ok(provider.models().iter().map(|m| m.to_string()).collect());
+            }
+        };
+
+        let json = serde_json::to_string(&models)?;
+        state
+            .cache
+            .set::<(), _, _>(
+                &source.cache_key,
+                json,
+                Some(Expiration::EX(self.ttl_secs)),
+                None,
+                false,
+            )
+            .await
+            .context("Failed to cache model list")?;
+
+        Ok(models)
+    }
+}
+
+fn vendor_source(user_id: &str, provider: AiProvider, api_key: SecretString) -> CatalogSource {
+    CatalogSource {
+        cache_key: format!("models/{user_id}/{provider}"),
+        prefix: None,
+        setup: ProviderSetup {
+            provider,
+            model: String::new(),
+            effort: None,
+            sampling: Sampling::default(),
+            api_key,
+            endpoint: None,
+        },
+    }
+}
+
+// Keyed by endpoint id so a re-registered endpoint with the same name isn't served stale models.
+fn custom_source(
+    state: &AppState,
+    conn: &mut MysqlConnection,
+    user_id: &str,
+    endpoint: CustomEndpoint,
+) -> CatalogSource {
+    let api_key = state
+        .service_container
+        .custom_endpoint_service
+        .get_api_key(conn, user_id, &endpoint)
+        .unwrap_or_else(|| SecretString::from(String::new()));
+
+    CatalogSource {
+        cache_key: format!("models/{user_id}/custom:{}", endpoint.id),
+        prefix: Some(endpoint.name.clone()),
+        setup: ProviderSetup {
+            provider: AiProvider::Custom,
+            model: String::new(),
+            effort: None,
+            sampling: Sampling::default(),
+            api_key,
+            endpoint: Some(endpoint),
+        },
+    }
+}
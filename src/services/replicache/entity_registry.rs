@@ -1,6 +1,9 @@
 use crate::{
     dtos,
-    repositories::{Repository, active_model::ActiveModelRepository, chat::ChatRepository},
+    repositories::{
+        Repository, active_model::ActiveModelRepository, attachment::AttachmentRepository,
+        chat::ChatRepository, system_prompt::SystemPromptRepository,
+    },
 };
 use std::collections::HashMap;
 
@@ -46,6 +49,14 @@ impl EntityRegistry {
             "activeModel",
             make_patch_fn!(ActiveModelRepository, dtos::active_model::ActiveModel),
         );
+        registry.register(
+            "systemPrompt",
+            make_patch_fn!(SystemPromptRepository, dtos::system_prompt::SystemPrompt),
+        );
+        registry.register(
+            "attachment",
+            make_patch_fn!(AttachmentRepository, dtos::attachment::Attachment),
+        );
 
         registry
     }
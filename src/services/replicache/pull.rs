@@ -207,6 +207,14 @@ fn collect_all_entities(
             pinned_at: cwm.pinned_at,
             created_at: cwm.created_at,
             updated_at: cwm.updated_at,
+            system_prompt: cwm.system_prompt,
+            summary: cwm.summary,
+            summary_message_id: cwm.summary_message_id,
+            provider: cwm.provider,
+            model: cwm.model,
+            reasoning: cwm.reasoning,
+            temperature: cwm.temperature,
+            top_p: cwm.top_p,
         };
         chats.push(chat);
         messages.extend(cwm.messages);
@@ -218,6 +226,16 @@ fn collect_all_entities(
         &mut map,
         services.active_model_service.list_for_user(conn, user_id)?,
     );
+    collect_entity_type(
+        &mut map,
+        services
+            .system_prompt_service
+            .list_for_user(conn, user_id)?,
+    );
+    collect_entity_type(
+        &mut map,
+        services.attachment_service.list_for_user(conn, user_id)?,
+    );
 
     Ok(map)
 }
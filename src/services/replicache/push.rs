@@ -19,15 +19,21 @@ pub fn process_mutations(
     let mut conn = state.db_pool.get().context("Failed to get DB connection")?;
 
     for mutation in mutations {
+        let prepared = parse_mutation(mutation.clone())
+            .context("Failed to parse raw mutation")
+            .and_then(|parsed| parsed.prepare(&state, &mut conn, current_user_id));
+
         let result: Result<()> = conn.transaction(|conn| {
-            let normal_result = process_single_mutation(
-                state.clone(),
-                conn,
-                mutation,
-                client_group_id,
-                current_user_id,
-                false, // error_mode = false
-            );
+            let normal_result = prepared.and_then(|()| {
+                process_single_mutation(
+                    state.clone(),
+                    conn,
+                    mutation,
+                    client_group_id,
+                    current_user_id,
+                    false, // error_mode = false
+                )
+            });
 
             if let Err(e) = normal_result {
                 tracing::warn!(
@@ -5,22 +5,48 @@ use uuid::Uuid;
 
 use crate::{
     models::{
+        attachment::Attachment,
+        message::Message,
         shared_chat::{SharedChat, SharedChatWithMessages},
         shared_message::SharedMessage,
     },
     repositories::{shared_chat::SharedChatRepository, shared_message::SharedMessageRepository},
-    services::{chat::ChatService, message::MessageService},
+    services::{attachment::AttachmentService, chat::ChatService, message::MessageService},
 };
 
 #[derive(Debug, Clone)]
 pub struct SharedChatService {
     chat_svc: ChatService,
     msg_svc: MessageService,
+    attachment_svc: AttachmentService,
 }
 
 impl SharedChatService {
-    pub fn new(chat_svc: ChatService, msg_svc: MessageService) -> Self {
-        Self { chat_svc, msg_svc }
+    pub fn new(
+        chat_svc: ChatService,
+        msg_svc: MessageService,
+        attachment_svc: AttachmentService,
+    ) -> Self {
+        Self {
+            chat_svc,
+            msg_svc,
+            attachment_svc,
+        }
+    }
+
+    // Attachments stay with the original messages, so a snapshot shows whatever of them still
+    // exists.
+    fn attachments_for(
+        &self,
+        conn: &mut MysqlConnection,
+        messages: &[SharedMessage],
+    ) -> Result<Vec<Attachment>> {
+        let source_ids: Vec<&str> = messages
+            .iter()
+            .filter_map(|m| m.source_message_id.as_deref())
+            .collect();
+
+        self.attachment_svc.list_for_messages(conn, &source_ids)
     }
 
     pub fn get(
@@ -33,15 +59,41 @@ impl SharedChatService {
 
         let messages: Vec<SharedMessage> =
             SharedMessageRepository::list_for_shared_chat(conn, shared_chat_id)?;
+        let attachments = self.attachments_for(conn, &messages)?;
 
         Ok(SharedChatWithMessages {
             id: chat.id,
             title: chat.title,
             created_at: chat.created_at,
             messages,
+            attachments,
         })
     }
 
+    // Only attachments of messages in the snapshot are public.
+    pub fn get_attachment(
+        &self,
+        conn: &mut MysqlConnection,
+        shared_chat_id: &str,
+        attachment_id: &str,
+    ) -> Result<Attachment> {
+        let chat: SharedChat =
+            SharedChatRepository::get(conn, shared_chat_id).context("shared chat not found")?;
+        let attachment = self
+            .attachment_svc
+            .get(conn, attachment_id, &chat.owner_user_id)?;
+
+        let messages = SharedMessageRepository::list_for_shared_chat(conn, shared_chat_id)?;
+        let shared = messages
+            .iter()
+            .any(|m| m.source_message_id.is_some() && m.source_message_id == attachment.message_id);
+        if !shared {
+            bail!("Attachment {attachment_id} is not part of shared chat {shared_chat_id}");
+        }
+
+        Ok(attachment)
+    }
+
     pub fn create(
         &self,
         conn: &mut MysqlConnection,
@@ -50,7 +102,9 @@ impl SharedChatService {
     ) -> Result<SharedChatWithMessages> {
         conn.transaction(|tx| {
             let private_chat = self.chat_svc.get(tx, chat_id, user_id)?;
-            let private_messages = self.msg_svc.list_for_chat(tx, chat_id, user_id)?;
+            // only the branch being shown is shared
+            let private_messages =
+                Message::active_path(self.msg_svc.list_for_chat(tx, chat_id, user_id)?);
 
             let shared_chat_id = Uuid::new_v4().to_string();
             let new_shared_chat = SharedChat {
@@ -67,9 +121,11 @@ impl SharedChatService {
                 .map(|m| SharedMessage {
                     id: Uuid::new_v4().to_string(),
                     shared_chat_id: shared_chat_id.clone(),
+                    source_message_id: Some(m.id),
                     role: m.role,
                     body: m.body,
                     reasoning: m.reasoning,
+                    citations: m.citations,
                     created_at: m.created_at,
                 })
                 .collect();
@@ -78,12 +134,14 @@ impl SharedChatService {
 
             let chat = SharedChatRepository::get(tx, &shared_chat_id)?;
             let msgs = SharedMessageRepository::list_for_shared_chat(tx, &shared_chat_id)?;
+            let attachments = self.attachments_for(tx, &msgs)?;
 
             Ok(SharedChatWithMessages {
                 id: chat.id,
                 title: chat.title,
                 created_at: chat.created_at,
                 messages: msgs,
+                attachments,
             })
         })
     }
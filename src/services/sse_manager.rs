@@ -1,11 +1,14 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
 use std::sync::Arc;
 use tokio::sync::{RwLock, broadcast};
-use tracing::{debug, info};
+use tower_sessions_redis_store::fred::prelude::Pool;
+use tracing::{debug, info, warn};
+
+use crate::infra;
 
 // make it massive for now. we have all of the backlog available for a chat. so if we lag, we
 // should identify the sequence number that we started to fail at, then start sending from the
@@ -13,6 +16,10 @@ use tracing::{debug, info};
 // probably still needs more thought.
 const CHANNEL_CAP: usize = 2000;
 
+// Events are sent through redis so they reach the web process the user is connected to, whichever
+// process produced them.
+const RELAY_CHANNEL: &str = "sse-events";
+
 #[derive(Debug, Clone)]
 struct ChatBacklog {
     msgs: VecDeque<SseMessage>,
@@ -30,11 +37,15 @@ impl ChatBacklog {
     }
 }
 
+// A chat and, for the replies of a compare, the model the events belong to. Each compare reply
+// streams and finishes on its own, so each keeps its own backlog.
+type StreamKey = (String, Option<String>);
+
 #[derive(Debug, Clone)]
 struct UserStream {
     tx: broadcast::Sender<SseMessage>,
-    backlogs: HashMap<String, ChatBacklog>,
-    open_chats: HashSet<String>,
+    backlogs: HashMap<StreamKey, ChatBacklog>,
+    open_chats: HashSet<StreamKey>,
 }
 
 impl UserStream {
@@ -50,27 +61,33 @@ impl UserStream {
     fn push(&mut self, msg: SseMessage) {
         let _ = self.tx.send(msg.clone());
 
-        if let Some(chat_id) = extract_chat_id(&msg) {
+        if let Some(key) = extract_stream_key(&msg) {
             let backlog = self
                 .backlogs
-                .entry(chat_id.to_owned())
+                .entry(key)
                 .or_insert_with(|| ChatBacklog::new());
 
             backlog.push(msg);
         }
     }
 
-    fn mark_chat_open(&mut self, chat_id: &str) {
-        self.open_chats.insert(chat_id.to_owned());
+    fn mark_chat_open(&mut self, key: StreamKey) {
+        self.open_chats.insert(key);
     }
 
-    fn mark_chat_closed(&mut self, chat_id: &str) {
-        self.open_chats.remove(chat_id);
-        if let Some(backlog) = self.backlogs.remove(chat_id) {
+    fn mark_chat_closed(&mut self, key: &StreamKey) {
+        self.open_chats.remove(key);
+        if let Some(backlog) = self.backlogs.remove(key) {
             drop(backlog);
         }
     }
 
+    // Every stream of the chat, including all the replies of a compare.
+    fn mark_all_closed(&mut self, chat_id: &str) {
+        self.open_chats.retain(|(c_id, _)| c_id != chat_id);
+        self.backlogs.retain(|(c_id, _), _| c_id != chat_id);
+    }
+
     fn all_chats_closed(&self) -> bool {
         self.open_chats.is_empty()
     }
@@ -84,7 +101,7 @@ impl UserStream {
 }
 
 // make this tagged
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum EventType {
     #[serde(rename = "chat-stream-chunk")]
     Chunk,
@@ -94,6 +111,19 @@ pub enum EventType {
     Err,
     #[serde(rename = "chat-stream-exit")]
     Exit,
+    #[serde(rename = "chat-stream-cancelled")]
+    Cancelled,
+    #[serde(rename = "chat-stream-progress")]
+    Progress,
+    #[serde(rename = "chat-stream-queued")]
+    Queued,
+    // A generation is being retried, everything sent for the chat before it is void.
+    #[serde(rename = "chat-stream-reset")]
+    Reset,
+    #[serde(rename = "chat-stream-tool-call")]
+    ToolCall,
+    #[serde(rename = "chat-stream-tool-result")]
+    ToolResult,
     #[serde(rename = "replicache-poke")]
     Replicache,
 }
@@ -105,7 +135,7 @@ impl std::fmt::Display for EventType {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SseMessage {
     #[serde(rename = "type")]
     pub event_type: EventType,
@@ -113,14 +143,38 @@ pub struct SseMessage {
     pub data: Option<Value>,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize)]
+struct RelayedMessage {
+    user_id: String,
+    msg: SseMessage,
+}
+
+#[derive(Debug, Clone)]
 pub struct SseManager {
     inner: Arc<RwLock<HashMap<String, UserStream>>>,
+    cache: Pool,
 }
 
 impl SseManager {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(cache: Pool) -> Self {
+        Self {
+            inner: Arc::default(),
+            cache,
+        }
+    }
+
+    // Delivers the events published by every process to the clients connected to this one.
+    pub async fn run_relay(self: Arc<Self>) {
+        infra::redis::subscribe(self.cache.clone(), RELAY_CHANNEL, |payload| {
+            let manager = self.clone();
+            async move {
+                match serde_json::from_str::<RelayedMessage>(&payload) {
+                    Ok(relayed) => manager.deliver(&relayed.user_id, relayed.msg).await,
+                    Err(e) => warn!(error = ?e, "Dropping malformed relayed event"),
+                }
+            }
+        })
+        .await;
     }
 
     pub async fn add_client(
@@ -139,6 +193,20 @@ impl SseManager {
     }
 
     pub async fn send_to_user(&self, user_id: &str, msg: SseMessage) {
+        let relayed = RelayedMessage {
+            user_id: user_id.to_owned(),
+            msg,
+        };
+        let payload = serde_json::to_string(&relayed).expect("Failed to serialize SseMessage");
+
+        // Without redis, at least the clients of this process still get it.
+        if let Err(e) = infra::redis::publish(&self.cache, RELAY_CHANNEL, payload).await {
+            warn!(error = ?e, "Failed to relay event");
+            self.deliver(user_id, relayed.msg).await;
+        }
+    }
+
+    async fn deliver(&self, user_id: &str, msg: SseMessage) {
         let mut guard = self.inner.write().await;
         if let Some(stream) = guard.get_mut(user_id) {
             update_chat_state(stream, &msg);
@@ -158,6 +226,16 @@ impl SseManager {
         }
     }
 
+    // Drops the chat's backlog, so clients reconnecting during the retry don't replay the failed
+    // attempt, and tells connected ones to do the same.
+    pub async fn reset_chat(&self, user_id: &str, chat_id: &str, attempt_id: &str) {
+        let msg = SseMessage {
+            event_type: EventType::Reset,
+            data: Some(serde_json::json!({ "chat_id": chat_id, "attempt_id": attempt_id })),
+        };
+        self.send_to_user(user_id, msg).await;
+    }
+
     pub async fn replicache_poke(&self, user_id: &str) {
         let msg = SseMessage {
             event_type: EventType::Replicache,
@@ -168,24 +246,82 @@ impl SseManager {
 }
 
 fn update_chat_state(stream: &mut UserStream, msg: &SseMessage) {
-    let chat_id = msg
-        .data
-        .as_ref()
-        .and_then(|v| v.get("chat_id"))
-        .and_then(|v| v.as_str());
-
-    if let Some(c_id) = chat_id {
+    if let Some(key) = extract_stream_key(msg) {
         match msg.event_type {
-            EventType::Chunk => stream.mark_chat_open(c_id),
-            EventType::Done | EventType::Err => stream.mark_chat_closed(c_id),
+            EventType::Chunk => stream.mark_chat_open(key),
+            EventType::Done | EventType::Err | EventType::Cancelled => {
+                stream.mark_chat_closed(&key)
+            }
+            EventType::Reset => stream.mark_all_closed(&key.0),
             _ => {}
         }
     }
 }
 
-fn extract_chat_id(msg: &SseMessage) -> Option<&str> {
-    msg.data
-        .as_ref()
-        .and_then(|v| v.get("chat_id"))
-        .and_then(|v| v.as_str())
+fn extract_stream_key(msg: &SseMessage) -> Option<StreamKey> {
+    let data = msg.data.as_ref()?;
+    let chat_id = data.get("chat_id")?.as_str()?;
+    let model = data.get("model").and_then(|v| v.as_str());
+    Some((chat_id.to_owned(), model.map(str::to_owned)))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn event(event_type: EventType, data: Value) -> SseMessage {
+        SseMessage {
+            event_type,
+            data: Some(data),
+        }
+    }
+
+    fn deliver(stream: &mut UserStream, msg: SseMessage) {
+        update_chat_state(stream, &msg);
+        stream.push(msg);
+    }
+
+    #[test]
+    fn a_finished_compare_reply_keeps_the_others_streaming() {
+        let mut stream = UserStream::new();
+        deliver(
+            &mut stream,
+            event(EventType::Chunk, json!({ "chat_id": "c", "model": "a" })),
+        );
+        deliver(
+            &mut stream,
+            event(EventType::Chunk, json!({ "chat_id": "c", "model": "b" })),
+        );
+        deliver(
+            &mut stream,
+            event(EventType::Done, json!({ "chat_id": "c", "model": "a" })),
+        );
+
+        let key = ("c".to_owned(), Some("b".to_owned()));
+        assert!(stream.open_chats.contains(&key));
+        assert_eq!(stream.backlogs[&key].msgs.len(), 1);
+        assert!(!stream.all_chats_closed());
+    }
+
+    #[test]
+    fn a_reset_closes_every_reply_of_the_chat() {
+        let mut stream = UserStream::new();
+        deliver(
+            &mut stream,
+            event(EventType::Chunk, json!({ "chat_id": "c", "model": "a" })),
+        );
+        deliver(
+            &mut stream,
+            event(EventType::Chunk, json!({ "chat_id": "c", "model": "b" })),
+        );
+        deliver(
+            &mut stream,
+            event(EventType::Reset, json!({ "chat_id": "c" })),
+        );
+
+        assert!(stream.all_chats_closed());
+        assert_eq!(stream.full_backlog_snapshot().len(), 1);
+    }
 }
@@ -0,0 +1,132 @@
+use crate::{
+    models::system_prompt::{Changeset, CreateArgs, SystemPrompt, UpdateArgs},
+    repositories::{Repository, system_prompt::SystemPromptRepository},
+};
+use anyhow::{Result, bail};
+use diesel::prelude::*;
+
+#[derive(Debug, Clone)]
+pub struct SystemPromptService {
+    repository: SystemPromptRepository,
+}
+
+impl SystemPromptService {
+    fn check_ownership(
+        &self,
+        conn: &mut MysqlConnection,
+        id: &str,
+        user_id: &str,
+    ) -> Result<SystemPrompt> {
+        let system_prompt = self
+            .repository
+            .find_by_id(conn, id)?
+            .ok_or(anyhow::anyhow!("Failed to find system_prompt"))?;
+
+        if system_prompt.user_id != user_id {
+            bail!("Forbidden: You do not have access to this system prompt.");
+        }
+
+        Ok(system_prompt)
+    }
+
+    pub fn new(repository: SystemPromptRepository) -> Self {
+        Self { repository }
+    }
+
+    pub fn create(
+        &self,
+        conn: &mut MysqlConnection,
+        args: CreateArgs,
+        user_id: &str,
+    ) -> Result<SystemPrompt> {
+        conn.transaction(|conn| {
+            // A user has one system prompt, creating another replaces what it says.
+            if let Some(existing) = self.repository.find_by_user_for_update(conn, user_id)? {
+                let changeset = Changeset {
+                    body: args.body,
+                    version: existing.version + 1,
+                    updated_at: args.updated_at.naive_utc(),
+                };
+                return self.repository.update(conn, &existing.id, changeset);
+            }
+
+            let system_prompt = SystemPrompt {
+                id: args.id,
+                user_id: user_id.to_string(),
+                body: args.body,
+                version: 1,
+                created_at: args.created_at.naive_utc(),
+                updated_at: args.updated_at.naive_utc(),
+            };
+
+            self.repository.create(conn, &system_prompt)
+        })
+    }
+
+    pub fn update(
+        &self,
+        conn: &mut MysqlConnection,
+        args: UpdateArgs,
+        user_id: &str,
+    ) -> Result<SystemPrompt> {
+        conn.transaction(|conn| {
+            let existing = self
+                .repository
+                .find_by_id_for_update(conn, &args.id)?
+                .ok_or_else(|| {
+                    anyhow::anyhow!(format!(
+                        "Failed to find existing system_prompt: {}",
+                        args.id
+                    ))
+                })?;
+
+            self.check_ownership(conn, &args.id, user_id)?;
+
+            let changeset = Changeset {
+                body: args.body,
+                version: existing.version + 1,
+                updated_at: args.updated_at.naive_utc(),
+            };
+
+            self.repository.update(conn, &args.id, changeset)
+        })
+    }
+
+    pub fn delete(
+        &self,
+        conn: &mut MysqlConnection,
+        id: &str,
+        user_id: &str,
+    ) -> Result<SystemPrompt> {
+        conn.transaction(|conn| {
+            let system_prompt = self
+                .repository
+                .find_by_id_for_update(conn, id)?
+                .ok_or_else(|| anyhow::anyhow!(format!("Failed to find system_prompt: {}", id)))?;
+
+            self.check_ownership(conn, id, user_id)?;
+
+            self.repository.delete(conn, id)?;
+
+            Ok(system_prompt)
+        })
+    }
+
+    pub fn list_for_user(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+    ) -> Result<Vec<SystemPrompt>> {
+        self.repository.find_by_user(conn, user_id)
+    }
+
+    pub fn get_for_user(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+    ) -> Result<Option<SystemPrompt>> {
+        // There is at most one.
+        let list = self.repository.find_by_user(conn, user_id)?;
+        Ok(list.into_iter().next())
+    }
+}
@@ -0,0 +1,47 @@
+use anyhow::{Context, Result};
+use chrono::{Days, NaiveDate};
+use diesel::prelude::*;
+
+use crate::{
+    models::message_usage::{CreateArgs, MessageUsage, UsageSummary},
+    repositories::message_usage::MessageUsageRepository,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct UsageService;
+
+impl UsageService {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn record(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+        args: CreateArgs,
+    ) -> Result<()> {
+        let new_usage = MessageUsage::build_new(user_id.to_owned(), args);
+        MessageUsageRepository::create(conn, &new_usage)
+    }
+
+    // Both bounds are inclusive days (UTC).
+    pub fn summary(
+        &self,
+        conn: &mut MysqlConnection,
+        user_id: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<UsageSummary> {
+        anyhow::ensure!(from <= to, "from must not be after to");
+
+        let start = from.and_hms_opt(0, 0, 0).context("start of day")?;
+        let end = to
+            .checked_add_days(Days::new(1))
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .context("end of day")?;
+
+        let rows = MessageUsageRepository::list_between(conn, user_id, start, end)?;
+        Ok(UsageSummary::build(from, to, &rows))
+    }
+}